@@ -0,0 +1,212 @@
+use crate::cucumber_runner::BrowserFlags;
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+const SETTINGS_PREFIX: &str = "browser_env.";
+/// Replaced by the delay of a slow motion run
+pub const SLOW_MO_PLACEHOLDER: &str = "{ms}";
+
+/// A variable the repo's hooks read to set up their browsers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EnvVar {
+    /// Empty when the repo has no variable for the flag
+    pub name: String,
+    pub value: String,
+}
+
+impl EnvVar {
+    fn new(name: &str, value: &str) -> Self {
+        EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Variables set for each browser flag of a run. Only `PWDEBUG` is Playwright's own, the
+/// defaults of the others are conventions that a repo's hooks may not follow.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BrowserEnv {
+    pub headed: EnvVar,
+    pub debug: EnvVar,
+    pub slow_mo: EnvVar,
+    pub keep_open: EnvVar,
+    pub trace: EnvVar,
+}
+
+impl Default for BrowserEnv {
+    fn default() -> Self {
+        BrowserEnv {
+            headed: EnvVar::new("HEADLESS", "false"),
+            debug: EnvVar::new("PWDEBUG", "1"),
+            slow_mo: EnvVar::new("SLOW_MO", SLOW_MO_PLACEHOLDER),
+            keep_open: EnvVar::new("KEEP_BROWSER_OPEN", "on-failure"),
+            trace: EnvVar::new("TRACE", "on"),
+        }
+    }
+}
+
+impl BrowserEnv {
+    /// Variables for the flags that are set, flags without a variable are left out
+    pub fn variables(&self, flags: &BrowserFlags) -> Vec<(String, String)> {
+        let slow_mo = flags.slow_mo_ms.map(|ms| {
+            let value = self
+                .slow_mo
+                .value
+                .replace(SLOW_MO_PLACEHOLDER, &ms.to_string());
+            EnvVar::new(&self.slow_mo.name, &value)
+        });
+        [
+            flags.headed.then_some(&self.headed),
+            flags.debug.then_some(&self.debug),
+            slow_mo.as_ref(),
+            flags.keep_open.then_some(&self.keep_open),
+            flags.trace.then_some(&self.trace),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|var| !var.name.is_empty())
+        .map(|var| (var.name.clone(), var.value.clone()))
+        .collect()
+    }
+
+    fn vars(&self) -> [&EnvVar; 5] {
+        [
+            &self.headed,
+            &self.debug,
+            &self.slow_mo,
+            &self.keep_open,
+            &self.trace,
+        ]
+    }
+}
+
+lazy_static! {
+    static ref CONFIGURED: RwLock<HashMap<String, BrowserEnv>> = RwLock::new(HashMap::new());
+}
+
+/// Use `env` for every run of the repo started from now on.
+pub fn configure(repo_path: &str, env: BrowserEnv) {
+    CONFIGURED
+        .write()
+        .unwrap()
+        .insert(repo_path.to_string(), env);
+}
+
+/// Variables configured for the repo, the defaults when it has none.
+pub fn for_repo(repo_path: &str) -> BrowserEnv {
+    CONFIGURED
+        .read()
+        .unwrap()
+        .get(repo_path)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Configure every repo that has saved variables.
+pub fn load_all(connection: &Connection) -> Result<(), String> {
+    let mut statement = connection
+        .prepare("SELECT key, value FROM settings WHERE key LIKE ?1 || '%'")
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(params![SETTINGS_PREFIX], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (key, value) = row.map_err(|e| e.to_string())?;
+        if let (Some(repo_path), Ok(env)) = (
+            key.strip_prefix(SETTINGS_PREFIX),
+            serde_json::from_str(&value),
+        ) {
+            configure(repo_path, env);
+        }
+    }
+    Ok(())
+}
+
+pub fn save(connection: &Connection, repo_path: &str, env: &BrowserEnv) -> Result<(), String> {
+    if let Some(var) = env
+        .vars()
+        .into_iter()
+        .find(|var| var.name.trim() != var.name || var.name.contains(['=', '\0']))
+    {
+        return Err(format!("Invalid variable name: {:?}", var.name));
+    }
+    let value = serde_json::to_string(env).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![format!("{}{}", SETTINGS_PREFIX, repo_path), value],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_variables_combine_flags() {
+        let flags = BrowserFlags {
+            headed: true,
+            slow_mo_ms: Some(250),
+            trace: true,
+            ..BrowserFlags::default()
+        };
+        assert_eq!(
+            BrowserEnv::default().variables(&flags),
+            env(&[("HEADLESS", "false"), ("SLOW_MO", "250"), ("TRACE", "on")])
+        );
+        assert!(BrowserEnv::default()
+            .variables(&BrowserFlags::default())
+            .is_empty());
+
+        // Renamed variables are used, flags without one set nothing
+        let custom = BrowserEnv {
+            headed: EnvVar::new("HEADED", "1"),
+            slow_mo: EnvVar::new("PW_SLOWMO", "{ms}ms"),
+            trace: EnvVar::default(),
+            ..BrowserEnv::default()
+        };
+        assert_eq!(
+            custom.variables(&flags),
+            env(&[("HEADED", "1"), ("PW_SLOWMO", "250ms")])
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let connection = database::open_in_memory();
+        let repo_path = "/repos/browser-env-test";
+        assert_eq!(for_repo(repo_path), BrowserEnv::default());
+
+        let custom = BrowserEnv {
+            keep_open: EnvVar::new("KEEP_OPEN", "true"),
+            ..BrowserEnv::default()
+        };
+        save(&connection, repo_path, &custom).unwrap();
+        load_all(&connection).unwrap();
+        assert_eq!(for_repo(repo_path), custom);
+
+        let invalid = BrowserEnv {
+            trace: EnvVar::new("TRACE=on", "on"),
+            ..BrowserEnv::default()
+        };
+        assert!(save(&connection, repo_path, &invalid).is_err());
+    }
+}
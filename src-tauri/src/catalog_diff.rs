@@ -0,0 +1,495 @@
+use crate::change_detector::ChangeKind;
+use crate::features_reader::{self, Feature, Scenario};
+use crate::gherkin_parser::{self, IdGenerator};
+use crate::organizations_reader::{self, Organizations, People, Teams};
+use crate::repo_source::RepoSource;
+use crate::steps_reader::{self, StepDefinition};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+// Layout of the e2e repo, the app reads the same folders
+pub const FEATURES_DIR: &str = "features";
+const DATA_DIR: &str = "data";
+
+/// Everything the app loads from an e2e repo, for one revision.
+pub struct Catalog {
+    pub features: Vec<Feature>,
+    pub scenarios: Vec<Scenario>,
+    pub examples: Vec<ExamplesBlock>,
+    pub people: People,
+    pub teams: Teams,
+    pub organizations: Organizations,
+    pub steps: Vec<StepDefinition>,
+    base_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExamplesBlock {
+    pub file_path: String,
+    pub scenario_name: String,
+    pub index: usize,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Feature,
+    Scenario,
+    Examples,
+    Tag,
+    Organization,
+    Team,
+    Person,
+    StepDefinition,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityChange {
+    pub kind: EntityKind,
+    pub key: String,
+    pub change: ChangeKind,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogDiff {
+    pub changes: Vec<EntityChange>,
+    pub summary: BTreeMap<EntityKind, ChangeCounts>,
+}
+
+/// Load the full catalog of the e2e repo at `source`. Organizations and step definitions
+/// are optional in older revisions, so a missing file yields an empty list instead of
+/// an error. A git tree is read in a single pass.
+pub async fn load_catalog(source: &RepoSource) -> Result<Catalog, String> {
+    let tree = source.clone();
+    let source = &tokio::task::spawn_blocking(move || {
+        tree.snapshot(&[FEATURES_DIR, DATA_DIR, steps_reader::STEP_DEFINITIONS_DIR])
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let features_source = source.subdir(FEATURES_DIR);
+    let (features, scenarios) = features_reader::get_features_from(&features_source)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (people, teams, organizations) =
+        organizations_reader::parse_from(&source.subdir(DATA_DIR)).unwrap_or_default();
+    let steps = steps_reader::parse_step_definitions_from(source).unwrap_or_default();
+
+    let mut examples = Vec::new();
+    for relative in features_source.list_files("", &["feature"])? {
+        let content = features_source.read_to_string(&relative)?;
+        let Ok(document) = gherkin_parser::parse(&relative, &content, &mut IdGenerator::new())
+        else {
+            continue;
+        };
+        let Some(feature) = document.feature else {
+            continue;
+        };
+        for scenario in feature.scenarios() {
+            for (index, block) in scenario.examples.iter().enumerate() {
+                let cells = |row: &gherkin_parser::TableRow| {
+                    row.cells.iter().map(|c| c.value.clone()).collect()
+                };
+                examples.push(ExamplesBlock {
+                    file_path: features_source.display_path(&relative),
+                    scenario_name: scenario.name.clone(),
+                    index,
+                    name: block.name.clone(),
+                    tags: block.tags.iter().map(|t| t.name.clone()).collect(),
+                    header: block.table_header.as_ref().map(cells).unwrap_or_default(),
+                    rows: block.table_body.iter().map(cells).collect(),
+                });
+            }
+        }
+    }
+
+    Ok(Catalog {
+        features,
+        scenarios,
+        examples,
+        people,
+        teams,
+        organizations,
+        steps,
+        base_path: source.base_path().to_string_lossy().into_owned(),
+    })
+}
+
+impl Catalog {
+    /// Path of a catalog file relative to the repo root
+    pub fn relative(&self, path: &str) -> String {
+        Path::new(path)
+            .strip_prefix(&self.base_path)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|_| path.to_string())
+    }
+
+    /// Entities keyed by stable identity (paths and names), with fields that only
+    /// identify or locate an entity stripped.
+    fn entities(&self) -> Vec<(EntityKind, String, Value)> {
+        let mut entities = Vec::new();
+        let feature_paths: HashMap<&str, String> = self
+            .features
+            .iter()
+            .map(|f| (f.id.as_str(), self.relative(&f.file_path)))
+            .collect();
+
+        for feature in &self.features {
+            let key = self.relative(&feature.file_path);
+            entities.push((
+                EntityKind::Feature,
+                key,
+                strip(json!(feature), &["id", "file_path", "authorship"]),
+            ));
+        }
+        for scenario in &self.scenarios {
+            let path = feature_paths
+                .get(scenario.feature_id.as_str())
+                .cloned()
+                .unwrap_or_default();
+            let key = format!("{} › {}", path, scenario.name);
+            let value = strip(
+                json!(scenario),
+                &["id", "feature_id", "line", "end_line", "authorship"],
+            );
+            entities.push((EntityKind::Scenario, key, value));
+        }
+        for block in &self.examples {
+            let key = format!(
+                "{} › {} › #{} {}",
+                self.relative(&block.file_path),
+                block.scenario_name,
+                block.index + 1,
+                block.name
+            );
+            let value = strip(json!(block), &["file_path", "scenario_name", "index"]);
+            entities.push((EntityKind::Examples, key.trim_end().to_string(), value));
+        }
+
+        let mut tag_usage: BTreeMap<&str, usize> = BTreeMap::new();
+        let tags = self.features.iter().flat_map(|f| f.tags.iter());
+        for tag in tags.chain(self.scenarios.iter().flat_map(|s| s.tags.iter())) {
+            *tag_usage.entry(tag).or_default() += 1;
+        }
+        for (tag, usage) in tag_usage {
+            entities.push((EntityKind::Tag, tag.to_string(), json!({ "usage": usage })));
+        }
+
+        for organization in &self.organizations {
+            let value = json!(organization);
+            let key = value["name"].as_str().unwrap_or_default().to_string();
+            entities.push((EntityKind::Organization, key, strip(value, &["name"])));
+        }
+        for team in &self.teams {
+            let value = json!(team);
+            let key = value["name"].as_str().unwrap_or_default().to_string();
+            entities.push((EntityKind::Team, key, strip(value, &["name"])));
+        }
+        for person in &self.people {
+            let value = json!(person);
+            let key = value["name"].as_str().unwrap_or_default().to_string();
+            entities.push((EntityKind::Person, key, strip(value, &["name"])));
+        }
+        for step in &self.steps {
+            let key = format!("{} {}", step.keyword, step.pattern);
+            let mut value = strip(json!(step), &["id", "line_number", "file_path"]);
+            value["file_path"] = json!(self.relative(&step.file_path));
+            entities.push((EntityKind::StepDefinition, key, value));
+        }
+
+        entities
+    }
+}
+
+/// Drop the given fields and sort string arrays, so set-like fields (tags, teams)
+/// compare equal regardless of order.
+fn strip(value: Value, fields: &[&str]) -> Value {
+    let Value::Object(object) = value else {
+        return value;
+    };
+    let object: Map<String, Value> = object
+        .into_iter()
+        .filter(|(key, _)| !fields.contains(&key.as_str()))
+        .map(|(key, value)| match value {
+            Value::Array(mut items) if items.iter().all(Value::is_string) => {
+                items.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                (key, Value::Array(items))
+            }
+            value => (key, value),
+        })
+        .collect();
+    Value::Object(object)
+}
+
+fn keyed(entities: Vec<(EntityKind, String, Value)>) -> BTreeMap<(EntityKind, String), Value> {
+    let mut keyed = BTreeMap::new();
+    for (kind, key, value) in entities {
+        // Duplicate names (e.g. two scenarios with the same title) get a numeric suffix
+        let mut unique = key.clone();
+        let mut n = 1;
+        while keyed.contains_key(&(kind, unique.clone())) {
+            n += 1;
+            unique = format!("{} ({})", key, n);
+        }
+        keyed.insert((kind, unique), value);
+    }
+    keyed
+}
+
+fn field_changes(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old = before.get(field).cloned().unwrap_or(Value::Null);
+            let new = after.get(field).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: field.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+/// Diff two catalogs by stable identity rather than the per-load UUIDs.
+pub fn diff_catalogs(before: &Catalog, after: &Catalog) -> CatalogDiff {
+    let old = keyed(before.entities());
+    let new = keyed(after.entities());
+
+    let mut changes = Vec::new();
+    for ((kind, key), value) in &new {
+        match old.get(&(*kind, key.clone())) {
+            None => changes.push(EntityChange {
+                kind: *kind,
+                key: key.clone(),
+                change: ChangeKind::Added,
+                fields: Vec::new(),
+            }),
+            Some(previous) => {
+                let fields = field_changes(previous, value);
+                if !fields.is_empty() {
+                    changes.push(EntityChange {
+                        kind: *kind,
+                        key: key.clone(),
+                        change: ChangeKind::Modified,
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+    for (kind, key) in old.keys() {
+        if !new.contains_key(&(*kind, key.clone())) {
+            changes.push(EntityChange {
+                kind: *kind,
+                key: key.clone(),
+                change: ChangeKind::Removed,
+                fields: Vec::new(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| (a.kind, &a.key).cmp(&(b.kind, &b.key)));
+
+    let mut summary: BTreeMap<EntityKind, ChangeCounts> = BTreeMap::new();
+    for change in &changes {
+        let counts = summary.entry(change.kind).or_default();
+        match change.change {
+            ChangeKind::Added => counts.added += 1,
+            ChangeKind::Removed => counts.removed += 1,
+            ChangeKind::Modified => counts.modified += 1,
+        }
+    }
+
+    CatalogDiff { changes, summary }
+}
+
+fn section_title(kind: EntityKind) -> &'static str {
+    match kind {
+        EntityKind::Feature => "Features",
+        EntityKind::Scenario => "Scenarios",
+        EntityKind::Examples => "Examples",
+        EntityKind::Tag => "Tags",
+        EntityKind::Organization => "Organizations",
+        EntityKind::Team => "Teams",
+        EntityKind::Person => "People",
+        EntityKind::StepDefinition => "Step definitions",
+    }
+}
+
+fn short_value(value: &Value) -> String {
+    match value {
+        Value::Null => "none".to_string(),
+        Value::String(s) if s.is_empty() => "\"\"".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Render a changeset as Markdown release notes, one section per entity kind.
+pub fn to_markdown(diff: &CatalogDiff) -> String {
+    if diff.changes.is_empty() {
+        return "No changes.\n".to_string();
+    }
+
+    let mut markdown = String::new();
+    for (kind, counts) in &diff.summary {
+        markdown.push_str(&format!(
+            "### {} (+{} -{} ~{})\n\n",
+            section_title(*kind),
+            counts.added,
+            counts.removed,
+            counts.modified
+        ));
+
+        for change in diff.changes.iter().filter(|c| c.kind == *kind) {
+            let label = match change.change {
+                ChangeKind::Added => "Added",
+                ChangeKind::Removed => "Removed",
+                ChangeKind::Modified => "Modified",
+            };
+            markdown.push_str(&format!("- **{}** `{}`\n", label, change.key));
+            for field in &change.fields {
+                markdown.push_str(&format!(
+                    "  - {}: {} → {}\n",
+                    field.field,
+                    short_value(&field.before),
+                    short_value(&field.after)
+                ));
+            }
+        }
+        markdown.push('\n');
+    }
+
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    async fn catalog_with(features: &[(&str, &str)], organizations: &str) -> Catalog {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("features")).unwrap();
+        fs::create_dir(dir.path().join("data")).unwrap();
+        for (path, content) in features {
+            fs::write(dir.path().join("features").join(path), content).unwrap();
+        }
+        fs::write(dir.path().join("data/organizations.yml"), organizations).unwrap();
+        load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap()
+    }
+
+    const ORGS: &str = "Org A:\n  id: \"1\"\n  blame: ana\n  team: core\n  users: {}\n";
+
+    #[tokio::test]
+    async fn test_diff_catalogs() {
+        let before = catalog_with(
+            &[(
+                "a.feature",
+                "Feature: A\n  Scenario: Kept\n    Given x\n  @old\n  Scenario: Gone\n    Given y\n",
+            )],
+            ORGS,
+        )
+        .await;
+        let after = catalog_with(
+            &[(
+                "a.feature",
+                "Feature: A\n  @new\n  Scenario: Kept\n    Given x\n    Then z\n  Scenario Outline: Fresh\n    Given <v>\n    Examples:\n      | v |\n      | 1 |\n",
+            )],
+            "Org A:\n  id: \"1\"\n  blame: bo\n  team: core\n  users: {}\n",
+        )
+        .await;
+
+        let diff = diff_catalogs(&before, &after);
+        let find = |kind: EntityKind, key: &str| {
+            diff.changes
+                .iter()
+                .find(|c| c.kind == kind && c.key == key)
+                .unwrap()
+        };
+
+        let kept = find(EntityKind::Scenario, "features/a.feature › Kept");
+        assert_eq!(kept.change, ChangeKind::Modified);
+        assert_eq!(
+            kept.fields,
+            vec![
+                FieldChange {
+                    field: "steps".into(),
+                    before: json!(1),
+                    after: json!(2)
+                },
+                FieldChange {
+                    field: "tags".into(),
+                    before: json!([]),
+                    after: json!(["@new"])
+                },
+            ]
+        );
+        assert_eq!(
+            find(EntityKind::Scenario, "features/a.feature › Gone").change,
+            ChangeKind::Removed
+        );
+        assert_eq!(
+            find(EntityKind::Scenario, "features/a.feature › Fresh").change,
+            ChangeKind::Added
+        );
+        assert_eq!(
+            find(EntityKind::Examples, "features/a.feature › Fresh › #1").change,
+            ChangeKind::Added
+        );
+        assert_eq!(find(EntityKind::Tag, "@old").change, ChangeKind::Removed);
+        assert_eq!(
+            find(EntityKind::Organization, "Org A").fields[0].field,
+            "blame"
+        );
+        assert_eq!(find(EntityKind::Person, "bo").change, ChangeKind::Added);
+        assert!(diff.changes.iter().all(|c| c.kind != EntityKind::Feature));
+        assert_eq!(diff.summary[&EntityKind::Scenario].modified, 1);
+
+        let markdown = to_markdown(&diff);
+        assert!(markdown.contains("### Scenarios (+1 -1 ~1)"));
+        assert!(markdown.contains("- **Modified** `features/a.feature › Kept`\n  - steps: 1 → 2\n"));
+    }
+
+    #[tokio::test]
+    async fn test_diff_identical_catalogs() {
+        let features = [("a.feature", "Feature: A\n  Scenario: S\n    Given x\n")];
+        let before = catalog_with(&features, ORGS).await;
+        let after = catalog_with(&features, ORGS).await;
+
+        let diff = diff_catalogs(&before, &after);
+        assert!(diff.changes.is_empty());
+        assert_eq!(to_markdown(&diff), "No changes.\n");
+    }
+}
@@ -0,0 +1,511 @@
+use crate::cucumber_messages::{compile_pickles, feature_files};
+use crate::gherkin_parser::{self, GherkinDocument, IdGenerator};
+use crate::git_reader::run_git;
+use crate::steps_reader::{parse_step_file, step_regex, StepDefinition};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChangedScenario {
+    pub file_path: String,
+    pub name: String,
+    pub line: u32,
+    pub change: ChangeKind,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeReport {
+    pub base_ref: String,
+    pub scenarios: Vec<ChangedScenario>,
+    pub step_definitions: Vec<String>,
+    /// `path:line` targets for every changed scenario that still exists
+    pub selection: Vec<String>,
+}
+
+/// Changed line ranges of one file, inclusive and 1-based. `added` refers to lines of
+/// the working tree version, `deleted` to lines of the base version.
+#[derive(Debug, Default, PartialEq)]
+struct FileDiff {
+    path: String,
+    added: Vec<(u32, u32)>,
+    deleted: Vec<(u32, u32)>,
+}
+
+struct ScenarioSpan {
+    name: String,
+    line: u32,
+    start: u32,
+    end: u32,
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse::<u32>().ok()?, count.parse::<u32>().ok()?),
+        None => (range.parse::<u32>().ok()?, 1),
+    };
+    (count > 0).then(|| (start, start + count - 1))
+}
+
+/// Parse `git diff --unified=0` output into changed line ranges per file.
+fn parse_diff(output: &str) -> Vec<FileDiff> {
+    let mut diffs: Vec<FileDiff> = Vec::new();
+    let mut old_path = String::new();
+
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("--- ") {
+            old_path = path.strip_prefix("a/").unwrap_or(path).to_string();
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            diffs.push(FileDiff {
+                path: if path == "/dev/null" {
+                    old_path.clone()
+                } else {
+                    path.to_string()
+                },
+                ..Default::default()
+            });
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            let Some(diff) = diffs.last_mut() else {
+                continue;
+            };
+            let mut parts = hunk.split_whitespace();
+            let old = parts.next().and_then(|p| p.strip_prefix('-'));
+            let new = parts.next().and_then(|p| p.strip_prefix('+'));
+            diff.deleted.extend(old.and_then(parse_range));
+            diff.added.extend(new.and_then(parse_range));
+        }
+    }
+
+    diffs
+}
+
+fn overlaps(ranges: &[(u32, u32)], start: u32, end: u32) -> bool {
+    ranges.iter().any(|&(from, to)| from <= end && to >= start)
+}
+
+fn scenario_spans(document: &GherkinDocument, line_count: u32) -> Vec<ScenarioSpan> {
+    let Some(feature) = &document.feature else {
+        return Vec::new();
+    };
+
+    let mut spans: Vec<ScenarioSpan> = feature
+        .scenarios()
+        .iter()
+        .map(|scenario| ScenarioSpan {
+            name: scenario.name.clone(),
+            line: scenario.location.line,
+            start: scenario
+                .tags
+                .iter()
+                .map(|t| t.location.line)
+                .chain(std::iter::once(scenario.location.line))
+                .min()
+                .unwrap_or(scenario.location.line),
+            end: line_count,
+        })
+        .collect();
+
+    for i in 1..spans.len() {
+        spans[i - 1].end = spans[i].start - 1;
+    }
+
+    spans
+}
+
+fn parse_document(path: &str, content: &str) -> Option<(GherkinDocument, u32)> {
+    let document = gherkin_parser::parse(path, content, &mut IdGenerator::new()).ok()?;
+    Some((document, content.lines().count() as u32))
+}
+
+fn base_version(repo_path: &str, base_ref: &str, path: &str) -> Option<String> {
+    run_git(repo_path, &["show", &format!("{}:./{}", base_ref, path)]).ok()
+}
+
+fn record(
+    changes: &mut BTreeMap<(String, String), ChangedScenario>,
+    file_path: &str,
+    span: &ScenarioSpan,
+    change: ChangeKind,
+    reason: String,
+) {
+    let entry = changes
+        .entry((file_path.to_string(), span.name.clone()))
+        .or_insert_with(|| ChangedScenario {
+            file_path: file_path.to_string(),
+            name: span.name.clone(),
+            line: span.line,
+            change,
+            reasons: Vec::new(),
+        });
+    if !entry.reasons.contains(&reason) {
+        entry.reasons.push(reason);
+    }
+}
+
+fn detect_feature_changes(
+    repo_path: &str,
+    base_ref: &str,
+    diff: &FileDiff,
+    changes: &mut BTreeMap<(String, String), ChangedScenario>,
+) {
+    let current = fs::read_to_string(Path::new(repo_path).join(&diff.path))
+        .ok()
+        .and_then(|content| parse_document(&diff.path, &content));
+    let previous = base_version(repo_path, base_ref, &diff.path)
+        .and_then(|content| parse_document(&diff.path, &content));
+
+    let new_spans = current
+        .as_ref()
+        .map(|(document, lines)| scenario_spans(document, *lines))
+        .unwrap_or_default();
+    let old_spans = previous
+        .as_ref()
+        .map(|(document, lines)| scenario_spans(document, *lines))
+        .unwrap_or_default();
+    let old_by_name: HashMap<&str, &ScenarioSpan> =
+        old_spans.iter().map(|s| (s.name.as_str(), s)).collect();
+
+    // Feature header and background changes affect every scenario in the file
+    let header_end = new_spans.first().map_or(u32::MAX, |s| s.start - 1);
+    let old_header_end = old_spans.first().map_or(u32::MAX, |s| s.start - 1);
+    let header_changed = (header_end > 0 && overlaps(&diff.added, 1, header_end))
+        || (old_header_end > 0 && overlaps(&diff.deleted, 1, old_header_end));
+
+    for span in &new_spans {
+        match old_by_name.get(span.name.as_str()) {
+            None => record(
+                changes,
+                &diff.path,
+                span,
+                ChangeKind::Added,
+                "new scenario".into(),
+            ),
+            Some(old) => {
+                if overlaps(&diff.added, span.start, span.end)
+                    || overlaps(&diff.deleted, old.start, old.end)
+                {
+                    record(
+                        changes,
+                        &diff.path,
+                        span,
+                        ChangeKind::Modified,
+                        "scenario edited".into(),
+                    );
+                } else if header_changed {
+                    record(
+                        changes,
+                        &diff.path,
+                        span,
+                        ChangeKind::Modified,
+                        "feature header or background edited".into(),
+                    );
+                }
+            }
+        }
+    }
+
+    for span in &old_spans {
+        if !new_spans.iter().any(|s| s.name == span.name) {
+            record(
+                changes,
+                &diff.path,
+                span,
+                ChangeKind::Removed,
+                "scenario removed".into(),
+            );
+        }
+    }
+}
+
+fn definition_spans(steps: &[StepDefinition], line_count: u32) -> Vec<(u32, u32)> {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let end = steps
+                .get(i + 1)
+                .map_or(line_count, |next| next.line_number as u32 - 1);
+            (step.line_number as u32, end)
+        })
+        .collect()
+}
+
+fn affected_definitions(repo_path: &str, base_ref: &str, diff: &FileDiff) -> Vec<StepDefinition> {
+    let mut affected = Vec::new();
+    let versions = [
+        (
+            fs::read_to_string(Path::new(repo_path).join(&diff.path)).ok(),
+            &diff.added,
+        ),
+        (base_version(repo_path, base_ref, &diff.path), &diff.deleted),
+    ];
+
+    for (content, ranges) in versions {
+        let Some(content) = content else { continue };
+        let steps = parse_step_file(&content, &diff.path);
+        let spans = definition_spans(&steps, content.lines().count() as u32);
+        for (step, (start, end)) in steps.into_iter().zip(spans) {
+            let known = affected
+                .iter()
+                .any(|a: &StepDefinition| a.keyword == step.keyword && a.pattern == step.pattern);
+            if !known && overlaps(ranges, start, end) {
+                affected.push(step);
+            }
+        }
+    }
+
+    affected
+}
+
+fn detect_step_usages(
+    repo_path: &str,
+    definitions: &[StepDefinition],
+    changes: &mut BTreeMap<(String, String), ChangedScenario>,
+) {
+    let matchers: Vec<(String, Regex)> = definitions
+        .iter()
+        .filter_map(|d| step_regex(d).map(|re| (format!("{} {}", d.keyword, d.pattern), re)))
+        .collect();
+    if matchers.is_empty() {
+        return;
+    }
+
+    let base = Path::new(repo_path);
+    let mut ids = IdGenerator::new();
+    for path in feature_files(base) {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let relative = path
+            .strip_prefix(base)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let Ok(document) = gherkin_parser::parse(&relative, &content, &mut ids) else {
+            continue;
+        };
+        let spans = scenario_spans(&document, content.lines().count() as u32);
+        let scenario_lines: HashMap<String, u32> = document
+            .feature
+            .as_ref()
+            .map(|f| {
+                f.scenarios()
+                    .iter()
+                    .map(|s| (s.id.clone(), s.location.line))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for pickle in compile_pickles(&document, &mut ids) {
+            let line = scenario_lines.get(&pickle.ast_node_ids[0]).copied();
+            let Some(span) = spans.iter().find(|s| Some(s.line) == line) else {
+                continue;
+            };
+            for (label, regex) in &matchers {
+                if pickle.steps.iter().any(|step| regex.is_match(&step.text)) {
+                    record(
+                        changes,
+                        &relative,
+                        span,
+                        ChangeKind::Modified,
+                        format!("uses changed step: {}", label),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Resolve a user supplied ref to a commit sha, so it can never be read as a git option.
+fn resolve_commit(repo_path: &str, base_ref: &str) -> Result<String, String> {
+    run_git(
+        repo_path,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            "--end-of-options",
+            &format!("{}^{{commit}}", base_ref),
+        ],
+    )
+    .map(|sha| sha.trim().to_string())
+    .map_err(|_| format!("Unknown git ref: {}", base_ref))
+}
+
+/// Compare the working tree of `repo_path` with `base_ref` and report which scenarios
+/// were added, modified or removed, either directly or through changed step definitions.
+pub fn detect_changes(repo_path: &str, base_ref: &str) -> Result<ChangeReport, String> {
+    let base_commit = resolve_commit(repo_path, base_ref)?;
+    let output = run_git(
+        repo_path,
+        &[
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-renames",
+            "--relative",
+            &base_commit,
+            "--",
+        ],
+    )?;
+    let mut diffs = parse_diff(&output);
+
+    let untracked = run_git(repo_path, &["ls-files", "--others", "--exclude-standard"])?;
+    diffs.extend(untracked.lines().map(|path| FileDiff {
+        path: path.to_string(),
+        added: vec![(1, u32::MAX)],
+        deleted: Vec::new(),
+    }));
+
+    let mut changes = BTreeMap::new();
+    let mut definitions = Vec::new();
+
+    for diff in &diffs {
+        if diff.path.ends_with(".feature") {
+            detect_feature_changes(repo_path, &base_commit, diff, &mut changes);
+        } else if diff.path.starts_with("step-definitions/")
+            && (diff.path.ends_with(".ts") || diff.path.ends_with(".js"))
+        {
+            definitions.extend(affected_definitions(repo_path, &base_commit, diff));
+        }
+    }
+
+    detect_step_usages(repo_path, &definitions, &mut changes);
+
+    let scenarios: Vec<ChangedScenario> = changes.into_values().collect();
+    let selection: BTreeSet<String> = scenarios
+        .iter()
+        .filter(|s| s.change != ChangeKind::Removed)
+        .map(|s| format!("{}:{}", s.file_path, s.line))
+        .collect();
+
+    Ok(ChangeReport {
+        base_ref: base_ref.to_string(),
+        scenarios,
+        step_definitions: definitions.iter().map(|d| d.id.clone()).collect(),
+        selection: selection.into_iter().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_diff() {
+        let output = "\
+diff --git a/features/a.feature b/features/a.feature
+--- a/features/a.feature
++++ b/features/a.feature
+@@ -3 +3,2 @@ Feature: A
+@@ -10,2 +11,0 @@
+diff --git a/gone.feature b/gone.feature
+--- a/gone.feature
++++ /dev/null
+@@ -1,4 +0,0 @@
+";
+        let diffs = parse_diff(output);
+        assert_eq!(
+            diffs,
+            vec![
+                FileDiff {
+                    path: "features/a.feature".into(),
+                    added: vec![(3, 4)],
+                    deleted: vec![(3, 3), (10, 11)],
+                },
+                FileDiff {
+                    path: "gone.feature".into(),
+                    added: vec![],
+                    deleted: vec![(1, 4)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_changes() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let git = |args: &[&str]| {
+            let mut full = vec!["-c", "user.name=Ana", "-c", "user.email=ana@example.com"];
+            full.extend_from_slice(args);
+            run_git(repo, &full).unwrap();
+        };
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+
+        write(
+            "features/invoices.feature",
+            "Feature: Invoices\n\n  Scenario: Create invoice\n    Given I create an invoice\n\n  Scenario: Delete invoice\n    Given I delete an invoice\n\n  Scenario: Send invoice\n    When I wait 3 seconds\n",
+        );
+        write(
+            "step-definitions/invoices.ts",
+            "Given('I create an invoice', async () => {\n  await create();\n});\n\nWhen('I wait {int} seconds', async (s) => {\n  await wait(s);\n});\n",
+        );
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "base"]);
+
+        write(
+            "features/invoices.feature",
+            "Feature: Invoices\n\n  Scenario: Create invoice\n    Given I create an invoice\n    Then I see it\n\n  Scenario: Send invoice\n    When I wait 3 seconds\n",
+        );
+        write(
+            "step-definitions/invoices.ts",
+            "Given('I create an invoice', async () => {\n  await create();\n});\n\nWhen('I wait {int} seconds', async (s) => {\n  await wait(s * 1000);\n});\n",
+        );
+        write(
+            "features/new.feature",
+            "Feature: New\n  Scenario: Brand new\n    Given x\n",
+        );
+
+        let report = detect_changes(repo, "HEAD").unwrap();
+        let find = |name: &str| report.scenarios.iter().find(|s| s.name == name).unwrap();
+
+        assert_eq!(report.scenarios.len(), 4);
+        assert_eq!(find("Create invoice").change, ChangeKind::Modified);
+        assert_eq!(find("Delete invoice").change, ChangeKind::Removed);
+        assert_eq!(find("Send invoice").change, ChangeKind::Modified);
+        assert_eq!(
+            find("Send invoice").reasons,
+            vec!["uses changed step: When I wait {int} seconds".to_string()]
+        );
+        assert_eq!(find("Brand new").change, ChangeKind::Added);
+        assert_eq!(
+            report.step_definitions,
+            vec!["step-definitions/invoices.ts:5"]
+        );
+        assert_eq!(
+            report.selection,
+            vec![
+                "features/invoices.feature:3",
+                "features/invoices.feature:7",
+                "features/new.feature:2"
+            ]
+        );
+
+        let output = dir.path().join("out.txt");
+        let option = format!("--output={}", output.display());
+        assert_eq!(
+            detect_changes(repo, &option).unwrap_err(),
+            format!("Unknown git ref: {}", option)
+        );
+        assert!(!output.exists());
+    }
+}
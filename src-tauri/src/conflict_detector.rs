@@ -0,0 +1,242 @@
+use crate::features_reader::{Feature, Scenario};
+use crate::organizations_reader::Organizations;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+/// Orgs shared by more scenarios than this are reported as hotspots by default
+pub const DEFAULT_HOTSPOT_THRESHOLD: usize = 10;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScenarioRef {
+    pub file_path: String,
+    pub line: u32,
+    pub name: String,
+    pub organization_name: String,
+}
+
+/// Scenarios sharing one organization, every pair of them conflicts.
+#[derive(Serialize, Debug)]
+pub struct ConflictGroup {
+    pub organization: String,
+    pub team: Option<String>,
+    pub owner: Option<String>,
+    pub scenarios: Vec<ScenarioRef>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Hotspot {
+    pub organization: String,
+    pub team: Option<String>,
+    pub owner: Option<String>,
+    pub scenarios: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ParallelPlan {
+    pub conflict_groups: Vec<ConflictGroup>,
+    /// Number of conflicting scenario pairs
+    pub conflict_edges: usize,
+    /// Run waves one after another, scenarios within a wave can run concurrently
+    pub waves: Vec<Vec<ScenarioRef>>,
+    pub hotspots: Vec<Hotspot>,
+    /// Scenarios without an organization, assumed not to conflict with anything
+    pub unassigned: usize,
+    /// Organizations used by scenarios but missing from organizations.yml
+    pub unknown_organizations: Vec<String>,
+}
+
+/// Build the org conflict graph and a schedule where no wave uses an org twice. The
+/// number of waves equals the size of the largest conflict group, scenarios without an
+/// org fill up the smallest waves.
+pub fn analyze(
+    features: &[Feature],
+    scenarios: &[Scenario],
+    organizations: &Organizations,
+    hotspot_threshold: usize,
+) -> ParallelPlan {
+    let feature_paths: HashMap<&str, &str> = features
+        .iter()
+        .map(|f| (f.id.as_str(), f.file_path.as_str()))
+        .collect();
+    let known: HashMap<&str, _> = organizations.iter().map(|o| (o.name.as_str(), o)).collect();
+
+    let mut by_organization: BTreeMap<&str, Vec<ScenarioRef>> = BTreeMap::new();
+    let mut unassigned = Vec::new();
+    for scenario in scenarios {
+        let reference = ScenarioRef {
+            file_path: feature_paths
+                .get(scenario.feature_id.as_str())
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            line: scenario.line,
+            name: scenario.name.clone(),
+            organization_name: scenario.organization_name.clone(),
+        };
+        if scenario.organization_name.is_empty() {
+            unassigned.push(reference);
+        } else {
+            by_organization
+                .entry(&scenario.organization_name)
+                .or_default()
+                .push(reference);
+        }
+    }
+
+    let mut waves: Vec<Vec<ScenarioRef>> = Vec::new();
+    for group in by_organization.values() {
+        for (index, reference) in group.iter().enumerate() {
+            if waves.len() <= index {
+                waves.push(Vec::new());
+            }
+            waves[index].push(reference.clone());
+        }
+    }
+    if waves.is_empty() && !unassigned.is_empty() {
+        waves.push(Vec::new());
+    }
+    let unassigned_count = unassigned.len();
+    for reference in unassigned {
+        let smallest = waves
+            .iter_mut()
+            .min_by_key(|wave| wave.len())
+            .expect("at least one wave");
+        smallest.push(reference);
+    }
+
+    let owner = |name: &str| known.get(name).map(|o| (o.team.clone(), o.blame.clone()));
+
+    let mut hotspots: Vec<Hotspot> = by_organization
+        .iter()
+        .filter(|(_, group)| group.len() > hotspot_threshold)
+        .map(|(name, group)| Hotspot {
+            organization: name.to_string(),
+            team: owner(name).map(|(team, _)| team),
+            owner: owner(name).map(|(_, blame)| blame),
+            scenarios: group.len(),
+        })
+        .collect();
+    hotspots.sort_by_key(|hotspot| Reverse(hotspot.scenarios));
+
+    let conflict_edges = by_organization
+        .values()
+        .map(|group| group.len() * (group.len() - 1) / 2)
+        .sum();
+    let unknown_organizations = by_organization
+        .keys()
+        .filter(|name| !known.contains_key(*name))
+        .map(|name| name.to_string())
+        .collect();
+    let conflict_groups = by_organization
+        .into_iter()
+        .filter(|(_, group)| group.len() > 1)
+        .map(|(name, scenarios)| ConflictGroup {
+            organization: name.to_string(),
+            team: owner(name).map(|(team, _)| team),
+            owner: owner(name).map(|(_, blame)| blame),
+            scenarios,
+        })
+        .collect();
+
+    ParallelPlan {
+        conflict_groups,
+        conflict_edges,
+        waves,
+        hotspots,
+        unassigned: unassigned_count,
+        unknown_organizations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog_diff::load_catalog;
+    use crate::repo_source::RepoSource;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_analyze() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("features")).unwrap();
+        fs::create_dir_all(dir.path().join("data")).unwrap();
+        let scenario = |name: &str, organization: &str| {
+            format!(
+                "  Scenario: {}\n    Given current organization is \"{}\"\n",
+                name, organization
+            )
+        };
+        let content = format!(
+            "Feature: F\n{}{}{}{}{}  Scenario: Free\n    Given nothing\n",
+            scenario("A1", "Org A"),
+            scenario("A2", "Org A"),
+            scenario("A3", "Org A"),
+            scenario("B1", "Org B"),
+            scenario("C1", "Org C"),
+        );
+        fs::write(dir.path().join("features/f.feature"), content).unwrap();
+        fs::write(
+            dir.path().join("data/organizations.yml"),
+            "Org A:\n  id: \"1\"\n  blame: ana\n  team: core\n  users: {}\nOrg B:\n  id: \"2\"\n  blame: ivo\n  team: money\n  users: {}\n",
+        )
+        .unwrap();
+        let catalog = load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap();
+
+        let plan = analyze(
+            &catalog.features,
+            &catalog.scenarios,
+            &catalog.organizations,
+            2,
+        );
+
+        assert_eq!(plan.conflict_edges, 3);
+        assert_eq!(plan.conflict_groups.len(), 1);
+        assert_eq!(plan.conflict_groups[0].scenarios.len(), 3);
+
+        let waves: Vec<Vec<&str>> = plan
+            .waves
+            .iter()
+            .map(|wave| wave.iter().map(|s| s.name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            waves,
+            vec![vec!["A1", "B1", "C1"], vec!["A2", "Free"], vec!["A3"]]
+        );
+
+        assert_eq!(
+            plan.hotspots,
+            vec![Hotspot {
+                organization: "Org A".into(),
+                team: Some("core".into()),
+                owner: Some("ana".into()),
+                scenarios: 3,
+            }]
+        );
+        assert_eq!(plan.unassigned, 1);
+        assert_eq!(plan.unknown_organizations, vec!["Org C"]);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_background_organization() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("features")).unwrap();
+        fs::write(
+            dir.path().join("features/f.feature"),
+            "Feature: F\n  Background:\n    Given current organization is \"Org A\"\n\n  Scenario: A1\n    Given a step\n  Scenario: A2\n    Given a step\n  Scenario: B1\n    Given current organization is \"Org B\"\n",
+        )
+        .unwrap();
+        let catalog = load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap();
+
+        let plan = analyze(&catalog.features, &catalog.scenarios, &Vec::new(), 2);
+
+        assert_eq!(plan.conflict_edges, 1);
+        assert_eq!(plan.conflict_groups[0].organization, "Org A");
+        assert_eq!(plan.conflict_groups[0].scenarios.len(), 2);
+        assert_eq!(plan.unassigned, 0);
+    }
+}
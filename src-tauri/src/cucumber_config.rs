@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::Path;
+
+/// Files cucumber-js reads its profiles from, in the order it looks for them
+const CONFIG_FILES: [&str; 6] = [
+    "cucumber.js",
+    "cucumber.cjs",
+    "cucumber.mjs",
+    "cucumber.json",
+    "cucumber.yaml",
+    "cucumber.yml",
+];
+
+/// Names of the profiles defined in the repo's cucumber config, in file order.
+pub fn list_profiles(repo_path: &str) -> Result<Vec<String>, String> {
+    for file in CONFIG_FILES {
+        let path = Path::new(repo_path).join(file);
+        if !path.is_file() {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        return if file.ends_with("js") {
+            Ok(js_profiles(&content))
+        } else {
+            data_profiles(&content)
+                .map_err(|e| format!("Invalid cucumber config {}: {}", path.display(), e))
+        };
+    }
+    Ok(Vec::new())
+}
+
+fn data_profiles(content: &str) -> Result<Vec<String>, String> {
+    // YAML is a superset of JSON
+    let config: serde_yml::Mapping = serde_yml::from_str(content).map_err(|e| e.to_string())?;
+    Ok(config
+        .keys()
+        .filter_map(|key| key.as_str().map(str::to_string))
+        .collect())
+}
+
+/// Keys of the exported object literal, plus `exports.name = ...` and
+/// `export const name = ...` profiles. Anything computed at runtime is missed.
+fn js_profiles(content: &str) -> Vec<String> {
+    let mut profiles = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_start();
+        let name = line
+            .strip_prefix("exports.")
+            .or_else(|| line.strip_prefix("module.exports."))
+            .or_else(|| line.strip_prefix("export const "));
+        if let Some(name) = name.and_then(|rest| rest.split('=').next()) {
+            let name = name.trim();
+            if is_identifier(name) {
+                profiles.push(name.to_string());
+            }
+        }
+    }
+
+    let start = ["module.exports", "export default"]
+        .iter()
+        .filter_map(|marker| content.find(marker))
+        .min()
+        .and_then(|at| content[at..].find('{').map(|brace| at + brace));
+    if let Some(start) = start {
+        profiles.extend(object_keys(&content[start..]));
+    }
+    profiles
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Top level keys of the object literal `source` starts with.
+fn object_keys(source: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut depth = 0;
+    let mut expecting_key = false;
+    let mut key = String::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' | '`' => {
+                let mut literal = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if next == c => break,
+                        _ => literal.push(next),
+                    }
+                }
+                if depth == 1 && expecting_key {
+                    key = literal;
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' | '[' | '(' => {
+                depth += 1;
+                if depth == 1 {
+                    expecting_key = true;
+                }
+            }
+            '}' | ']' | ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            ',' if depth == 1 => {
+                expecting_key = true;
+                key.clear();
+            }
+            ':' if depth == 1 && expecting_key => {
+                if !key.is_empty() {
+                    keys.push(key.clone());
+                }
+                expecting_key = false;
+            }
+            c if depth == 1 && expecting_key && (c.is_alphanumeric() || c == '_' || c == '$') => {
+                key.push(c);
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_js_profiles() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("cucumber.js"),
+            r#"const common = ['--require step-definitions/**/*.ts', '--format progress'].join(' ');
+
+module.exports = {
+  // Runs against the local app
+  default: common,
+  local: `${common} --tags "not @broken"`,
+  'ci-chrome': { parallel: 4, tags: 'not @wip' },
+};
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            list_profiles(dir.path().to_str().unwrap()).unwrap(),
+            vec!["default", "local", "ci-chrome"]
+        );
+    }
+
+    #[test]
+    fn test_data_profiles() {
+        let dir = tempdir().unwrap();
+        assert!(list_profiles(dir.path().to_str().unwrap())
+            .unwrap()
+            .is_empty());
+
+        fs::write(
+            dir.path().join("cucumber.yml"),
+            "default:\n  paths: [features]\nstaging:\n  tags: not @local\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list_profiles(dir.path().to_str().unwrap()).unwrap(),
+            vec!["default", "staging"]
+        );
+
+        fs::write(
+            dir.path().join("cucumber.json"),
+            r#"{"default": {}, "ci": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            list_profiles(dir.path().to_str().unwrap()).unwrap(),
+            vec!["default", "ci"]
+        );
+    }
+}
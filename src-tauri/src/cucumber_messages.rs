@@ -0,0 +1,475 @@
+use crate::gherkin_parser::{self, GherkinDocument, IdGenerator, Scenario, Step, TableRow};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Envelope {
+    Source(Source),
+    GherkinDocument(GherkinDocument),
+    Pickle(Pickle),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    pub uri: String,
+    pub data: String,
+    pub media_type: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pickle {
+    pub id: String,
+    pub uri: String,
+    pub location: gherkin_parser::Location,
+    pub name: String,
+    pub language: String,
+    pub steps: Vec<PickleStep>,
+    pub tags: Vec<PickleTag>,
+    pub ast_node_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickleStep {
+    pub ast_node_ids: Vec<String>,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub step_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub argument: Option<PickleStepArgument>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickleStepArgument {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_string: Option<PickleDocString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_table: Option<PickleTable>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickleDocString {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct PickleTable {
+    pub rows: Vec<PickleTableRow>,
+}
+
+#[derive(Serialize)]
+pub struct PickleTableRow {
+    pub cells: Vec<PickleTableCell>,
+}
+
+#[derive(Serialize)]
+pub struct PickleTableCell {
+    pub value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickleTag {
+    pub name: String,
+    pub ast_node_id: String,
+}
+
+const GHERKIN_MEDIA_TYPE: &str = "text/x.cucumber.gherkin+plain";
+
+/// Build `source`, `gherkinDocument` and `pickle` envelopes for every feature file
+/// under `base_path`. Files are visited in sorted order and IDs are incrementing,
+/// so the output is stable between runs.
+pub fn build_envelopes(base_path: &str) -> Result<Vec<Envelope>, String> {
+    let base = Path::new(base_path);
+    let paths = feature_files(base);
+
+    let mut ids = IdGenerator::new();
+    let mut envelopes = Vec::new();
+
+    for path in paths {
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let uri = path
+            .strip_prefix(base)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let document = gherkin_parser::parse(&uri, &data, &mut ids)?;
+        let pickles = compile_pickles(&document, &mut ids);
+
+        envelopes.push(Envelope::Source(Source {
+            uri,
+            data,
+            media_type: GHERKIN_MEDIA_TYPE.to_string(),
+        }));
+        envelopes.push(Envelope::GherkinDocument(document));
+        envelopes.extend(pickles.into_iter().map(Envelope::Pickle));
+    }
+
+    Ok(envelopes)
+}
+
+/// Sorted paths of all `.feature` files under `base`, skipping `node_modules`.
+pub fn feature_files(base: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = WalkDir::new(base)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension().and_then(std::ffi::OsStr::to_str) == Some("feature")
+                && !p.components().any(|c| c.as_os_str() == "node_modules")
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Serialize envelopes as NDJSON, one message per line, like `--format message`.
+pub fn to_ndjson(envelopes: &[Envelope]) -> Result<String, String> {
+    let mut output = String::new();
+    for envelope in envelopes {
+        let line = serde_json::to_string(envelope).map_err(|e| e.to_string())?;
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+pub fn export_messages(base_path: &str, output_path: Option<&str>) -> Result<String, String> {
+    let ndjson = to_ndjson(&build_envelopes(base_path)?)?;
+
+    match output_path {
+        Some(output_path) => {
+            fs::write(output_path, &ndjson)
+                .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+            Ok(output_path.to_string())
+        }
+        None => Ok(ndjson),
+    }
+}
+
+struct PickleContext<'a> {
+    uri: &'a str,
+    language: &'a str,
+    tags: Vec<PickleTag>,
+    background_steps: Vec<&'a Step>,
+}
+
+pub fn compile_pickles(document: &GherkinDocument, ids: &mut IdGenerator) -> Vec<Pickle> {
+    let mut pickles = Vec::new();
+    let feature = match &document.feature {
+        Some(feature) => feature,
+        None => return pickles,
+    };
+
+    let mut context = PickleContext {
+        uri: &document.uri,
+        language: &feature.language,
+        tags: to_pickle_tags(&feature.tags),
+        background_steps: Vec::new(),
+    };
+
+    for child in &feature.children {
+        if let Some(background) = &child.background {
+            context.background_steps.extend(background.steps.iter());
+        }
+        if let Some(scenario) = &child.scenario {
+            compile_scenario(&context, scenario, ids, &mut pickles);
+        }
+        if let Some(rule) = &child.rule {
+            let mut rule_context = PickleContext {
+                uri: context.uri,
+                language: context.language,
+                tags: context
+                    .tags
+                    .iter()
+                    .chain(to_pickle_tags(&rule.tags).iter())
+                    .map(clone_tag)
+                    .collect(),
+                background_steps: context.background_steps.clone(),
+            };
+            for rule_child in &rule.children {
+                if let Some(background) = &rule_child.background {
+                    rule_context
+                        .background_steps
+                        .extend(background.steps.iter());
+                }
+                if let Some(scenario) = &rule_child.scenario {
+                    compile_scenario(&rule_context, scenario, ids, &mut pickles);
+                }
+            }
+        }
+    }
+
+    pickles
+}
+
+fn clone_tag(tag: &PickleTag) -> PickleTag {
+    PickleTag {
+        name: tag.name.clone(),
+        ast_node_id: tag.ast_node_id.clone(),
+    }
+}
+
+fn to_pickle_tags(tags: &[gherkin_parser::Tag]) -> Vec<PickleTag> {
+    tags.iter()
+        .map(|tag| PickleTag {
+            name: tag.name.clone(),
+            ast_node_id: tag.id.clone(),
+        })
+        .collect()
+}
+
+fn compile_scenario(
+    context: &PickleContext,
+    scenario: &Scenario,
+    ids: &mut IdGenerator,
+    pickles: &mut Vec<Pickle>,
+) {
+    let scenario_tags = to_pickle_tags(&scenario.tags);
+
+    if scenario.examples.is_empty() {
+        let steps = compile_steps(context, scenario, None, ids);
+        pickles.push(Pickle {
+            id: ids.next_id(),
+            uri: context.uri.to_string(),
+            location: scenario.location,
+            name: scenario.name.clone(),
+            language: context.language.to_string(),
+            steps,
+            tags: context
+                .tags
+                .iter()
+                .chain(scenario_tags.iter())
+                .map(clone_tag)
+                .collect(),
+            ast_node_ids: vec![scenario.id.clone()],
+        });
+        return;
+    }
+
+    for examples in &scenario.examples {
+        let header = match &examples.table_header {
+            Some(header) => header,
+            None => continue,
+        };
+        let examples_tags = to_pickle_tags(&examples.tags);
+
+        for row in &examples.table_body {
+            let steps = compile_steps(context, scenario, Some((header, row)), ids);
+            pickles.push(Pickle {
+                id: ids.next_id(),
+                uri: context.uri.to_string(),
+                location: row.location,
+                name: interpolate(&scenario.name, Some((header, row))),
+                language: context.language.to_string(),
+                steps,
+                tags: context
+                    .tags
+                    .iter()
+                    .chain(scenario_tags.iter())
+                    .chain(examples_tags.iter())
+                    .map(clone_tag)
+                    .collect(),
+                ast_node_ids: vec![scenario.id.clone(), row.id.clone()],
+            });
+        }
+    }
+}
+
+fn compile_steps(
+    context: &PickleContext,
+    scenario: &Scenario,
+    row: Option<(&TableRow, &TableRow)>,
+    ids: &mut IdGenerator,
+) -> Vec<PickleStep> {
+    let mut steps = Vec::new();
+    if scenario.steps.is_empty() {
+        return steps;
+    }
+
+    // A conjunction opening the scenario continues the last background step
+    let mut last_type = "Unknown".to_string();
+    for step in &context.background_steps {
+        steps.push(compile_step(step, None, &mut last_type, ids));
+    }
+    for step in &scenario.steps {
+        steps.push(compile_step(step, row, &mut last_type, ids));
+    }
+
+    steps
+}
+
+fn compile_step(
+    step: &Step,
+    row: Option<(&TableRow, &TableRow)>,
+    last_type: &mut String,
+    ids: &mut IdGenerator,
+) -> PickleStep {
+    let step_type = if step.keyword_type == "Conjunction" {
+        last_type.clone()
+    } else {
+        step.keyword_type.clone()
+    };
+    *last_type = step_type.clone();
+
+    let mut ast_node_ids = vec![step.id.clone()];
+    if let Some((_, body_row)) = row {
+        ast_node_ids.push(body_row.id.clone());
+    }
+
+    let argument = if let Some(doc_string) = &step.doc_string {
+        Some(PickleStepArgument {
+            doc_string: Some(PickleDocString {
+                media_type: doc_string.media_type.as_ref().map(|m| interpolate(m, row)),
+                content: interpolate(&doc_string.content, row),
+            }),
+            data_table: None,
+        })
+    } else {
+        step.data_table.as_ref().map(|table| PickleStepArgument {
+            doc_string: None,
+            data_table: Some(PickleTable {
+                rows: table
+                    .rows
+                    .iter()
+                    .map(|r| PickleTableRow {
+                        cells: r
+                            .cells
+                            .iter()
+                            .map(|c| PickleTableCell {
+                                value: interpolate(&c.value, row),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            }),
+        })
+    };
+
+    PickleStep {
+        ast_node_ids,
+        id: ids.next_id(),
+        step_type,
+        text: interpolate(&step.text, row),
+        argument,
+    }
+}
+
+fn interpolate(text: &str, row: Option<(&TableRow, &TableRow)>) -> String {
+    let (header, body) = match row {
+        Some(row) => row,
+        None => return text.to_string(),
+    };
+
+    header
+        .cells
+        .iter()
+        .zip(body.cells.iter())
+        .fold(text.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("<{}>", name.value), &value.value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    const SOURCE: &str = r#"@smoke
+Feature: Invoices
+
+  Background:
+    Given current organization is "Billing LLC"
+
+  Scenario Outline: Create <kind> invoice
+    When I create a "<kind>" invoice
+    And I send it
+
+    @web
+    Examples:
+      | kind  |
+      | draft |
+      | final |
+
+  Scenario: List invoices
+    Then I should see invoices
+"#;
+
+    #[test]
+    fn test_build_envelopes() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("features")).unwrap();
+        fs::write(dir.path().join("features/invoices.feature"), SOURCE).unwrap();
+
+        let envelopes = build_envelopes(dir.path().to_str().unwrap()).unwrap();
+        let ndjson = to_ndjson(&envelopes).unwrap();
+        let messages: Vec<Value> = ndjson
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0]["source"]["uri"], "features/invoices.feature");
+        assert_eq!(messages[0]["source"]["mediaType"], GHERKIN_MEDIA_TYPE);
+        assert_eq!(
+            messages[1]["gherkinDocument"]["feature"]["name"],
+            "Invoices"
+        );
+
+        let draft = &messages[2]["pickle"];
+        assert_eq!(draft["name"], "Create draft invoice");
+        assert_eq!(draft["location"]["line"], 14);
+        assert_eq!(draft["steps"].as_array().unwrap().len(), 3);
+        assert_eq!(draft["steps"][1]["text"], r#"I create a "draft" invoice"#);
+        assert_eq!(draft["steps"][2]["type"], "Action");
+        assert_eq!(draft["tags"][0]["name"], "@smoke");
+        assert_eq!(draft["tags"][1]["name"], "@web");
+        assert_eq!(draft["astNodeIds"].as_array().unwrap().len(), 2);
+
+        assert_eq!(messages[3]["pickle"]["name"], "Create final invoice");
+        assert_eq!(messages[4]["pickle"]["name"], "List invoices");
+        assert_eq!(messages[4]["pickle"]["steps"][1]["type"], "Outcome");
+    }
+
+    #[test]
+    fn test_build_envelopes_is_stable() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("b.feature"), SOURCE).unwrap();
+        fs::write(dir.path().join("a.feature"), SOURCE).unwrap();
+        let base_path = dir.path().to_str().unwrap();
+
+        let first = export_messages(base_path, None).unwrap();
+        let second = export_messages(base_path, None).unwrap();
+        assert_eq!(first, second);
+        assert!(first
+            .lines()
+            .next()
+            .unwrap()
+            .contains(r#""uri":"a.feature""#));
+    }
+
+    #[test]
+    fn test_compile_pickles_conjunction_after_background() {
+        let source = "# language: en\nFeature: F\n  Background:\n    Given a user\n\n  Scenario: S\n    And an invoice\n";
+        let mut ids = IdGenerator::new();
+        let document = gherkin_parser::parse("f.feature", source, &mut ids).unwrap();
+        let pickles = compile_pickles(&document, &mut ids);
+
+        assert_eq!(pickles[0].language, "en");
+        assert_eq!(pickles[0].steps[1].step_type, "Context");
+    }
+}
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Deserialize)]
+struct JsonFeature {
+    uri: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    elements: Vec<JsonElement>,
+}
+
+#[derive(Deserialize)]
+struct JsonElement {
+    line: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    steps: Vec<JsonStep>,
+    #[serde(default)]
+    before: Vec<JsonStep>,
+    #[serde(default)]
+    after: Vec<JsonStep>,
+}
+
+#[derive(Deserialize)]
+struct JsonStep {
+    #[serde(default)]
+    keyword: String,
+    #[serde(default)]
+    name: String,
+    line: Option<u32>,
+    result: Option<JsonResult>,
+    #[serde(default)]
+    embeddings: Vec<JsonEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct JsonResult {
+    #[serde(default)]
+    status: String,
+    /// Nanoseconds
+    duration: Option<u64>,
+    error_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonEmbedding {
+    data: String,
+    mime_type: String,
+}
+
+/// Ordered from best to worst, a scenario takes the worst status of its steps
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStatus {
+    Passed,
+    Skipped,
+    Pending,
+    Undefined,
+    Ambiguous,
+    Failed,
+}
+
+impl ResultStatus {
+    pub fn parse(status: &str) -> Self {
+        match status {
+            "passed" => ResultStatus::Passed,
+            "skipped" => ResultStatus::Skipped,
+            "pending" => ResultStatus::Pending,
+            "undefined" => ResultStatus::Undefined,
+            "ambiguous" => ResultStatus::Ambiguous,
+            _ => ResultStatus::Failed,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultStatus::Passed => "passed",
+            ResultStatus::Skipped => "skipped",
+            ResultStatus::Pending => "pending",
+            ResultStatus::Undefined => "undefined",
+            ResultStatus::Ambiguous => "ambiguous",
+            ResultStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub media_type: String,
+    /// Base64 for binary media types, plain text otherwise
+    pub data: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StepResult {
+    /// `Before` and `After` for hooks
+    pub keyword: String,
+    pub name: String,
+    pub line: Option<u32>,
+    pub status: ResultStatus,
+    pub seconds: f64,
+    pub error_message: Option<String>,
+    pub stack_trace: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Result of one scenario, or one example row of an outline, keyed by `uri:line`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScenarioResult {
+    pub key: String,
+    pub uri: String,
+    pub line: u32,
+    pub feature_name: String,
+    pub name: String,
+    pub status: ResultStatus,
+    pub seconds: f64,
+    pub steps: Vec<StepResult>,
+    /// Line of the scenario this result belongs to, set by `run_selection::link_results`
+    pub scenario_line: Option<u32>,
+    /// `uri:scenario_line`, the same for every row of an outline and across runs
+    pub scenario_key: Option<String>,
+}
+
+/// How long one scenario (or one example row of an outline) took in a report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioDuration {
+    /// Feature path as cucumber reported it, relative to where it ran
+    pub uri: String,
+    pub line: u32,
+    pub seconds: f64,
+}
+
+fn normalize_uri(uri: &str) -> String {
+    uri.replace('\\', "/").trim_start_matches("./").to_string()
+}
+
+/// Read scenario durations from a `--format json` report.
+pub fn read_durations(report_path: &str) -> Result<Vec<ScenarioDuration>, String> {
+    let content = fs::read_to_string(report_path)
+        .map_err(|e| format!("Failed to read {}: {}", report_path, e))?;
+    parse_durations(&content).map_err(|e| format!("Invalid cucumber report {}: {}", report_path, e))
+}
+
+pub fn parse_durations(content: &str) -> Result<Vec<ScenarioDuration>, String> {
+    let features: Vec<JsonFeature> = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut durations = Vec::new();
+    for feature in features {
+        for element in feature.elements {
+            // Some formatters report the background as its own element
+            if element.kind == "background" {
+                continue;
+            }
+            let nanoseconds: u64 = element
+                .before
+                .iter()
+                .chain(&element.steps)
+                .chain(&element.after)
+                .filter_map(|step| step.result.as_ref().and_then(|r| r.duration))
+                .sum();
+            durations.push(ScenarioDuration {
+                uri: normalize_uri(&feature.uri),
+                line: element.line,
+                seconds: nanoseconds as f64 / 1e9,
+            });
+        }
+    }
+
+    Ok(durations)
+}
+
+/// Split a cucumber error into its message and the stack trace that follows it.
+fn split_error(error: &str) -> (String, Option<String>) {
+    let lines: Vec<&str> = error.lines().collect();
+    match lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("at "))
+    {
+        Some(0) | None => (error.trim_end().to_string(), None),
+        Some(index) => (
+            lines[..index].join("\n").trim_end().to_string(),
+            Some(lines[index..].join("\n")),
+        ),
+    }
+}
+
+fn step_result(step: JsonStep, hook: &str) -> StepResult {
+    let result = step.result.unwrap_or(JsonResult {
+        status: String::new(),
+        duration: None,
+        error_message: None,
+    });
+    let (error_message, stack_trace) = match result.error_message.as_deref().map(split_error) {
+        Some((message, stack)) => (Some(message), stack),
+        None => (None, None),
+    };
+
+    StepResult {
+        keyword: if step.keyword.trim().is_empty() {
+            hook.to_string()
+        } else {
+            step.keyword.trim().to_string()
+        },
+        name: step.name,
+        line: step.line,
+        status: ResultStatus::parse(&result.status),
+        seconds: result.duration.unwrap_or(0) as f64 / 1e9,
+        error_message,
+        stack_trace,
+        attachments: step
+            .embeddings
+            .into_iter()
+            .map(|embedding| Attachment {
+                media_type: embedding.mime_type,
+                data: embedding.data,
+            })
+            .collect(),
+    }
+}
+
+/// Read per-scenario and per-step results from a `--format json` report.
+pub fn read_results(report_path: &str) -> Result<Vec<ScenarioResult>, String> {
+    let content = fs::read_to_string(report_path)
+        .map_err(|e| format!("Failed to read {}: {}", report_path, e))?;
+    parse_results(&content).map_err(|e| format!("Invalid cucumber report {}: {}", report_path, e))
+}
+
+pub fn parse_results(content: &str) -> Result<Vec<ScenarioResult>, String> {
+    let features: Vec<JsonFeature> = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut results = Vec::new();
+    for feature in features {
+        let uri = normalize_uri(&feature.uri);
+        for element in feature.elements {
+            if element.kind == "background" {
+                continue;
+            }
+            let steps: Vec<StepResult> = element
+                .before
+                .into_iter()
+                .map(|step| step_result(step, "Before"))
+                .chain(element.steps.into_iter().map(|step| step_result(step, "")))
+                .chain(
+                    element
+                        .after
+                        .into_iter()
+                        .map(|step| step_result(step, "After")),
+                )
+                .collect();
+
+            results.push(ScenarioResult {
+                key: format!("{}:{}", uri, element.line),
+                uri: uri.clone(),
+                line: element.line,
+                feature_name: feature.name.clone(),
+                name: element.name,
+                status: steps
+                    .iter()
+                    .map(|step| step.status)
+                    .max()
+                    .unwrap_or(ResultStatus::Passed),
+                seconds: steps.iter().map(|step| step.seconds).sum(),
+                steps,
+                scenario_line: None,
+                scenario_key: None,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_durations() {
+        let report = r#"[{
+            "uri": "./features/a.feature",
+            "elements": [
+                {"line": 3, "type": "scenario", "steps": [
+                    {"result": {"status": "passed", "duration": 1500000000}},
+                    {"result": {"status": "skipped"}}
+                ]},
+                {"line": 12, "type": "scenario", "steps": [
+                    {"hidden": true, "result": {"status": "passed", "duration": 250000000}}
+                ]}
+            ]
+        }]"#;
+
+        assert_eq!(
+            parse_durations(report).unwrap(),
+            vec![
+                ScenarioDuration {
+                    uri: "features/a.feature".into(),
+                    line: 3,
+                    seconds: 1.5
+                },
+                ScenarioDuration {
+                    uri: "features/a.feature".into(),
+                    line: 12,
+                    seconds: 0.25
+                },
+            ]
+        );
+        assert!(parse_durations("{}").is_err());
+    }
+
+    #[test]
+    fn test_parse_results() {
+        let report = r#"[{
+            "uri": "features/a.feature",
+            "name": "A",
+            "elements": [
+                {"line": 14, "name": "Rows", "type": "scenario",
+                 "before": [{"result": {"status": "passed", "duration": 1000000}}],
+                 "steps": [
+                    {"keyword": "Given ", "name": "a 1", "line": 8,
+                     "result": {"status": "failed", "duration": 2000000000,
+                                "error_message": "Error: boom\n    at Context.<anonymous> (steps.ts:3:9)"},
+                     "embeddings": [{"data": "aGk=", "mime_type": "image/png"}]},
+                    {"keyword": "Then ", "name": "done", "line": 9, "result": {"status": "skipped"}}
+                 ]},
+                {"line": 2, "name": "Todo", "type": "scenario", "steps": [
+                    {"keyword": "Given ", "name": "later", "line": 3, "result": {"status": "undefined"}}
+                ]}
+            ]
+        }]"#;
+
+        let results = parse_results(report).unwrap();
+
+        assert_eq!(results[0].key, "features/a.feature:14");
+        assert_eq!(results[0].status, ResultStatus::Failed);
+        assert!((results[0].seconds - 2.001).abs() < 1e-9);
+        let keywords: Vec<&str> = results[0]
+            .steps
+            .iter()
+            .map(|s| s.keyword.as_str())
+            .collect();
+        assert_eq!(keywords, vec!["Before", "Given", "Then"]);
+        let failed = &results[0].steps[1];
+        assert_eq!(failed.error_message.as_deref(), Some("Error: boom"));
+        assert_eq!(
+            failed.stack_trace.as_deref(),
+            Some("    at Context.<anonymous> (steps.ts:3:9)")
+        );
+        assert_eq!(failed.attachments[0].media_type, "image/png");
+        assert_eq!(results[1].status, ResultStatus::Undefined);
+    }
+}
@@ -1,30 +1,676 @@
-use std::path::Path;
-use std::process::{Command, Stdio};
+use crate::browser_env;
+use crate::cucumber_report_reader::{self, ScenarioResult};
+use crate::run_selection;
+use crate::toolchain::{self, Toolchain};
+use chrono::Utc;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+use tokio::sync::oneshot;
+use uuid::Uuid;
 
+pub const START_EVENT: &str = "e2e-run-start";
+pub const OUTPUT_EVENT: &str = "e2e-run-output";
+pub const EXIT_EVENT: &str = "e2e-run-exit";
+
+/// Profile used when a run does not pick one
+pub const DEFAULT_PROFILE: &str = "local";
+
+/// How long a cancelled run gets to shut down its browsers before it is killed
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+// Output still buffered when the process exits, browsers left behind may keep the pipes open
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How the browsers of a run behave, for debugging a scenario without editing `.env`. The
+/// flags combine, `browser_env` maps them to the variables the repo reads.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct BrowserFlags {
+    pub headed: bool,
+    /// Playwright inspector, steps through the browser actions
+    pub debug: bool,
+    /// Delay between browser actions in milliseconds
+    pub slow_mo_ms: Option<u32>,
+    /// Failed scenarios leave their browser open for inspection
+    pub keep_open: bool,
+    /// Record a trace for every scenario, not only failed ones
+    pub trace: bool,
+}
+
+/// How cucumber is invoked, independent of what it runs
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RunOptions {
+    /// Profile from the repo's cucumber config, `DEFAULT_PROFILE` when not set
+    pub profile: Option<String>,
+    pub extra_args: Vec<String>,
+    /// Set on the cucumber process, so they win over the repo's `.env` and the browser flags
+    pub env: BTreeMap<String, String>,
+    pub browser: BrowserFlags,
+}
+
+impl RunOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.profile.as_deref().is_some_and(|p| p.trim().is_empty()) {
+            return Err("Profile name must not be empty".to_string());
+        }
+        if let Some(key) = self
+            .env
+            .keys()
+            .find(|key| key.is_empty() || key.contains(['=', '\0']))
+        {
+            return Err(format!("Invalid environment variable name: {:?}", key));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ActiveRun {
+    pub run_id: String,
+    pub pid: Option<u32>,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+    pub target: String,
+    pub cancelling: bool,
+}
+
+struct RegisteredRun {
+    info: ActiveRun,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+lazy_static! {
+    static ref RUNS: Mutex<HashMap<String, RegisteredRun>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunStart {
+    pub run_id: String,
+    pub folder_path: String,
+    pub target: String,
+    pub command_line: String,
+    /// With the profile resolved, enough to start the same run again
+    pub options: RunOptions,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunOutput {
+    pub run_id: String,
+    pub stream: Stream,
+    pub line: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunExit {
+    pub run_id: String,
+    pub success: bool,
+    pub code: Option<i32>,
+    pub status: String,
+    pub duration_ms: u64,
+    pub cancelled: bool,
+    /// JSON report cucumber wrote for the run
+    pub report_path: Option<String>,
+    /// Per-scenario results from the report, `None` when cucumber wrote none
+    pub results: Option<Vec<ScenarioResult>>,
+    /// Why the report could not be read
+    pub report_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunEvent {
+    Start(RunStart),
+    Output(RunOutput),
+    Exit(RunExit),
+}
+
+pub type EventSink = Arc<dyn Fn(RunEvent) + Send + Sync>;
+
+/// Start cucumber for a feature file, optionally filtered to one scenario, and return
+/// the run ID right away. The start, output lines and the exit are reported through
+/// `on_event`.
+/// Must be called from within the tokio runtime.
 pub fn run_cucumber(
     folder_path: &str,
     feature_file: &str,
     scenario_name: Option<&str>,
+    options: &RunOptions,
+    on_event: impl Fn(RunEvent) + Send + Sync + 'static,
+) -> Result<String, String> {
+    let (args, target) = match scenario_name {
+        Some(name) => (
+            scenario_args(folder_path, feature_file, name),
+            format!("{} ({})", feature_file, name),
+        ),
+        None => (vec![feature_file.to_string()], feature_file.to_string()),
+    };
+
+    run_cucumber_args(folder_path, args, target, options, on_event)
+}
+
+/// Target the scenarios named exactly `name` by line, `--name` alone is a regex match
+/// and "Create invoice" would also run "Create invoice draft". When the file can't be
+/// parsed here, an anchored `--name` still lets cucumber find them.
+fn scenario_args(folder_path: &str, feature_file: &str, name: &str) -> Vec<String> {
+    match run_selection::scenario_lines(folder_path, feature_file, name) {
+        Ok(lines) => {
+            let lines: Vec<String> = lines.iter().map(u32::to_string).collect();
+            vec![format!("{}:{}", feature_file, lines.join(":"))]
+        }
+        Err(_) => vec![
+            feature_file.to_string(),
+            "--name".to_string(),
+            format!("^{}$", regex::escape(name)),
+        ],
+    }
+}
+
+/// Run several `file` or `file:line` targets in a single cucumber invocation.
+pub fn run_cucumber_targets(
+    folder_path: &str,
+    targets: &[String],
+    options: &RunOptions,
+    on_event: impl Fn(RunEvent) + Send + Sync + 'static,
+) -> Result<String, String> {
+    if targets.is_empty() {
+        return Err("Nothing to run".to_string());
+    }
+
+    run_cucumber_args(
+        folder_path,
+        targets.to_vec(),
+        targets.join(" "),
+        options,
+        on_event,
+    )
+}
+
+fn report_path() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join("protestify").join("reports");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create report dir {}: {}", dir.display(), e))?;
+    Ok(dir.join(format!("{}.json", Uuid::new_v4())))
+}
+
+/// Fill in the results of a finished run from its JSON report, linked to the scenarios
+/// in `folder_path`
+fn attach_results(exit: &mut RunExit, report_path: &Path, folder_path: &str) {
+    if !report_path.exists() {
+        return;
+    }
+    let path = report_path.to_string_lossy().to_string();
+    match cucumber_report_reader::read_results(&path) {
+        Ok(mut results) => {
+            run_selection::link_results(folder_path, &mut results);
+            for result in results.iter_mut() {
+                result.scenario_key = result
+                    .scenario_line
+                    .map(|line| format!("{}:{}", result.uri, line));
+            }
+            exit.results = Some(results);
+        }
+        Err(e) => exit.report_error = Some(e),
+    }
+    exit.report_path = Some(path);
+}
+
+fn run_cucumber_args(
+    folder_path: &str,
+    mut args: Vec<String>,
+    target: String,
+    options: &RunOptions,
+    on_event: impl Fn(RunEvent) + Send + Sync + 'static,
 ) -> Result<String, String> {
-    let folder = Path::new(folder_path);
-    let mut command = Command::new("npx");
-    command.arg("cucumber-js").arg(feature_file);
+    options.validate()?;
+    let options = RunOptions {
+        profile: Some(
+            options
+                .profile
+                .clone()
+                .unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        ),
+        ..options.clone()
+    };
+    let report = report_path()?;
+    let folder = folder_path.to_string();
+    let on_event: EventSink = Arc::new(on_event);
+    let toolchain = toolchain::resolve(Path::new(folder_path));
+    let (runner, mut cucumber_args) = toolchain.package_runner();
+    cucumber_args.push("cucumber-js".to_string());
+    cucumber_args.append(&mut args);
+    cucumber_args.extend([
+        "-p".to_string(),
+        options.profile.clone().unwrap_or_default(),
+    ]);
+    cucumber_args.extend(options.extra_args.iter().cloned());
+    cucumber_args.extend(["--format".to_string(), format!("json:{}", report.display())]);
 
-    if let Some(name) = scenario_name {
-        command.arg("--name").arg(name);
+    spawn_process(
+        &toolchain,
+        runner,
+        &cucumber_args,
+        Path::new(folder_path),
+        target,
+        &options,
+        Arc::new(move |event| match event {
+            // Reading a large report blocks, keep it off the async workers
+            RunEvent::Exit(mut exit) => {
+                let (report, folder, on_event) = (report.clone(), folder.clone(), on_event.clone());
+                tokio::task::spawn_blocking(move || {
+                    attach_results(&mut exit, &report, &folder);
+                    on_event(RunEvent::Exit(exit));
+                });
+            }
+            event => on_event(event),
+        }),
+    )
+}
+
+/// Runs that have not exited yet, oldest first.
+pub fn active_runs() -> Vec<ActiveRun> {
+    let runs = RUNS.lock().unwrap();
+    let mut active: Vec<ActiveRun> = runs.values().map(|run| run.info.clone()).collect();
+    active.sort_by_key(|run| run.started_at);
+    active
+}
+
+/// Ask a run to stop. Its process group gets SIGTERM and, if it is still alive after
+/// the grace period, SIGKILL. The exit event follows once the process is gone.
+pub fn cancel_run(run_id: &str) -> Result<(), String> {
+    let mut runs = RUNS.lock().unwrap();
+    let run = runs
+        .get_mut(run_id)
+        .ok_or_else(|| format!("Run not found: {}", run_id))?;
+
+    if let Some(cancel) = run.cancel.take() {
+        run.info.cancelling = true;
+        let _ = cancel.send(());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn signal_group(child: &Child, force: bool) {
+    if let Some(pid) = child.id() {
+        let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+        // The child leads its own process group, a negative pid signals the whole group
+        unsafe {
+            libc::kill(-(pid as i32), signal);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn signal_group(child: &Child, force: bool) {
+    if let Some(pid) = child.id() {
+        let pid = pid.to_string();
+        let mut args = vec!["/T", "/PID", pid.as_str()];
+        if force {
+            args.push("/F");
+        }
+        let _ = std::process::Command::new("taskkill").args(args).status();
+    }
+}
+
+async fn terminate(child: &mut Child) -> std::io::Result<std::process::ExitStatus> {
+    signal_group(child, false);
+    match tokio::time::timeout(CANCEL_GRACE_PERIOD, child.wait()).await {
+        Ok(status) => status,
+        Err(_) => {
+            signal_group(child, true);
+            child.wait().await
+        }
+    }
+}
+
+async fn forward_lines(
+    reader: impl AsyncRead + Unpin,
+    run_id: String,
+    stream: Stream,
+    on_event: EventSink,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    // Read raw bytes so invalid UTF-8 in the output does not end the stream
+    while let Ok(read) = reader.read_until(b'\n', &mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buffer)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        on_event(RunEvent::Output(RunOutput {
+            run_id: run_id.clone(),
+            stream,
+            line,
+        }));
+        buffer.clear();
     }
+}
 
-    command.arg("-p").arg("local").current_dir(folder);
+/// The command as it would be typed in a shell
+fn command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg.replace('"', "\\\""))
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn spawn_process(
+    toolchain: &Toolchain,
+    program: &str,
+    args: &[String],
+    folder: &Path,
+    target: String,
+    options: &RunOptions,
+    on_event: EventSink,
+) -> Result<String, String> {
+    let mut command = toolchain.command(program);
+    command
+        .args(args)
+        .envs(browser_env::for_repo(&folder.to_string_lossy()).variables(&options.browser))
+        .envs(&options.env)
+        .current_dir(folder)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Own process group, so cancelling also reaches the browsers started by the tests
+    #[cfg(unix)]
+    command.process_group(0);
 
-    let status = command
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
+    let mut child = command
+        .spawn()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
 
-    if status.success() {
-        Ok("Command executed successfully".to_string())
-    } else {
-        Err(format!("Command failed with status: {}", status))
+    let run_id = Uuid::new_v4().to_string();
+    let started = Instant::now();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let (cancel, cancelled) = oneshot::channel();
+    let started_at = Utc::now().timestamp_millis();
+
+    RUNS.lock().unwrap().insert(
+        run_id.clone(),
+        RegisteredRun {
+            info: ActiveRun {
+                run_id: run_id.clone(),
+                pid: child.id(),
+                started_at,
+                target: target.clone(),
+                cancelling: false,
+            },
+            cancel: Some(cancel),
+        },
+    );
+    on_event(RunEvent::Start(RunStart {
+        run_id: run_id.clone(),
+        folder_path: folder.to_string_lossy().to_string(),
+        target,
+        command_line: command_line(program, args),
+        options: options.clone(),
+        started_at,
+    }));
+
+    let id = run_id.clone();
+    tokio::spawn(async move {
+        let output = tokio::spawn({
+            let (id, on_event) = (id.clone(), on_event.clone());
+            async move {
+                tokio::join!(
+                    forward_lines(stdout, id.clone(), Stream::Stdout, on_event.clone()),
+                    forward_lines(stderr, id, Stream::Stderr, on_event),
+                );
+            }
+        });
+
+        let (status, was_cancelled) = tokio::select! {
+            status = child.wait() => (status, false),
+            Ok(()) = cancelled => (terminate(&mut child).await, true),
+        };
+        let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, output).await;
+        RUNS.lock().unwrap().remove(&id);
+
+        let exit = match status {
+            Ok(status) => RunExit {
+                run_id: id,
+                success: status.success() && !was_cancelled,
+                code: status.code(),
+                status: status.to_string(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                cancelled: was_cancelled,
+                report_path: None,
+                results: None,
+                report_error: None,
+            },
+            Err(e) => RunExit {
+                run_id: id,
+                success: false,
+                code: None,
+                status: format!("Failed to wait for command: {}", e),
+                duration_ms: started.elapsed().as_millis() as u64,
+                cancelled: was_cancelled,
+                report_path: None,
+                results: None,
+                report_error: None,
+            },
+        };
+        on_event(RunEvent::Exit(exit));
+    });
+
+    Ok(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_spawn_process_streams_output() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let args = [
+            "-c".to_string(),
+            "echo $OUT $HEADLESS $SLOW_MO; echo err >&2; exit 3".to_string(),
+        ];
+        // Explicit env wins over the variables of the browser flags
+        let options = RunOptions {
+            env: BTreeMap::from([
+                ("OUT".to_string(), "out".to_string()),
+                ("SLOW_MO".to_string(), "50".to_string()),
+            ]),
+            browser: BrowserFlags {
+                headed: true,
+                slow_mo_ms: Some(500),
+                ..BrowserFlags::default()
+            },
+            ..RunOptions::default()
+        };
+        let run_id = spawn_process(
+            &Toolchain::default(),
+            "sh",
+            &args,
+            Path::new("."),
+            "echo".to_string(),
+            &options,
+            Arc::new(move |event| {
+                let _ = sender.send(event);
+            }),
+        )
+        .unwrap();
+
+        let mut lines = Vec::new();
+        let exit = loop {
+            match receiver.recv().await.unwrap() {
+                RunEvent::Start(start) => {
+                    assert_eq!(start.run_id, run_id);
+                    assert_eq!(
+                        start.command_line,
+                        "sh -c \"echo $OUT $HEADLESS $SLOW_MO; echo err >&2; exit 3\""
+                    );
+                    assert_eq!(start.options, options);
+                }
+                RunEvent::Output(output) => {
+                    assert_eq!(output.run_id, run_id);
+                    lines.push((output.stream, output.line));
+                }
+                RunEvent::Exit(exit) => break exit,
+            }
+        };
+
+        lines.sort_by_key(|(stream, _)| *stream == Stream::Stderr);
+        assert_eq!(
+            lines,
+            vec![
+                (Stream::Stdout, "out false 50".to_string()),
+                (Stream::Stderr, "err".to_string())
+            ]
+        );
+        assert_eq!(exit.run_id, run_id);
+        assert!(!exit.success);
+        assert_eq!(exit.code, Some(3));
+        assert!(!exit.cancelled);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_run_stops_process_group() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        // The background sleep stands in for a browser started by the test
+        let args = ["-c".to_string(), "sleep 30 & sleep 30".to_string()];
+        let run_id = spawn_process(
+            &Toolchain::default(),
+            "sh",
+            &args,
+            Path::new("."),
+            "sleepy".to_string(),
+            &RunOptions::default(),
+            Arc::new(move |event| {
+                let _ = sender.send(event);
+            }),
+        )
+        .unwrap();
+
+        let active = active_runs();
+        let run = active.iter().find(|run| run.run_id == run_id).unwrap();
+        assert_eq!(run.target, "sleepy");
+        assert!(run.pid.is_some());
+
+        cancel_run(&run_id).unwrap();
+        let exit = loop {
+            if let RunEvent::Exit(exit) = receiver.recv().await.unwrap() {
+                break exit;
+            }
+        };
+
+        assert!(exit.cancelled);
+        assert!(!exit.success);
+        assert!(exit.duration_ms < CANCEL_GRACE_PERIOD.as_millis() as u64);
+        assert!(!active_runs().iter().any(|run| run.run_id == run_id));
+        assert!(cancel_run(&run_id).is_err());
+    }
+
+    #[test]
+    fn test_attach_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = dir.path().join("report.json");
+        let mut exit = RunExit {
+            run_id: "run".into(),
+            success: true,
+            code: Some(0),
+            status: "exit status: 0".into(),
+            duration_ms: 1,
+            cancelled: false,
+            report_path: None,
+            results: None,
+            report_error: None,
+        };
+
+        let folder = dir.path().to_str().unwrap();
+        attach_results(&mut exit, &report, folder);
+        assert!(exit.report_path.is_none() && exit.results.is_none());
+
+        std::fs::write(&report, "[{").unwrap();
+        attach_results(&mut exit, &report, folder);
+        assert!(exit.results.is_none());
+        let error = exit.report_error.take().unwrap();
+        assert!(error.starts_with("Invalid cucumber report"));
+
+        std::fs::create_dir_all(dir.path().join("features")).unwrap();
+        std::fs::write(
+            dir.path().join("features/a.feature"),
+            "Feature: A\n  Scenario: S\n    Given x\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &report,
+            r#"[{"uri": "features/a.feature", "elements": [{"line": 2, "steps": []}]}]"#,
+        )
+        .unwrap();
+        attach_results(&mut exit, &report, folder);
+        let results = exit.results.unwrap();
+        assert_eq!(results[0].key, "features/a.feature:2");
+        assert_eq!(results[0].scenario_key.as_deref(), Some("features/a.feature:2"));
+        assert!(exit.report_path.is_some());
+    }
+
+    #[test]
+    fn test_scenario_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        std::fs::create_dir_all(dir.path().join("features")).unwrap();
+        std::fs::write(
+            dir.path().join("features/a.feature"),
+            "Feature: A\n  Scenario: Pay (twice)\n    Given x\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            scenario_args(repo, "features/a.feature", "Pay (twice)"),
+            vec!["features/a.feature:2"]
+        );
+        // Not in the file as it is now, cucumber matches the name itself
+        assert_eq!(
+            scenario_args(repo, "features/a.feature", "Pay"),
+            vec!["features/a.feature", "--name", "^Pay$"]
+        );
+        assert_eq!(
+            scenario_args(repo, "features/b.feature", "Pay (twice)"),
+            vec!["features/b.feature", "--name", r"^Pay \(twice\)$"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_cucumber_targets_requires_targets() {
+        let options = RunOptions::default();
+        assert!(run_cucumber_targets(".", &[], &options, |_| {}).is_err());
+
+        let options = RunOptions {
+            env: BTreeMap::from([("A=B".to_string(), "1".to_string())]),
+            ..RunOptions::default()
+        };
+        let targets = ["features/a.feature".to_string()];
+        assert!(run_cucumber_targets(".", &targets, &options, |_| {}).is_err());
     }
 }
@@ -0,0 +1,171 @@
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+
+/// Schema migrations, applied in order. `PRAGMA user_version` stores how many have
+/// already run, so only append to this list.
+const MIGRATIONS: &[&str] = &[
+    // 1: suite metrics snapshots
+    "CREATE TABLE metric_snapshots (
+        id INTEGER PRIMARY KEY,
+        repo_path TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL,
+        commit_sha TEXT,
+        source TEXT NOT NULL,
+        features INTEGER NOT NULL,
+        scenarios INTEGER NOT NULL,
+        broken INTEGER NOT NULL,
+        organizations INTEGER NOT NULL,
+        teams INTEGER NOT NULL,
+        owners INTEGER NOT NULL
+    );
+    CREATE INDEX metric_snapshots_repo ON metric_snapshots (repo_path, recorded_at);
+    CREATE TABLE metric_breakdowns (
+        snapshot_id INTEGER NOT NULL REFERENCES metric_snapshots (id) ON DELETE CASCADE,
+        dimension TEXT NOT NULL,
+        name TEXT NOT NULL,
+        scenarios INTEGER NOT NULL,
+        broken INTEGER NOT NULL
+    );
+    CREATE INDEX metric_breakdowns_snapshot ON metric_breakdowns (snapshot_id);",
+    // 2: named test suites
+    "CREATE TABLE suites (
+        repo_path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        definition TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (repo_path, name)
+    );",
+    // 3: pending run queue and app level settings
+    "CREATE TABLE run_queue (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        folder_path TEXT NOT NULL,
+        feature_file TEXT NOT NULL,
+        scenario_name TEXT,
+        enqueued_at INTEGER NOT NULL,
+        run_id TEXT
+    );
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // 4: run history
+    "CREATE TABLE runs (
+        id TEXT PRIMARY KEY,
+        repo_path TEXT NOT NULL,
+        target TEXT NOT NULL,
+        command_line TEXT NOT NULL,
+        profile TEXT,
+        env TEXT NOT NULL,
+        browser TEXT NOT NULL,
+        commit_sha TEXT,
+        branch TEXT,
+        dirty INTEGER NOT NULL,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        exit_code INTEGER,
+        status TEXT NOT NULL,
+        log_path TEXT,
+        report_path TEXT
+    );
+    CREATE INDEX runs_repo ON runs (repo_path, started_at);
+    CREATE TABLE run_results (
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        uri TEXT NOT NULL,
+        line INTEGER NOT NULL,
+        scenario_line INTEGER,
+        name TEXT NOT NULL,
+        status TEXT NOT NULL,
+        seconds REAL NOT NULL,
+        error_message TEXT
+    );
+    CREATE INDEX run_results_run ON run_results (run_id);",
+    // 5: run options for queued runs and named run presets
+    "ALTER TABLE run_queue ADD COLUMN options TEXT NOT NULL DEFAULT '{}';
+    CREATE TABLE run_presets (
+        repo_path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        options TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (repo_path, name)
+    );",
+    // 6: extra args of runs and reruns linked to the run they repeat
+    "ALTER TABLE runs ADD COLUMN extra_args TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE runs ADD COLUMN parent_run_id TEXT REFERENCES runs (id) ON DELETE SET NULL;",
+    // 7: screenshots, videos, traces and logs found after a run
+    "CREATE TABLE run_artifacts (
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        modified_at INTEGER NOT NULL,
+        scenario_key TEXT,
+        shared INTEGER NOT NULL,
+        PRIMARY KEY (run_id, path)
+    );",
+];
+
+/// Open (or create) the local database at `path` and bring its schema up to date.
+pub fn open(path: &Path) -> Result<Connection, String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create data dir: {}", e))?;
+    }
+
+    let connection =
+        Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+    migrate(&connection)?;
+
+    Ok(connection)
+}
+
+fn migrate(connection: &Connection) -> Result<(), String> {
+    connection
+        .execute_batch("PRAGMA foreign_keys = ON;")
+        .map_err(|e| e.to_string())?;
+
+    let version: usize = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        connection
+            .execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                migration,
+                index + 1
+            ))
+            .map_err(|e| format!("Migration {} failed: {}", index + 1, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn open_in_memory() -> Connection {
+    let connection = Connection::open_in_memory().unwrap();
+    migrate(&connection).unwrap();
+    connection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_open_runs_migrations_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested/protestify.db");
+
+        let connection = open(&path).unwrap();
+        let version: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        drop(connection);
+
+        // Reopening must not try to create the tables again
+        assert!(open(&path).is_ok());
+    }
+}
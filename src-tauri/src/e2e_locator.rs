@@ -3,7 +3,6 @@ use serde::{Deserialize, Serialize};
 use serde_json::{from_str, Value};
 use std::path::{Path, PathBuf};
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, BufReader};
 
 async fn is_valid_package_json(package_json_path: &PathBuf) -> bool {
     if let Ok(contents) = fs::read_to_string(package_json_path).await {
@@ -85,23 +84,25 @@ async fn find_package_json_with_repo_url(entry_point: Option<&str>) -> Result<Op
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct E2eEnv {
-    password: String,
-    api_url: String,
-    app_url: String,
+pub(crate) struct E2eEnv {
+    pub(crate) password: String,
+    pub(crate) api_url: String,
+    pub(crate) app_url: String,
 }
 
-async fn read_from_env_file(file_path: &str) -> Result<E2eEnv, String> {
-    let path = Path::new(file_path);
-    let file = fs::File::open(&path).await.map_err(|_| "File not found".to_string())?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+pub(crate) async fn read_from_env_file(file_path: &str) -> Result<E2eEnv, String> {
+    let content = fs::read_to_string(file_path)
+        .await
+        .map_err(|_| "File not found".to_string())?;
+    Ok(parse_env(&content))
+}
 
+pub(crate) fn parse_env(content: &str) -> E2eEnv {
     let mut password = String::new();
     let mut api_url = String::new();
     let mut app_url = String::new();
 
-    while let Ok(Some(line)) = lines.next_line().await {
+    for line in content.lines() {
         if let Some((key, value)) = line.split_once('=') {
             let key = key.trim();
             let value = value.trim().to_string();
@@ -114,11 +115,11 @@ async fn read_from_env_file(file_path: &str) -> Result<E2eEnv, String> {
         }
     }
 
-    Ok(E2eEnv {
+    E2eEnv {
         password,
         api_url,
         app_url,
-    })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
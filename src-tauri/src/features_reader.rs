@@ -1,12 +1,13 @@
+use crate::git_reader::Authorship;
+use crate::reference_extractor::References;
+use crate::repo_source::RepoSource;
 use lazy_static::lazy_static;
 use phf::phf_map;
 use regex::Regex;
 use serde::Serialize;
 use std::collections::BTreeSet;
-use std::io::{BufRead, BufReader, Result};
-use tokio::fs::read;
+use std::io::{BufRead, BufReader, Error, Result};
 use uuid::Uuid;
-use walkdir::WalkDir;
 
 #[derive(Serialize)]
 pub struct Feature {
@@ -15,6 +16,9 @@ pub struct Feature {
     pub description: String,
     pub file_path: String,
     pub tags: Vec<String>,
+    pub comments: Vec<String>,
+    pub references: References,
+    pub authorship: Option<Authorship>,
 }
 
 #[derive(Serialize)]
@@ -28,6 +32,11 @@ pub struct Scenario {
     pub tags: Vec<String>,
     pub feature_id: String,
     pub organization_name: String,
+    pub line: u32,
+    pub end_line: u32,
+    pub comments: Vec<String>,
+    pub references: References,
+    pub authorship: Option<Authorship>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -101,21 +110,32 @@ fn get_target_string(name: &str, flag: Option<&str>, date: Option<&str>) -> Stri
 }
 
 pub async fn get_all_features(base_path: String) -> Result<(Vec<Feature>, Vec<Scenario>)> {
+    get_features_from(&RepoSource::working_dir(&base_path)).await
+}
+
+pub async fn get_features_from(source: &RepoSource) -> Result<(Vec<Feature>, Vec<Scenario>)> {
     let mut features = Vec::new();
     let mut scenarios = Vec::new();
 
-    for entry in WalkDir::new(base_path).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(std::ffi::OsStr::to_str) == Some("feature") {
-            let contents = read(&path).await?;
-            let reader = BufReader::new(&contents[..]);
-            let file_path = path.to_string_lossy().into_owned();
-
-            let (file_feature, file_scenarios) = process_file_content(reader, file_path).await?;
-
-            features.push(file_feature);
-            scenarios.extend(file_scenarios);
-        }
+    // Reading a git tree spawns git, keep it off the async workers
+    let reader_source = source.clone();
+    let files = tokio::task::spawn_blocking(move || {
+        let relatives = reader_source.list_files("", &["feature"])?;
+        let contents = reader_source.read_files(&relatives)?;
+        Ok::<_, String>(relatives.into_iter().zip(contents))
+    })
+    .await
+    .map_err(Error::other)?
+    .map_err(Error::other)?;
+
+    for (relative, contents) in files {
+        let reader = BufReader::new(contents.as_bytes());
+        let file_path = source.display_path(&relative);
+
+        let (file_feature, file_scenarios) = process_file_content(reader, file_path).await?;
+
+        features.push(file_feature);
+        scenarios.extend(file_scenarios);
     }
 
     Ok((features, scenarios))
@@ -128,19 +148,46 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
         description: String::new(),
         file_path: path.clone(),
         tags: Vec::new(),
+        comments: Vec::new(),
+        references: References::default(),
+        authorship: None,
     };
 
     let mut scenarios = Vec::new();
     let mut current_scenario: Option<Scenario> = None;
 
     let mut pending_tags = Vec::new();
+    // Comments directly above a keyword belong to it, other comments to the enclosing block
+    let mut pending_comments = Vec::new();
     let mut example_lines_count: u32 = 0;
+    // Last line with scenario content, tags and comments may belong to the next scenario
+    let mut last_content_line: u32 = 0;
+    // Organization set in the Background, scenarios inherit it unless they set their own
+    let mut background_organization = String::new();
 
     let mut context = Context::None;
 
-    for line in reader.lines() {
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index as u32 + 1;
         let line = line?.trim().to_string();
-        if line.starts_with("@") {
+
+        let is_keyword = line.starts_with("Feature:")
+            || line.starts_with("Scenario:")
+            || line.starts_with("Scenario Outline:");
+        if !pending_comments.is_empty()
+            && !is_keyword
+            && !line.starts_with('@')
+            && !line.starts_with('#')
+        {
+            match current_scenario.as_mut() {
+                Some(scenario) => scenario.comments.append(&mut pending_comments),
+                None => feature.comments.append(&mut pending_comments),
+            }
+        }
+
+        if line.starts_with('#') {
+            pending_comments.push(line.trim_start_matches('#').trim().to_string());
+        } else if line.starts_with("@") {
             context = Context::None;
 
             pending_tags.extend(line.split_whitespace().map(String::from));
@@ -149,6 +196,7 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
 
             feature.name = line["Feature:".len()..].trim().to_string();
             feature.tags = pending_tags.clone();
+            feature.comments.append(&mut pending_comments);
 
             pending_tags.clear();
         } else if line.starts_with("Scenario:") || line.starts_with("Scenario Outline:") {
@@ -171,6 +219,7 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
                     }
                     _ => {}
                 }
+                scenario.end_line = last_content_line;
                 scenarios.push(scenario);
             }
 
@@ -187,7 +236,12 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
                 examples: 0,
                 tags: pending_tags.clone(),
                 feature_id: feature.id.clone(),
-                organization_name: String::new(),
+                organization_name: background_organization.clone(),
+                line: line_number,
+                end_line: line_number,
+                comments: std::mem::take(&mut pending_comments),
+                references: References::default(),
+                authorship: None,
             });
 
             pending_tags.clear();
@@ -230,6 +284,8 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
                         .targets
                         .insert(get_target_string(&name, flag, date));
                 }
+            } else if let Some(organization_name) = capture_group(&ORGANIZATION_RE, &line) {
+                background_organization = organization_name.trim().to_string();
             }
         } else {
             match context {
@@ -250,15 +306,21 @@ async fn process_file_content<R: BufRead>(reader: R, path: String) -> Result<(Fe
                 _ => {}
             }
         }
+
+        if !line.is_empty() && !line.starts_with('@') && !line.starts_with('#') {
+            last_content_line = line_number;
+        }
     }
 
     if let Some(mut scenario) = current_scenario {
+        scenario.comments.append(&mut pending_comments);
         match context {
             Context::ExamplesBody => {
                 scenario.examples = example_lines_count;
             }
             _ => {}
         }
+        scenario.end_line = last_content_line;
         scenarios.push(scenario);
     }
 
@@ -566,6 +628,8 @@ mod tests {
             assert_eq!(scenarios[0].tags[4], "@web");
             assert_eq!(scenarios[0].feature_id, feature.id);
             assert_eq!(scenarios[0].organization_name, "Some Org LLC");
+            assert_eq!(scenarios[0].line, 9);
+            assert_eq!(scenarios[0].end_line, 23);
 
             assert!(Uuid::parse_str(&scenarios[1].id).is_ok());
             assert_eq!(scenarios[1].name, "Another scenario");
@@ -576,6 +640,8 @@ mod tests {
             assert_eq!(scenarios[1].tags.len(), 0);
             assert_eq!(scenarios[1].feature_id, feature.id);
             assert_eq!(scenarios[1].organization_name, "Some Org LLC");
+            assert_eq!(scenarios[1].line, 25);
+            assert_eq!(scenarios[1].end_line, 28);
 
             assert!(Uuid::parse_str(&scenarios[2].id).is_ok());
             assert_eq!(scenarios[2].name, "Another with description");
@@ -589,6 +655,8 @@ mod tests {
             assert_eq!(scenarios[2].tags.len(), 0);
             assert_eq!(scenarios[2].feature_id, feature.id);
             assert_eq!(scenarios[2].organization_name, "Some Org LLC");
+            assert_eq!(scenarios[2].line, 30);
+            assert_eq!(scenarios[2].end_line, 35);
         }
 
         #[tokio::test]
@@ -824,5 +892,35 @@ mod tests {
             assert_eq!(feature.tags.len(), 0);
             assert_eq!(scenarios.len(), 0);
         }
+
+        #[tokio::test]
+        async fn test_comments_are_not_description() {
+            let content = r#"
+                Feature: Sample Feature
+                    This is a sample feature description.
+                    # see https://example.com/docs
+                    It continues here.
+
+                # Scenario comment
+                Scenario: Sample Scenario
+                    A scenario description.
+                    # owned by billing
+                    Given current organization is "Sample Org"
+            "#;
+            let reader = Cursor::new(content);
+            let path = "comments.feature".to_string();
+            let (feature, scenarios) = process_file_content(reader, path).await.unwrap();
+
+            assert_eq!(
+                feature.description,
+                "This is a sample feature description. It continues here."
+            );
+            assert_eq!(feature.comments, vec!["see https://example.com/docs"]);
+            assert_eq!(scenarios[0].description, "A scenario description.");
+            assert_eq!(
+                scenarios[0].comments,
+                vec!["Scenario comment", "owned by billing"]
+            );
+        }
     }
 }
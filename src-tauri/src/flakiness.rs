@@ -0,0 +1,323 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Age in days after which a flip counts half as much
+pub const DEFAULT_HALF_LIFE_DAYS: f64 = 14.0;
+const FAILURE_MESSAGES: usize = 3;
+const DAY_MS: f64 = 86_400_000.0;
+
+/// Outcome of one scenario in one finished run, outline rows already folded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub uri: String,
+    pub scenario_line: u32,
+    pub name: String,
+    pub run_id: String,
+    pub commit: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub ended_at: i64,
+    pub passed: bool,
+    pub error_message: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FailureMessage {
+    pub message: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FlakyScenario {
+    /// `uri:scenario_line`, as in the run history
+    pub key: String,
+    pub uri: String,
+    pub scenario_line: u32,
+    pub name: String,
+    /// Recency weighted share of same-commit reruns that changed outcome, 0 to 1
+    pub score: f64,
+    pub runs: usize,
+    pub failures: usize,
+    /// Outcome changes between consecutive runs on the same commit
+    pub flips: usize,
+    /// Failures followed by a pass on the same commit
+    pub retry_passes: usize,
+    pub last_flip_at: Option<i64>,
+    pub failure_messages: Vec<FailureMessage>,
+}
+
+/// Passed and failed scenario outcomes from finished runs, oldest first. Skipped,
+/// pending and undefined scenarios, cancelled runs and runs on a dirty working tree
+/// say nothing about flakiness.
+pub fn load_observations(
+    connection: &Connection,
+    repo_path: &str,
+) -> Result<Vec<Observation>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT r.uri, COALESCE(r.scenario_line, r.line), r.name, r.status, r.error_message,
+                runs.id, runs.commit_sha, runs.ended_at
+             FROM run_results r JOIN runs ON runs.id = r.run_id
+             WHERE runs.repo_path = ?1 AND runs.status IN ('passed', 'failed')
+                AND NOT runs.dirty AND r.status IN ('passed', 'failed')
+             ORDER BY runs.ended_at, r.line",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(params![repo_path], |row| {
+            let status: String = row.get(3)?;
+            Ok(Observation {
+                uri: row.get(0)?,
+                scenario_line: row.get(1)?,
+                name: row.get(2)?,
+                passed: status == "passed",
+                error_message: row.get(4)?,
+                run_id: row.get(5)?,
+                commit: row.get(6)?,
+                ended_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    // Fold example rows into one outcome per scenario and run
+    let mut observations: Vec<Observation> = Vec::new();
+    let mut folded: HashMap<(String, String, u32), usize> = HashMap::new();
+    for row in rows {
+        let row = row.map_err(|e| e.to_string())?;
+        let key = (row.run_id.clone(), row.uri.clone(), row.scenario_line);
+        match folded.get(&key) {
+            Some(&index) => {
+                let previous = &mut observations[index];
+                if !row.passed && previous.passed {
+                    previous.passed = false;
+                    previous.error_message = row.error_message;
+                }
+            }
+            None => {
+                folded.insert(key, observations.len());
+                observations.push(row);
+            }
+        }
+    }
+    Ok(observations)
+}
+
+/// First line of an error, the rest is usually a diff or stack that differs per run
+fn message_summary(message: &str) -> String {
+    message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Score every scenario that flipped at least once, flakiest first.
+pub fn score(observations: &[Observation], now: i64, half_life_days: f64) -> Vec<FlakyScenario> {
+    let mut by_scenario: BTreeMap<(&str, u32), Vec<&Observation>> = BTreeMap::new();
+    for observation in observations {
+        by_scenario
+            .entry((&observation.uri, observation.scenario_line))
+            .or_default()
+            .push(observation);
+    }
+
+    let weight = |at: i64| 0.5_f64.powf((now - at).max(0) as f64 / DAY_MS / half_life_days);
+
+    let mut flaky = Vec::new();
+    for ((uri, scenario_line), mut runs) in by_scenario {
+        runs.sort_by_key(|o| o.ended_at);
+
+        // Compare each run with the previous one on the same commit
+        let mut last_on_commit: HashMap<&str, &Observation> = HashMap::new();
+        let (mut flips, mut retry_passes, mut last_flip_at) = (0, 0, None);
+        let (mut flipped_weight, mut total_weight) = (0.0, 0.0);
+        for run in &runs {
+            let Some(commit) = run.commit.as_deref() else {
+                continue;
+            };
+            if let Some(previous) = last_on_commit.insert(commit, run) {
+                let w = weight(run.ended_at);
+                total_weight += w;
+                if previous.passed != run.passed {
+                    flips += 1;
+                    flipped_weight += w;
+                    last_flip_at = Some(run.ended_at);
+                    if run.passed {
+                        retry_passes += 1;
+                    }
+                }
+            }
+        }
+        if flips == 0 {
+            continue;
+        }
+
+        let mut messages: HashMap<String, usize> = HashMap::new();
+        for message in runs.iter().filter_map(|o| o.error_message.as_deref()) {
+            *messages.entry(message_summary(message)).or_default() += 1;
+        }
+        let mut failure_messages: Vec<FailureMessage> = messages
+            .into_iter()
+            .map(|(message, count)| FailureMessage { message, count })
+            .collect();
+        failure_messages.sort_by(|a, b| b.count.cmp(&a.count).then(a.message.cmp(&b.message)));
+        failure_messages.truncate(FAILURE_MESSAGES);
+
+        flaky.push(FlakyScenario {
+            key: format!("{}:{}", uri, scenario_line),
+            uri: uri.to_string(),
+            scenario_line,
+            name: runs.last().map(|o| o.name.clone()).unwrap_or_default(),
+            score: flipped_weight / total_weight,
+            runs: runs.len(),
+            failures: runs.iter().filter(|o| !o.passed).count(),
+            flips,
+            retry_passes,
+            last_flip_at,
+            failure_messages,
+        });
+    }
+
+    flaky.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.flips.cmp(&a.flips))
+            .then(a.key.cmp(&b.key))
+    });
+    flaky
+}
+
+/// The `limit` flakiest scenarios of a repo according to its run history.
+pub fn top_flaky(
+    connection: &Connection,
+    repo_path: &str,
+    now: i64,
+    limit: usize,
+) -> Result<Vec<FlakyScenario>, String> {
+    let observations = load_observations(connection, repo_path)?;
+    let mut flaky = score(&observations, now, DEFAULT_HALF_LIFE_DAYS);
+    flaky.truncate(limit);
+    Ok(flaky)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_report_reader::parse_results;
+    use crate::cucumber_runner::{RunExit, RunOptions, RunStart};
+    use crate::database;
+    use crate::run_history::{finish_run, insert_run, GitState};
+
+    const DAY: i64 = 86_400_000;
+
+    fn observation(line: u32, commit: &str, day: i64, passed: bool) -> Observation {
+        Observation {
+            uri: "features/a.feature".into(),
+            scenario_line: line,
+            name: format!("S{}", line),
+            run_id: format!("{}-{}", commit, day),
+            commit: Some(commit.into()),
+            ended_at: day * DAY,
+            passed,
+            error_message: (!passed).then(|| "Timeout waiting for #save\n    at x".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let observations = [
+            // Flips twice on one commit, a week ago
+            observation(2, "a", 23, false),
+            observation(2, "a", 23, true),
+            observation(2, "a", 23, false),
+            // Fails on one commit, then passes after a fix on the next
+            observation(5, "a", 29, false),
+            observation(5, "b", 30, true),
+            observation(5, "b", 30, true),
+            // Flipped once long ago, stable since
+            observation(9, "a", 0, false),
+            observation(9, "a", 0, true),
+            observation(9, "b", 30, true),
+            observation(9, "b", 30, true),
+        ];
+
+        let flaky = score(&observations, 30 * DAY, DEFAULT_HALF_LIFE_DAYS);
+
+        let keys: Vec<&str> = flaky.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, vec!["features/a.feature:2", "features/a.feature:9"]);
+        assert_eq!(flaky[0].score, 1.0);
+        assert_eq!((flaky[0].flips, flaky[0].retry_passes), (2, 1));
+        assert_eq!(flaky[0].failures, 2);
+        assert_eq!(
+            flaky[0].failure_messages,
+            vec![FailureMessage {
+                message: "Timeout waiting for #save".into(),
+                count: 2
+            }]
+        );
+        // The old flip is outweighed by recent stable reruns
+        assert!(flaky[1].score < 0.25);
+    }
+
+    #[test]
+    fn test_top_flaky_from_history() {
+        let connection = database::open_in_memory();
+        // A fail then pass on a dirty tree is an edit, not a flake
+        let runs = [
+            ("r1", "failed", "passed", false),
+            ("r2", "passed", "passed", false),
+            ("r3", "failed", "passed", true),
+            ("r4", "passed", "passed", true),
+        ];
+        for (index, (run_id, row_7, row_8, dirty)) in runs.iter().enumerate() {
+            let git = GitState {
+                commit: Some("a".into()),
+                branch: Some("main".into()),
+                dirty: *dirty,
+            };
+            let start = RunStart {
+                run_id: run_id.to_string(),
+                folder_path: "/repo".into(),
+                target: "features/a.feature".into(),
+                command_line: String::new(),
+                options: RunOptions::default(),
+                started_at: index as i64,
+            };
+            insert_run(&connection, &start, &git, None).unwrap();
+            let mut results = parse_results(&format!(
+                r#"[{{"uri": "features/a.feature", "elements": [
+                    {{"line": 7, "steps": [{{"result": {{"status": "{}", "error_message": "boom"}}}}]}},
+                    {{"line": 8, "steps": [{{"result": {{"status": "{}"}}}}]}}
+                ]}}]"#,
+                row_7, row_8
+            ))
+            .unwrap();
+            for result in &mut results {
+                result.scenario_line = Some(4);
+            }
+            let exit = RunExit {
+                run_id: run_id.to_string(),
+                success: *row_7 == "passed",
+                code: Some(0),
+                status: String::new(),
+                duration_ms: 0,
+                cancelled: false,
+                report_path: None,
+                results: None,
+                report_error: None,
+            };
+            finish_run(&connection, &exit, index as i64 + 1, &results).unwrap();
+        }
+
+        let flaky = top_flaky(&connection, "/repo", 5, 10).unwrap();
+
+        assert_eq!(flaky.len(), 1);
+        assert_eq!(flaky[0].key, "features/a.feature:4");
+        assert_eq!(
+            (flaky[0].runs, flaky[0].flips, flaky[0].retry_passes),
+            (2, 1, 1)
+        );
+        assert_eq!(flaky[0].failure_messages[0].message, "boom");
+    }
+}
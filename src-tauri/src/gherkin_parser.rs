@@ -0,0 +1,827 @@
+use serde::Serialize;
+
+/// Gherkin AST shaped after the Cucumber Messages `GherkinDocument`, so it can be
+/// serialized straight into a `gherkinDocument` envelope.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GherkinDocument {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<Feature>,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Comment {
+    pub location: Location,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Tag {
+    pub location: Location,
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Feature {
+    pub location: Location,
+    pub tags: Vec<Tag>,
+    pub language: String,
+    pub keyword: String,
+    pub name: String,
+    pub description: String,
+    pub children: Vec<FeatureChild>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FeatureChild {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Rule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Background>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<Scenario>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub location: Location,
+    pub tags: Vec<Tag>,
+    pub keyword: String,
+    pub name: String,
+    pub description: String,
+    pub children: Vec<RuleChild>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RuleChild {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Background>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<Scenario>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Background {
+    pub id: String,
+    pub location: Location,
+    pub keyword: String,
+    pub name: String,
+    pub description: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Scenario {
+    pub id: String,
+    pub location: Location,
+    pub tags: Vec<Tag>,
+    pub keyword: String,
+    pub name: String,
+    pub description: String,
+    pub steps: Vec<Step>,
+    pub examples: Vec<Examples>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    pub id: String,
+    pub location: Location,
+    pub keyword: String,
+    pub keyword_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_string: Option<DocString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_table: Option<DataTable>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DocString {
+    pub location: Location,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub content: String,
+    pub delimiter: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DataTable {
+    pub location: Location,
+    pub rows: Vec<TableRow>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TableRow {
+    pub id: String,
+    pub location: Location,
+    pub cells: Vec<TableCell>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TableCell {
+    pub location: Location,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Examples {
+    pub id: String,
+    pub location: Location,
+    pub tags: Vec<Tag>,
+    pub keyword: String,
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_header: Option<TableRow>,
+    pub table_body: Vec<TableRow>,
+}
+
+impl Feature {
+    /// All scenarios in document order, including the ones nested in rules.
+    pub fn scenarios(&self) -> Vec<&Scenario> {
+        let mut scenarios = Vec::new();
+        for child in &self.children {
+            if let Some(scenario) = &child.scenario {
+                scenarios.push(scenario);
+            }
+            if let Some(rule) = &child.rule {
+                scenarios.extend(rule.children.iter().filter_map(|c| c.scenario.as_ref()));
+            }
+        }
+        scenarios
+    }
+}
+
+/// Hands out predictable, incrementing IDs ("0", "1", ...) so that parsing the same
+/// sources in the same order always yields the same IDs.
+#[derive(Default)]
+pub struct IdGenerator {
+    next: u64,
+}
+
+impl IdGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next_id(&mut self) -> String {
+        let id = self.next.to_string();
+        self.next += 1;
+        id
+    }
+}
+
+const STEP_KEYWORDS: [(&str, &str); 6] = [
+    ("Given ", "Context"),
+    ("When ", "Action"),
+    ("Then ", "Outcome"),
+    ("And ", "Conjunction"),
+    ("But ", "Conjunction"),
+    ("* ", "Unknown"),
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Block {
+    None,
+    Feature,
+    Rule,
+    Background,
+    Scenario,
+    Examples,
+}
+
+struct DocStringState {
+    delimiter: String,
+    indent: usize,
+    location: Location,
+    media_type: Option<String>,
+    lines: Vec<String>,
+}
+
+struct Parser<'a> {
+    ids: &'a mut IdGenerator,
+    feature: Option<Feature>,
+    rule: Option<Rule>,
+    background: Option<Background>,
+    scenario: Option<Scenario>,
+    examples: Option<Examples>,
+    comments: Vec<Comment>,
+    language: String,
+    pending_tags: Vec<Tag>,
+    block: Block,
+    description: Vec<String>,
+    in_description: bool,
+    doc_string: Option<DocStringState>,
+}
+
+/// Parse the contents of a `.feature` file into a Gherkin AST with source locations.
+pub fn parse(uri: &str, source: &str, ids: &mut IdGenerator) -> Result<GherkinDocument, String> {
+    let mut parser = Parser {
+        ids,
+        feature: None,
+        rule: None,
+        background: None,
+        scenario: None,
+        examples: None,
+        comments: Vec::new(),
+        language: "en".to_string(),
+        pending_tags: Vec::new(),
+        block: Block::None,
+        description: Vec::new(),
+        in_description: false,
+        doc_string: None,
+    };
+
+    for (index, line) in source.lines().enumerate() {
+        parser.process_line(index as u32 + 1, line)?;
+    }
+
+    if parser.doc_string.is_some() {
+        return Err(format!("{}: unterminated doc string", uri));
+    }
+
+    parser.close_rule();
+    parser.close_description();
+
+    Ok(GherkinDocument {
+        uri: uri.to_string(),
+        feature: parser.feature,
+        comments: parser.comments,
+    })
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+/// Dialect of a `# language: xx` header line
+fn language_header(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('#')?.trim_start();
+    let language = rest
+        .strip_prefix("language")?
+        .trim_start()
+        .strip_prefix(':')?;
+    Some(language.trim()).filter(|language| !language.is_empty())
+}
+
+fn strip_keyword<'a>(line: &'a str, keywords: &[&str]) -> Option<(String, &'a str)> {
+    keywords.iter().find_map(|keyword| {
+        line.strip_prefix(keyword)
+            .map(|rest| (keyword.trim_end_matches(':').to_string(), rest.trim()))
+    })
+}
+
+fn parse_cells(line: &str, line_number: u32) -> Vec<TableCell> {
+    let mut cells = Vec::new();
+    let chars: Vec<char> = line.chars().collect();
+    let mut position = match chars.iter().position(|&c| c == '|') {
+        Some(position) => position + 1,
+        None => return cells,
+    };
+
+    let mut value = String::new();
+    let mut start: Option<usize> = None;
+    while position < chars.len() {
+        let c = chars[position];
+        if c == '\\' && position + 1 < chars.len() {
+            let next = chars[position + 1];
+            start.get_or_insert(position);
+            match next {
+                'n' => value.push('\n'),
+                '|' | '\\' => value.push(next),
+                _ => {
+                    value.push(c);
+                    value.push(next);
+                }
+            }
+            position += 2;
+            continue;
+        }
+        if c == '|' {
+            cells.push(TableCell {
+                location: Location {
+                    line: line_number,
+                    column: start.unwrap_or(position) as u32 + 1,
+                },
+                value: value.trim().to_string(),
+            });
+            value.clear();
+            start = None;
+        } else {
+            if !c.is_whitespace() {
+                start.get_or_insert(position);
+            }
+            value.push(c);
+        }
+        position += 1;
+    }
+
+    cells
+}
+
+impl Parser<'_> {
+    fn process_line(&mut self, line_number: u32, raw: &str) -> Result<(), String> {
+        let line = raw.trim();
+        let indent = indentation(raw);
+        let location = Location {
+            line: line_number,
+            column: indent as u32 + 1,
+        };
+
+        if let Some(state) = self.doc_string.as_mut() {
+            if line == state.delimiter {
+                let state = self.doc_string.take().unwrap();
+                self.attach_doc_string(state);
+            } else {
+                let content = if indentation(raw) >= state.indent {
+                    raw.chars().skip(state.indent).collect::<String>()
+                } else {
+                    raw.trim_start().to_string()
+                };
+                state.lines.push(content.trim_end().to_string());
+            }
+            return Ok(());
+        }
+
+        if let Some(language) = language_header(line).filter(|_| self.feature.is_none()) {
+            // Only English keywords are known, other dialects would parse as free text
+            if language != "en" {
+                return Err(format!(
+                    "line {}: language {} is not supported",
+                    line_number, language
+                ));
+            }
+            self.language = language.to_string();
+            return Ok(());
+        }
+
+        if line.starts_with('#') {
+            self.comments.push(Comment {
+                location: Location {
+                    line: line_number,
+                    column: 1,
+                },
+                text: raw.trim_end().to_string(),
+            });
+            return Ok(());
+        }
+
+        if line.starts_with("\"\"\"") || line.starts_with("```") {
+            self.close_description();
+            let delimiter = line[..3].to_string();
+            let media_type = line[3..].trim();
+            self.doc_string = Some(DocStringState {
+                delimiter,
+                indent,
+                location,
+                media_type: (!media_type.is_empty()).then(|| media_type.to_string()),
+                lines: Vec::new(),
+            });
+            return Ok(());
+        }
+
+        if line.starts_with('@') {
+            self.close_description();
+            self.parse_tags(raw, line_number);
+            return Ok(());
+        }
+
+        if line.starts_with('|') {
+            self.close_description();
+            self.attach_row(line_number, raw);
+            return Ok(());
+        }
+
+        if let Some((keyword, name)) = strip_keyword(line, &["Feature:"]) {
+            self.close_description();
+            if self.feature.is_some() {
+                return Err(format!(
+                    "line {}: multiple features in one file",
+                    line_number
+                ));
+            }
+            let tags = std::mem::take(&mut self.pending_tags);
+            self.feature = Some(Feature {
+                location,
+                tags,
+                language: self.language.clone(),
+                keyword,
+                name: name.to_string(),
+                description: String::new(),
+                children: Vec::new(),
+            });
+            self.open_description(Block::Feature);
+            return Ok(());
+        }
+
+        if self.feature.is_none() {
+            // Free text before the Feature keyword is not part of any node
+            return Ok(());
+        }
+
+        if let Some((keyword, name)) = strip_keyword(line, &["Rule:"]) {
+            self.close_rule();
+            let tags = std::mem::take(&mut self.pending_tags);
+            self.rule = Some(Rule {
+                id: self.ids.next_id(),
+                location,
+                tags,
+                keyword,
+                name: name.to_string(),
+                description: String::new(),
+                children: Vec::new(),
+            });
+            self.open_description(Block::Rule);
+        } else if let Some((keyword, name)) = strip_keyword(line, &["Background:"]) {
+            self.close_scenario();
+            self.background = Some(Background {
+                id: self.ids.next_id(),
+                location,
+                keyword,
+                name: name.to_string(),
+                description: String::new(),
+                steps: Vec::new(),
+            });
+            self.open_description(Block::Background);
+        } else if let Some((keyword, name)) = strip_keyword(
+            line,
+            &[
+                "Scenario Outline:",
+                "Scenario Template:",
+                "Scenario:",
+                "Example:",
+            ],
+        ) {
+            self.close_scenario();
+            let tags = std::mem::take(&mut self.pending_tags);
+            self.scenario = Some(Scenario {
+                id: self.ids.next_id(),
+                location,
+                tags,
+                keyword,
+                name: name.to_string(),
+                description: String::new(),
+                steps: Vec::new(),
+                examples: Vec::new(),
+            });
+            self.open_description(Block::Scenario);
+        } else if let Some((keyword, name)) = strip_keyword(line, &["Examples:", "Scenarios:"]) {
+            self.close_examples();
+            let tags = std::mem::take(&mut self.pending_tags);
+            self.examples = Some(Examples {
+                id: self.ids.next_id(),
+                location,
+                tags,
+                keyword,
+                name: name.to_string(),
+                description: String::new(),
+                table_header: None,
+                table_body: Vec::new(),
+            });
+            self.open_description(Block::Examples);
+        } else if let Some((keyword, keyword_type)) = STEP_KEYWORDS
+            .iter()
+            .find(|(keyword, _)| line.starts_with(keyword))
+        {
+            self.close_description();
+            let step = Step {
+                id: self.ids.next_id(),
+                location,
+                keyword: keyword.to_string(),
+                keyword_type: keyword_type.to_string(),
+                text: line[keyword.len()..].trim().to_string(),
+                doc_string: None,
+                data_table: None,
+            };
+            if let Some(scenario) = self.scenario.as_mut() {
+                scenario.steps.push(step);
+            } else if let Some(background) = self.background.as_mut() {
+                background.steps.push(step);
+            }
+        } else if self.in_description && !(self.description.is_empty() && line.is_empty()) {
+            self.description.push(raw.trim_end().to_string());
+        }
+
+        Ok(())
+    }
+
+    fn parse_tags(&mut self, raw: &str, line_number: u32) {
+        let mut offset = 0;
+        for token in raw.split_whitespace() {
+            let column = raw[offset..].find(token).unwrap() + offset;
+            offset = column + token.len();
+            if token.starts_with('#') {
+                break;
+            }
+            if token.starts_with('@') {
+                self.pending_tags.push(Tag {
+                    location: Location {
+                        line: line_number,
+                        column: raw[..column].chars().count() as u32 + 1,
+                    },
+                    name: token.to_string(),
+                    id: self.ids.next_id(),
+                });
+            }
+        }
+    }
+
+    fn attach_row(&mut self, line_number: u32, raw: &str) {
+        let row = TableRow {
+            id: self.ids.next_id(),
+            location: Location {
+                line: line_number,
+                column: indentation(raw) as u32 + 1,
+            },
+            cells: parse_cells(raw, line_number),
+        };
+
+        if let Some(examples) = self.examples.as_mut() {
+            if examples.table_header.is_none() {
+                examples.table_header = Some(row);
+            } else {
+                examples.table_body.push(row);
+            }
+            return;
+        }
+
+        if let Some(step) = self.last_step_mut() {
+            step.data_table
+                .get_or_insert_with(|| DataTable {
+                    location: row.location,
+                    rows: Vec::new(),
+                })
+                .rows
+                .push(row);
+        }
+    }
+
+    fn attach_doc_string(&mut self, state: DocStringState) {
+        let doc_string = DocString {
+            location: state.location,
+            media_type: state.media_type,
+            content: state.lines.join("\n"),
+            delimiter: state.delimiter,
+        };
+        if let Some(step) = self.last_step_mut() {
+            step.doc_string = Some(doc_string);
+        }
+    }
+
+    fn last_step_mut(&mut self) -> Option<&mut Step> {
+        if self.examples.is_some() {
+            return None;
+        }
+        if let Some(scenario) = self.scenario.as_mut() {
+            return scenario.steps.last_mut();
+        }
+        self.background.as_mut().and_then(|b| b.steps.last_mut())
+    }
+
+    fn open_description(&mut self, block: Block) {
+        self.block = block;
+        self.in_description = true;
+        self.description.clear();
+    }
+
+    fn close_description(&mut self) {
+        if !self.in_description {
+            return;
+        }
+        self.in_description = false;
+
+        while self.description.last().is_some_and(|l| l.trim().is_empty()) {
+            self.description.pop();
+        }
+        let description = self.description.join("\n");
+        self.description.clear();
+
+        match self.block {
+            Block::Feature => {
+                if let Some(feature) = self.feature.as_mut() {
+                    feature.description = description;
+                }
+            }
+            Block::Rule => {
+                if let Some(rule) = self.rule.as_mut() {
+                    rule.description = description;
+                }
+            }
+            Block::Background => {
+                if let Some(background) = self.background.as_mut() {
+                    background.description = description;
+                }
+            }
+            Block::Scenario => {
+                if let Some(scenario) = self.scenario.as_mut() {
+                    scenario.description = description;
+                }
+            }
+            Block::Examples => {
+                if let Some(examples) = self.examples.as_mut() {
+                    examples.description = description;
+                }
+            }
+            Block::None => {}
+        }
+    }
+
+    fn close_examples(&mut self) {
+        self.close_description();
+        if let Some(examples) = self.examples.take() {
+            if let Some(scenario) = self.scenario.as_mut() {
+                scenario.examples.push(examples);
+            }
+        }
+    }
+
+    fn close_scenario(&mut self) {
+        self.close_examples();
+
+        let background = self.background.take();
+        let scenario = self.scenario.take();
+        if background.is_none() && scenario.is_none() {
+            return;
+        }
+
+        if let Some(rule) = self.rule.as_mut() {
+            rule.children.push(RuleChild {
+                background,
+                scenario,
+            });
+        } else if let Some(feature) = self.feature.as_mut() {
+            feature.children.push(FeatureChild {
+                rule: None,
+                background,
+                scenario,
+            });
+        }
+    }
+
+    fn close_rule(&mut self) {
+        self.close_scenario();
+        if let Some(rule) = self.rule.take() {
+            if let Some(feature) = self.feature.as_mut() {
+                feature.children.push(FeatureChild {
+                    rule: Some(rule),
+                    background: None,
+                    scenario: None,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"# a leading comment
+@smoke
+Feature: Invoices
+  Creating and sending invoices
+
+  Background:
+    Given current organization is "Billing LLC"
+
+  @billing @wip
+  Scenario Outline: Create <kind> invoice
+    Given user is on a "invoices" screen
+    When I create an invoice with:
+      | field  | value  |
+      | kind   | <kind> |
+    Then I should see
+      """markdown
+      Invoice <kind> created
+      """
+
+    @mobile
+    Examples: Kinds
+      | kind  |
+      | draft |
+      | final |
+
+  Rule: Payments
+    Scenario: Pay invoice
+      * I pay it
+"#;
+
+    #[test]
+    fn test_parse_structure_and_locations() {
+        let mut ids = IdGenerator::new();
+        let document = parse("features/invoices.feature", SOURCE, &mut ids).unwrap();
+        let feature = document.feature.unwrap();
+
+        assert_eq!(document.comments.len(), 1);
+        assert_eq!(document.comments[0].location.line, 1);
+
+        assert_eq!(feature.name, "Invoices");
+        assert_eq!(feature.keyword, "Feature");
+        assert_eq!(feature.description, "  Creating and sending invoices");
+        assert_eq!(feature.location, Location { line: 3, column: 1 });
+        assert_eq!(feature.tags[0].name, "@smoke");
+        assert_eq!(feature.children.len(), 3);
+
+        let background = feature.children[0].background.as_ref().unwrap();
+        assert_eq!(background.steps.len(), 1);
+        assert_eq!(background.steps[0].keyword_type, "Context");
+
+        let scenario = feature.children[1].scenario.as_ref().unwrap();
+        assert_eq!(scenario.keyword, "Scenario Outline");
+        assert_eq!(
+            scenario.location,
+            Location {
+                line: 10,
+                column: 3
+            }
+        );
+        assert_eq!(scenario.tags.len(), 2);
+        assert_eq!(
+            scenario.tags[1].location,
+            Location {
+                line: 9,
+                column: 12
+            }
+        );
+        assert_eq!(scenario.steps.len(), 3);
+
+        let table = scenario.steps[1].data_table.as_ref().unwrap();
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[1].cells[1].value, "<kind>");
+
+        let doc_string = scenario.steps[2].doc_string.as_ref().unwrap();
+        assert_eq!(doc_string.media_type.as_deref(), Some("markdown"));
+        assert_eq!(doc_string.content, "Invoice <kind> created");
+
+        let examples = &scenario.examples[0];
+        assert_eq!(examples.name, "Kinds");
+        assert_eq!(examples.tags[0].name, "@mobile");
+        assert_eq!(
+            examples.table_header.as_ref().unwrap().cells[0].value,
+            "kind"
+        );
+        assert_eq!(examples.table_body.len(), 2);
+        assert_eq!(examples.table_body[1].location.line, 24);
+
+        let rule = feature.children[2].rule.as_ref().unwrap();
+        assert_eq!(rule.name, "Payments");
+        assert_eq!(
+            rule.children[0].scenario.as_ref().unwrap().steps[0].keyword,
+            "* "
+        );
+        assert_eq!(feature.scenarios().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_ids_are_predictable() {
+        let first = parse("a.feature", SOURCE, &mut IdGenerator::new()).unwrap();
+        let second = parse("a.feature", SOURCE, &mut IdGenerator::new()).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_escaped_cells() {
+        let cells = parse_cells(r"  | a \| b | c\\d |  |", 4);
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells[0].value, "a | b");
+        assert_eq!(cells[0].location, Location { line: 4, column: 5 });
+        assert_eq!(cells[1].value, r"c\d");
+        assert_eq!(cells[2].value, "");
+    }
+
+    #[test]
+    fn test_parse_unterminated_doc_string() {
+        let source = "Feature: F\n  Scenario: S\n    Given x\n      \"\"\"\n      text\n";
+        let result = parse("f.feature", source, &mut IdGenerator::new());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_language_header() {
+        let source = "#language:en\nFeature: F\n";
+        let document = parse("f.feature", source, &mut IdGenerator::new()).unwrap();
+        assert_eq!(document.feature.unwrap().language, "en");
+        assert!(document.comments.is_empty());
+
+        let source = "# language: de\nFunktionalität: F\n";
+        let error = parse("f.feature", source, &mut IdGenerator::new()).unwrap_err();
+        assert_eq!(error, "line 1: language de is not supported");
+    }
+}
@@ -0,0 +1,388 @@
+use crate::features_reader::{Feature, Scenario};
+use crate::toolchain;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Marks the commit lines in `git log` output, `-p` adds a patch after each of them
+const LOG_FORMAT: &str = "--format=%x00%H%x09%an%x09%at";
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Authorship {
+    pub created_by: String,
+    pub created_at: i64,
+    pub last_modified_by: String,
+    pub last_modified_at: i64,
+    pub commit_count: usize,
+    /// The file has uncommitted edits, so its scenarios are left without authorship
+    pub uncommitted: bool,
+}
+
+/// Changed lines of one `-U0` diff hunk, a length of 0 means the start is the line
+/// after which lines were added or removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hunk {
+    old_start: u32,
+    old_len: u32,
+    new_start: u32,
+    new_len: u32,
+}
+
+impl Hunk {
+    fn parse(header: &str) -> Option<Self> {
+        let mut ranges = header.strip_prefix("@@ -")?.split(' ');
+        let old = ranges.next()?;
+        let new = ranges.next()?.strip_prefix('+')?;
+        let range = |range: &str| -> Option<(u32, u32)> {
+            match range.split_once(',') {
+                Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+        let (old_start, old_len) = range(old)?;
+        let (new_start, new_len) = range(new)?;
+        Some(Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        })
+    }
+
+    /// Whether the hunk changes lines of `start..=end` or removes lines inside it
+    fn touches(&self, start: u32, end: u32) -> bool {
+        if self.new_len == 0 {
+            start <= self.new_start && self.new_start < end
+        } else {
+            self.new_start <= end && start < self.new_start + self.new_len
+        }
+    }
+}
+
+/// Line `line` of the newer revision in the older one. A changed line maps to the first
+/// (`first`) or last line the hunk replaced, which is outside the range when it added them.
+fn map_line(hunks: &[Hunk], line: u32, first: bool) -> i64 {
+    let mut shift = 0;
+    for hunk in hunks {
+        let new_end = hunk.new_start + hunk.new_len;
+        if hunk.new_len > 0 && (hunk.new_start..new_end).contains(&line) {
+            let old_start = hunk.old_start as i64;
+            return match (first, hunk.old_len) {
+                (true, 0) => old_start + 1,
+                (true, _) | (false, 0) => old_start,
+                (false, len) => old_start + len as i64 - 1,
+            };
+        }
+        let before = if hunk.new_len == 0 {
+            hunk.new_start < line
+        } else {
+            new_end <= line
+        };
+        if before {
+            shift += hunk.old_len as i64 - hunk.new_len as i64;
+        }
+    }
+    line as i64 + shift
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LogEntry {
+    commit: String,
+    author: String,
+    author_time: i64,
+    hunks: Vec<Hunk>,
+}
+
+lazy_static! {
+    // History per file for the HEAD it was read at, dropped as a whole once HEAD moves
+    static ref HISTORY_CACHE: Mutex<(String, HashMap<String, Vec<LogEntry>>)> =
+        Mutex::new((String::new(), HashMap::new()));
+}
+
+/// Run a git command in `repo_path` and return its stdout.
+pub fn run_git(repo_path: &str, args: &[&str]) -> Result<String, String> {
+    let output = toolchain::std_command("git")
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn head_commit(repo_path: &str) -> Result<String, String> {
+    run_git(repo_path, &["rev-parse", "HEAD"]).map(|sha| sha.trim().to_string())
+}
+
+/// Commits in `git log -p -U0` output written with `LOG_FORMAT`, newest first.
+fn parse_log(output: &str) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = Vec::new();
+    for line in output.lines() {
+        if let Some(line) = line.strip_prefix('\0') {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(commit), Some(author), Some(author_time)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            entries.push(LogEntry {
+                commit: commit.to_string(),
+                author: author.to_string(),
+                author_time: author_time.trim().parse().unwrap_or_default(),
+                hunks: Vec::new(),
+            });
+        } else if let (Some(hunk), Some(entry)) = (Hunk::parse(line), entries.last_mut()) {
+            entry.hunks.push(hunk);
+        }
+    }
+    entries
+}
+
+/// Commits that changed lines `start..=end` (1-based) of the newest revision, newest
+/// first. The range is followed back through each commit's hunks like `git log -L`
+/// does, until the commit that added it.
+fn range_entries(entries: &[LogEntry], (start, end): (u32, u32)) -> Vec<&LogEntry> {
+    let (mut start, mut end) = (start as i64, end as i64);
+    let mut touched = Vec::new();
+    for entry in entries {
+        if start > end || start < 1 {
+            break;
+        }
+        if entry
+            .hunks
+            .iter()
+            .any(|h| h.touches(start as u32, end as u32))
+        {
+            touched.push(entry);
+        }
+        start = map_line(&entry.hunks, start as u32, true);
+        end = map_line(&entry.hunks, end as u32, false);
+    }
+    touched
+}
+
+/// Authorship from the commits that touched a file or range, newest first. The oldest
+/// one created it, even if none of its lines survive.
+fn summarize(entries: &[&LogEntry]) -> Option<Authorship> {
+    let modified = entries.first()?;
+    let created = entries.last()?;
+    let commits: HashSet<&str> = entries.iter().map(|e| e.commit.as_str()).collect();
+    Some(Authorship {
+        created_by: created.author.clone(),
+        created_at: created.author_time,
+        last_modified_by: modified.author.clone(),
+        last_modified_at: modified.author_time,
+        commit_count: commits.len(),
+        uncommitted: false,
+    })
+}
+
+/// History of `file_path` up to HEAD, read with one `git log` and kept until HEAD moves.
+fn history(repo_path: &str, head: &str, file_path: &str) -> Result<Vec<LogEntry>, String> {
+    {
+        let mut cache = HISTORY_CACHE.lock().unwrap();
+        if cache.0 != head {
+            *cache = (head.to_string(), HashMap::new());
+        }
+        if let Some(entries) = cache.1.get(file_path) {
+            return Ok(entries.clone());
+        }
+    }
+
+    let output = run_git(
+        repo_path,
+        &["log", "--follow", "-p", "-U0", LOG_FORMAT, "--", file_path],
+    )?;
+    let entries = parse_log(&output);
+
+    let mut cache = HISTORY_CACHE.lock().unwrap();
+    if cache.0 == head {
+        cache.1.insert(file_path.to_string(), entries.clone());
+    }
+    Ok(entries)
+}
+
+/// Files with uncommitted edits, relative to `repo_path`
+fn uncommitted_files(repo_path: &str) -> Result<HashSet<PathBuf>, String> {
+    let output = run_git(
+        repo_path,
+        &["diff", "HEAD", "--name-only", "--relative", "-z"],
+    )?;
+    Ok(output
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Attach git authorship to every feature (whole file) and scenario (its line range),
+/// reading each file's history once. Line numbers of a file with uncommitted edits don't
+/// match its history, so its scenarios get none and the feature is marked `uncommitted`.
+/// Uses the local repository only; if `repo_path` is not a git checkout this is an error
+/// and the catalog is left untouched.
+pub fn attach_authorship(
+    repo_path: &str,
+    features: &mut [Feature],
+    scenarios: &mut [Scenario],
+) -> Result<(), String> {
+    let head = head_commit(repo_path)?;
+    let uncommitted = uncommitted_files(repo_path)?;
+
+    for feature in features.iter_mut() {
+        // Untracked files have no history yet
+        let Ok(entries) = history(repo_path, &head, &feature.file_path) else {
+            continue;
+        };
+        let Some(mut authorship) = summarize(&entries.iter().collect::<Vec<_>>()) else {
+            continue;
+        };
+        let relative = Path::new(&feature.file_path)
+            .strip_prefix(repo_path)
+            .unwrap_or(Path::new(&feature.file_path));
+        authorship.uncommitted = uncommitted.contains(relative);
+        let skip_scenarios = authorship.uncommitted;
+        feature.authorship = Some(authorship);
+        if skip_scenarios {
+            continue;
+        }
+
+        for scenario in scenarios.iter_mut().filter(|s| s.feature_id == feature.id) {
+            let range = (scenario.line, scenario.end_line.max(scenario.line));
+            scenario.authorship = summarize(&range_entries(&entries, range));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features_reader::get_all_features;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_log() {
+        let output = "\
+\0bbbb\tBo\t300
+
+diff --git a/a.feature b/a.feature
+@@ -3,2 +3,2 @@
+-  Scenario: S
++  Scenario: T
+\0aaaa\tAna\t100
+
+diff --git a/a.feature b/a.feature
+";
+        let entries = parse_log(output);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].hunks,
+            vec![Hunk {
+                old_start: 3,
+                old_len: 2,
+                new_start: 3,
+                new_len: 2
+            }]
+        );
+
+        let authorship = summarize(&entries.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(authorship.created_by, "Ana");
+        assert_eq!(authorship.created_at, 100);
+        assert_eq!(authorship.last_modified_by, "Bo");
+        assert_eq!(authorship.last_modified_at, 300);
+        assert_eq!(authorship.commit_count, 2);
+
+        assert!(summarize(&[]).is_none());
+    }
+
+    #[test]
+    fn test_range_entries() {
+        let entry = |commit: &str, hunks: &[&str]| LogEntry {
+            commit: commit.to_string(),
+            author: commit.to_string(),
+            author_time: 0,
+            hunks: hunks.iter().map(|h| Hunk::parse(h).unwrap()).collect(),
+        };
+        let entries = [
+            // Two lines added above, then line 8 rewritten
+            entry("c", &["@@ -6 +8 @@"]),
+            entry("b", &["@@ -1,0 +2,2 @@"]),
+            entry("a", &["@@ -0,0 +1,10 @@"]),
+        ];
+        let commits = |range| -> Vec<&str> {
+            range_entries(&entries, range)
+                .iter()
+                .map(|e| e.commit.as_str())
+                .collect()
+        };
+
+        assert_eq!(commits((7, 9)), vec!["c", "a"]);
+        assert_eq!(commits((2, 3)), vec!["b"]);
+        assert_eq!(commits((10, 12)), vec!["a"]);
+        assert_eq!(commits((1, 2)), vec!["b", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_attach_authorship() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let git = |name: &str, args: &[&str]| {
+            let user = format!("user.name={}", name);
+            let mut full = vec!["-c", &user, "-c", "user.email=dev@example.com"];
+            full.extend_from_slice(args);
+            run_git(repo, &full).unwrap();
+        };
+
+        git("Ana", &["init", "-q"]);
+        fs::write(
+            dir.path().join("a.feature"),
+            "Feature: A\n\n  Scenario: S\n    Given x\n",
+        )
+        .unwrap();
+        git("Ana", &["add", "."]);
+        git("Ana", &["commit", "-q", "-m", "first"]);
+        // Every line of the scenario is rewritten, it was still created by Ana
+        fs::write(
+            dir.path().join("a.feature"),
+            "Feature: A\n\n  Scenario: T\n    Given y\n",
+        )
+        .unwrap();
+        git("Bo", &["commit", "-q", "-am", "second"]);
+
+        let (mut features, mut scenarios) = get_all_features(repo.to_string()).await.unwrap();
+        attach_authorship(repo, &mut features, &mut scenarios).unwrap();
+
+        let authorship = scenarios[0].authorship.as_ref().unwrap();
+        assert_eq!(authorship.created_by, "Ana");
+        assert_eq!(authorship.last_modified_by, "Bo");
+        assert_eq!(authorship.commit_count, 2);
+        assert_eq!(
+            features[0].authorship.as_ref().unwrap().last_modified_by,
+            "Bo"
+        );
+
+        // Line numbers of an edited file don't match its history
+        fs::write(
+            dir.path().join("a.feature"),
+            "Feature: A\n\n\n  Scenario: T\n    Given y\n",
+        )
+        .unwrap();
+        let (mut features, mut scenarios) = get_all_features(repo.to_string()).await.unwrap();
+        attach_authorship(repo, &mut features, &mut scenarios).unwrap();
+
+        assert!(features[0].authorship.as_ref().unwrap().uncommitted);
+        assert!(scenarios[0].authorship.is_none());
+    }
+}
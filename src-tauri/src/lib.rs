@@ -1,25 +1,111 @@
+mod browser_env;
+mod catalog_diff;
+mod change_detector;
 mod clone_detector;
+mod conflict_detector;
+mod cucumber_config;
+mod cucumber_messages;
+mod cucumber_report_reader;
 mod cucumber_runner;
+mod database;
 mod e2e_locator;
 mod features_reader;
 mod file_reader;
+mod flakiness;
+mod gherkin_parser;
+mod git_reader;
+mod metrics_store;
 mod organizations_reader;
+mod preflight;
+mod quarantine;
+mod reference_extractor;
+mod repo_source;
+mod report_export;
+mod run_artifacts;
+mod run_history;
+mod run_presets;
+mod run_queue;
+mod run_selection;
+mod shard_planner;
 mod steps_reader;
+mod suite_store;
+mod tag_expression;
+mod toolchain;
 
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
 
+fn database_path(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(data_dir.join("protestify.db"))
+}
+
+fn open_database(app: &tauri::AppHandle) -> Result<rusqlite::Connection, String> {
+    database::open(&database_path(app)?)
+}
+
+async fn load_features(
+    base_path: &str,
+    git_ref: Option<&str>,
+    ticket_patterns: &[String],
+) -> Result<
+    (
+        Vec<features_reader::Feature>,
+        Vec<features_reader::Scenario>,
+    ),
+    String,
+> {
+    let extractor = reference_extractor::ReferenceExtractor::new(ticket_patterns)?;
+    let source = repo_source::RepoSource::new(base_path, git_ref)?;
+    let (mut features, mut scenarios) = features_reader::get_features_from(&source)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Authorship is best effort, the catalog is still useful outside of a git checkout
+    if git_ref.is_none() {
+        let _ = git_reader::attach_authorship(base_path, &mut features, &mut scenarios);
+    }
+    reference_extractor::attach_references(&extractor, &mut features, &mut scenarios);
+
+    Ok((features, scenarios))
+}
+
+/// `ticket_patterns` are regexes for issue keys, Jira style keys are matched when empty
 #[tauri::command(async)]
-async fn get_features(base_path: String) -> serde_json::Value {
-    let result = features_reader::get_all_features(base_path).await;
-    match result {
+async fn get_features(
+    base_path: String,
+    git_ref: Option<String>,
+    ticket_patterns: Option<Vec<String>>,
+) -> serde_json::Value {
+    let ticket_patterns = ticket_patterns.unwrap_or_default();
+    match load_features(&base_path, git_ref.as_deref(), &ticket_patterns).await {
         Ok((features, scenarios)) => json!({ "features": features, "scenarios": scenarios }),
-        Err(e) => json!({ "err": e.to_string() }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Which scenarios cover each ticket referenced in tags, descriptions and comments
+#[tauri::command(async)]
+async fn get_ticket_index(
+    base_path: String,
+    git_ref: Option<String>,
+    ticket_patterns: Option<Vec<String>>,
+) -> serde_json::Value {
+    let ticket_patterns = ticket_patterns.unwrap_or_default();
+    match load_features(&base_path, git_ref.as_deref(), &ticket_patterns).await {
+        Ok((features, scenarios)) => {
+            json!({ "ok": reference_extractor::ticket_index(&features, &scenarios) })
+        }
+        Err(e) => json!({ "err": e }),
     }
 }
 
 #[tauri::command]
-fn get_organizations(file_path: &str) -> serde_json::Value {
-    let orgs = organizations_reader::parse(file_path);
+fn get_organizations(file_path: &str, git_ref: Option<&str>) -> serde_json::Value {
+    let orgs = repo_source::RepoSource::new(file_path, git_ref)
+        .and_then(|source| organizations_reader::parse_from(&source));
     match orgs {
         Ok((people, teams, organizations)) => {
             json!({ "people": people, "teams": teams, "organizations": organizations })
@@ -51,14 +137,464 @@ fn get_file_contents(file_path: &str, scenario_name: Option<&str>) -> serde_json
     }
 }
 
-#[tauri::command]
-fn run_e2e(
+/// Record a run in the history and forward its events to the frontend, tagged with
+/// the run ID
+fn emit_run_events(
+    app: &tauri::AppHandle,
+) -> impl Fn(cucumber_runner::RunEvent) + Send + Sync + 'static {
+    let app = app.clone();
+    let history = app.state::<Arc<run_history::RunHistory>>().inner().clone();
+    move |event| {
+        let app = app.clone();
+        history.record(event, move |event, errors| {
+            for error in errors {
+                let _ = app.emit(run_history::ERROR_EVENT, error);
+            }
+            let _ = match event {
+                cucumber_runner::RunEvent::Start(start) => {
+                    app.emit(cucumber_runner::START_EVENT, start)
+                }
+                cucumber_runner::RunEvent::Output(output) => {
+                    app.emit(cucumber_runner::OUTPUT_EVENT, output)
+                }
+                cucumber_runner::RunEvent::Exit(exit) => {
+                    app.emit(cucumber_runner::EXIT_EVENT, exit)
+                }
+            };
+        });
+    }
+}
+
+/// Environment checklist for the e2e repo, with fix hints for failed checks
+#[tauri::command(async)]
+async fn get_preflight(
+    base_path: String,
+    options: Option<cucumber_runner::RunOptions>,
+) -> serde_json::Value {
+    let options = options.unwrap_or_default();
+    match tauri::async_runtime::spawn_blocking(move || {
+        preflight::run_preflight(&base_path, &options)
+    })
+    .await
+    {
+        Ok(preflight) => json!({ "ok": preflight }),
+        Err(e) => json!({ "err": e.to_string() }),
+    }
+}
+
+/// Refuse to start a run while a preflight check fails
+fn check_preflight(folder_path: &str, options: &cucumber_runner::RunOptions) -> Result<(), String> {
+    let result = preflight::run_preflight(folder_path, options);
+    if result.ok {
+        Ok(())
+    } else {
+        Err(format!("Preflight failed:\n{}", result.errors()))
+    }
+}
+
+/// `check_preflight` off the async workers
+async fn require_preflight(
     folder_path: &str,
-    feature_file: &str,
-    scenario_name: Option<&str>,
+    options: &cucumber_runner::RunOptions,
+) -> Result<(), String> {
+    let (folder_path, options) = (folder_path.to_string(), options.clone());
+    tauri::async_runtime::spawn_blocking(move || check_preflight(&folder_path, &options))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Start a run and return its ID, output streams in through `e2e-run-output` events
+#[tauri::command(async)]
+async fn run_e2e(
+    app: tauri::AppHandle,
+    folder_path: String,
+    feature_file: String,
+    scenario_name: Option<String>,
+    options: Option<cucumber_runner::RunOptions>,
+) -> serde_json::Value {
+    let options = options.unwrap_or_default();
+    if let Err(e) = require_preflight(&folder_path, &options).await {
+        return json!({ "err": e });
+    }
+    let on_event = emit_run_events(&app);
+    match cucumber_runner::run_cucumber(
+        &folder_path,
+        &feature_file,
+        scenario_name.as_deref(),
+        &options,
+        on_event,
+    ) {
+        Ok(run_id) => json!({ "ok": run_id }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Stop a run, its whole process group is terminated and killed after a grace period
+#[tauri::command]
+fn cancel_run(run_id: &str) -> serde_json::Value {
+    match cucumber_runner::cancel_run(run_id) {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn list_runs() -> serde_json::Value {
+    json!({ "ok": cucumber_runner::active_runs() })
+}
+
+fn create_run_queue(app: &tauri::AppHandle) -> Result<Arc<run_queue::RunQueue>, String> {
+    let check: run_queue::Check =
+        Arc::new(|item: &run_queue::QueueItem| check_preflight(&item.folder_path, &item.options));
+    let start: run_queue::Starter = Arc::new(
+        |item: &run_queue::QueueItem, on_event: cucumber_runner::EventSink| {
+            cucumber_runner::run_cucumber(
+                &item.folder_path,
+                &item.feature_file,
+                item.scenario_name.as_deref(),
+                &item.options,
+                move |event| on_event(event),
+            )
+        },
+    );
+    run_queue::RunQueue::new(
+        database_path(app)?,
+        check,
+        start,
+        Arc::new(emit_run_events(app)),
+    )
+}
+
+fn queue_status(queue: &Arc<run_queue::RunQueue>) -> serde_json::Value {
+    match queue.status() {
+        Ok(status) => json!({ "ok": status }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Change the queue and return its status. Pumping runs preflights and starts runs, so
+/// it happens off the async workers.
+async fn update_queue(
+    app: &tauri::AppHandle,
+    update: impl FnOnce(&Arc<run_queue::RunQueue>) -> Result<(), String> + Send + 'static,
+) -> serde_json::Value {
+    let queue = app.state::<Arc<run_queue::RunQueue>>().inner().clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        update(&queue)?;
+        queue.status()
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+    match result {
+        Ok(status) => json!({ "ok": status }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Add runs to the end of the queue, they start as soon as a slot is free
+#[tauri::command(async)]
+async fn enqueue_runs(
+    app: tauri::AppHandle,
+    requests: Vec<run_queue::QueueRequest>,
+) -> serde_json::Value {
+    update_queue(&app, move |queue| {
+        run_queue::enqueue(&queue.connection()?, &requests)?;
+        queue.pump()
+    })
+    .await
+}
+
+#[tauri::command(async)]
+async fn get_queue(app: tauri::AppHandle) -> serde_json::Value {
+    queue_status(&app.state::<Arc<run_queue::RunQueue>>())
+}
+
+#[tauri::command(async)]
+async fn remove_queued_run(app: tauri::AppHandle, id: String) -> serde_json::Value {
+    let queue = app.state::<Arc<run_queue::RunQueue>>();
+    match queue
+        .connection()
+        .and_then(|connection| run_queue::remove(&connection, &id))
+    {
+        Ok(()) => queue_status(&queue),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Move a pending run to `index`, 0 runs it next
+#[tauri::command(async)]
+async fn move_queued_run(app: tauri::AppHandle, id: String, index: usize) -> serde_json::Value {
+    let queue = app.state::<Arc<run_queue::RunQueue>>();
+    match queue
+        .connection()
+        .and_then(|connection| run_queue::move_item(&connection, &id, index))
+    {
+        Ok(()) => queue_status(&queue),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command(async)]
+async fn set_queue_concurrency(app: tauri::AppHandle, max_concurrency: usize) -> serde_json::Value {
+    update_queue(&app, move |queue| {
+        run_queue::set_max_concurrency(&queue.connection()?, max_concurrency)?;
+        queue.pump()
+    })
+    .await
+}
+
+/// Pausing only holds back pending runs, runs already started keep going
+#[tauri::command(async)]
+async fn set_queue_paused(app: tauri::AppHandle, paused: bool) -> serde_json::Value {
+    update_queue(&app, move |queue| queue.set_paused(paused)).await
+}
+
+/// Runs of a repo, newest first, optionally within a time range (ms) or for a branch
+#[tauri::command]
+fn get_run_history(
+    app: tauri::AppHandle,
+    base_path: &str,
+    since: Option<i64>,
+    until: Option<i64>,
+    branch: Option<&str>,
+) -> serde_json::Value {
+    let result = open_database(&app).and_then(|connection| {
+        run_history::query_runs(&connection, base_path, since, until, branch)
+    });
+    match result {
+        Ok(runs) => json!({ "ok": runs }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn get_run_results(app: tauri::AppHandle, run_id: &str) -> serde_json::Value {
+    let result = open_database(&app).and_then(|connection| {
+        Ok(json!({
+            "run": run_history::get_run(&connection, run_id)?,
+            "results": run_history::run_results(&connection, run_id)?,
+        }))
+    });
+    match result {
+        Ok(run) => json!({ "ok": run }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Screenshots, videos, traces and logs of a run, paths are served through the asset
+/// protocol
+#[tauri::command]
+fn get_run_artifacts(app: tauri::AppHandle, run_id: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_artifacts::run_artifacts(&connection, run_id))
+    {
+        Ok(artifacts) => json!({ "ok": artifacts }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Export a run from the history as JUnit XML or a standalone HTML report, returns the
+/// report itself when no `output_path` is given
+#[tauri::command(async)]
+async fn export_run_report(
+    app: tauri::AppHandle,
+    run_id: String,
+    format: report_export::ReportFormat,
+    output_path: Option<String>,
+) -> serde_json::Value {
+    match open_database(&app).and_then(|connection| {
+        report_export::export_run(&connection, &run_id, format, output_path.as_deref())
+    }) {
+        Ok(result) => json!({ "ok": result }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Output folders of the repo that are scanned for artifacts after each run
+#[tauri::command]
+fn get_artifact_dirs(app: tauri::AppHandle, base_path: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_artifacts::output_dirs(&connection, base_path))
+    {
+        Ok(dirs) => json!({ "ok": dirs }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn set_artifact_dirs(
+    app: tauri::AppHandle,
+    base_path: &str,
+    dirs: Vec<String>,
+) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_artifacts::set_output_dirs(&connection, base_path, &dirs))
+    {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Variables the browser flags of a run set in the repo
+#[tauri::command]
+fn get_browser_env(base_path: &str) -> serde_json::Value {
+    json!({ "ok": browser_env::for_repo(base_path) })
+}
+
+#[tauri::command]
+fn set_browser_env(
+    app: tauri::AppHandle,
+    base_path: &str,
+    env: browser_env::BrowserEnv,
+) -> serde_json::Value {
+    match open_database(&app).and_then(|connection| browser_env::save(&connection, base_path, &env))
+    {
+        Ok(()) => {
+            browser_env::configure(base_path, env);
+            json!({ "ok": true })
+        }
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Last result per scenario, keyed by `file:line` relative to the repo root
+#[tauri::command]
+fn get_last_results(app: tauri::AppHandle, base_path: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_history::last_results(&connection, base_path))
+    {
+        Ok(results) => json!({ "ok": results }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Scenarios that changed outcome between runs on the same commit, flakiest first
+#[tauri::command]
+fn get_flaky_scenarios(
+    app: tauri::AppHandle,
+    base_path: &str,
+    limit: Option<usize>,
+) -> serde_json::Value {
+    let flaky = open_database(&app).and_then(|connection| {
+        flakiness::top_flaky(
+            &connection,
+            base_path,
+            chrono::Utc::now().timestamp_millis(),
+            limit.unwrap_or(20),
+        )
+    });
+    match flaky {
+        Ok(flaky) => json!({ "ok": flaky }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+async fn load_catalog_diff(
+    base_path: &str,
+    from_ref: Option<&str>,
+    to_ref: Option<&str>,
+) -> Result<catalog_diff::CatalogDiff, String> {
+    let before =
+        catalog_diff::load_catalog(&repo_source::RepoSource::new(base_path, from_ref)?).await?;
+    let after =
+        catalog_diff::load_catalog(&repo_source::RepoSource::new(base_path, to_ref)?).await?;
+    Ok(catalog_diff::diff_catalogs(&before, &after))
+}
+
+/// Diff the catalog at `from_ref` against `to_ref`, the working directory is used for a missing ref
+#[tauri::command(async)]
+async fn diff_catalogs(
+    base_path: String,
+    from_ref: Option<String>,
+    to_ref: Option<String>,
 ) -> serde_json::Value {
-    match cucumber_runner::run_cucumber(folder_path, feature_file, scenario_name) {
-        Ok(output) => json!({ "ok": output }),
+    match load_catalog_diff(&base_path, from_ref.as_deref(), to_ref.as_deref()).await {
+        Ok(diff) => json!({ "ok": diff }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command(async)]
+async fn get_catalog_release_notes(
+    base_path: String,
+    from_ref: Option<String>,
+    to_ref: Option<String>,
+) -> serde_json::Value {
+    match load_catalog_diff(&base_path, from_ref.as_deref(), to_ref.as_deref()).await {
+        Ok(diff) => json!({ "ok": catalog_diff::to_markdown(&diff) }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command(async)]
+async fn detect_changed_scenarios(base_path: String, base_ref: String) -> serde_json::Value {
+    match change_detector::detect_changes(&base_path, &base_ref) {
+        Ok(report) => json!({ "ok": report }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Scenarios sharing an organization conflict when run in parallel
+#[tauri::command(async)]
+async fn get_parallel_plan(
+    base_path: String,
+    hotspot_threshold: Option<usize>,
+) -> serde_json::Value {
+    let source = repo_source::RepoSource::working_dir(&base_path);
+    match catalog_diff::load_catalog(&source).await {
+        Ok(catalog) => {
+            let threshold =
+                hotspot_threshold.unwrap_or(conflict_detector::DEFAULT_HOTSPOT_THRESHOLD);
+            let plan = conflict_detector::analyze(
+                &catalog.features,
+                &catalog.scenarios,
+                &catalog.organizations,
+                threshold,
+            );
+            json!({ "ok": plan })
+        }
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Split the suite into `shard_count` balanced shards, timed from run history and cucumber
+/// JSON reports
+#[tauri::command(async)]
+async fn plan_shards(
+    app: tauri::AppHandle,
+    base_path: String,
+    shard_count: usize,
+    granularity: Option<shard_planner::Granularity>,
+    report_paths: Option<Vec<String>>,
+) -> serde_json::Value {
+    let history = open_database(&app).and_then(|connection| {
+        run_history::recent_durations(&connection, &base_path, shard_planner::HISTORY_RUNS)
+    });
+    let mut durations = match history {
+        Ok(durations) => durations,
+        Err(e) => return json!({ "err": e }),
+    };
+    for report_path in report_paths.unwrap_or_default() {
+        match cucumber_report_reader::read_durations(&report_path) {
+            Ok(report) => durations.extend(report),
+            Err(e) => return json!({ "err": e }),
+        }
+    }
+
+    let source = repo_source::RepoSource::working_dir(&base_path);
+    let plan = catalog_diff::load_catalog(&source)
+        .await
+        .and_then(|catalog| {
+            shard_planner::plan_shards(
+                &catalog,
+                shard_count,
+                granularity.unwrap_or_default(),
+                &durations,
+            )
+        });
+    match plan {
+        Ok(plan) => json!({ "ok": plan }),
         Err(e) => json!({ "err": e }),
     }
 }
@@ -69,9 +605,330 @@ fn detect_organization_clones(organizations: Vec<(String, String)>) -> serde_jso
     json!({ "ok": clone_groups })
 }
 
+#[tauri::command(async)]
+async fn export_cucumber_messages(
+    base_path: String,
+    output_path: Option<String>,
+) -> serde_json::Value {
+    match cucumber_messages::export_messages(&base_path, output_path.as_deref()) {
+        Ok(result) => json!({ "ok": result }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command(async)]
+async fn record_metrics_snapshot(app: tauri::AppHandle, base_path: String) -> serde_json::Value {
+    let connection = match open_database(&app) {
+        Ok(connection) => connection,
+        Err(e) => return json!({ "err": e }),
+    };
+    match metrics_store::record_current(connection, &base_path).await {
+        Ok(recorded) => json!({ "ok": recorded }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Record a snapshot for every first-parent commit not recorded yet, `since` is passed to `git log`
+#[tauri::command(async)]
+async fn backfill_metrics(
+    app: tauri::AppHandle,
+    base_path: String,
+    since: Option<String>,
+    limit: Option<usize>,
+) -> serde_json::Value {
+    let connection = match open_database(&app) {
+        Ok(connection) => connection,
+        Err(e) => return json!({ "err": e }),
+    };
+    match metrics_store::backfill(connection, &base_path, since.as_deref(), limit).await {
+        Ok(recorded) => json!({ "ok": recorded }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn get_metric_series(
+    app: tauri::AppHandle,
+    base_path: &str,
+    metric: &str,
+    bucket: &str,
+    dimension: Option<&str>,
+    name: Option<&str>,
+) -> serde_json::Value {
+    let series = open_database(&app).and_then(|connection| {
+        metrics_store::query_series(&connection, base_path, metric, bucket, dimension.zip(name))
+    });
+    match series {
+        Ok(points) => json!({ "ok": points }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn mark_broken(
+    file_path: &str,
+    line: u32,
+    examples_index: Option<usize>,
+    reason: Option<String>,
+    ticket: Option<String>,
+    until: Option<String>,
+) -> serde_json::Value {
+    let metadata = quarantine::BrokenMetadata {
+        reason,
+        ticket,
+        until,
+    };
+    match quarantine::mark_broken(file_path, line, examples_index, &metadata) {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn unmark_broken(file_path: &str, line: u32, examples_index: Option<usize>) -> serde_json::Value {
+    match quarantine::unmark_broken(file_path, line, examples_index) {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn get_expired_quarantines(base_path: &str) -> serde_json::Value {
+    let today = chrono::Local::now().date_naive();
+    match quarantine::expired_quarantines(base_path, today) {
+        Ok(expired) => json!({ "ok": expired }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn get_suites(app: tauri::AppHandle, base_path: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| suite_store::list_suites(&connection, base_path))
+    {
+        Ok(suites) => json!({ "ok": suites }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn save_suite(
+    app: tauri::AppHandle,
+    base_path: &str,
+    suite: suite_store::Suite,
+) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| suite_store::save_suite(&connection, base_path, &suite))
+    {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn delete_suite(app: tauri::AppHandle, base_path: &str, name: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| suite_store::delete_suite(&connection, base_path, name))
+    {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+async fn load_suite(
+    app: &tauri::AppHandle,
+    base_path: &str,
+    name: &str,
+) -> Result<suite_store::ResolvedSuite, String> {
+    let suite = suite_store::get_suite(&open_database(app)?, base_path, name)?;
+    let catalog =
+        catalog_diff::load_catalog(&repo_source::RepoSource::working_dir(base_path)).await?;
+    suite_store::resolve_suite(&catalog, &suite)
+}
+
+#[tauri::command(async)]
+async fn resolve_suite(
+    app: tauri::AppHandle,
+    base_path: String,
+    name: String,
+) -> serde_json::Value {
+    match load_suite(&app, &base_path, &name).await {
+        Ok(resolved) => json!({ "ok": resolved }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Start all scenarios of a suite as one cucumber invocation, using `file:line` targets
+#[tauri::command(async)]
+async fn run_suite(
+    app: tauri::AppHandle,
+    base_path: String,
+    name: String,
+    options: Option<cucumber_runner::RunOptions>,
+) -> serde_json::Value {
+    let resolved = match load_suite(&app, &base_path, &name).await {
+        Ok(resolved) => resolved,
+        Err(e) => return json!({ "err": e }),
+    };
+    let targets: Vec<String> = resolved
+        .scenarios
+        .iter()
+        .map(|s| format!("{}:{}", s.file_path, s.line))
+        .collect();
+    let options = options.unwrap_or_default();
+    if let Err(e) = require_preflight(&base_path, &options).await {
+        return json!({ "err": e });
+    }
+
+    match cucumber_runner::run_cucumber_targets(
+        &base_path,
+        &targets,
+        &options,
+        emit_run_events(&app),
+    ) {
+        Ok(run_id) => json!({ "ok": run_id }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Start a mixed selection of features, scenarios, example rows and tag expressions as
+/// one cucumber invocation, with warnings about feature files that were skipped
+#[tauri::command(async)]
+async fn run_selected(
+    app: tauri::AppHandle,
+    base_path: String,
+    selections: Vec<run_selection::Selection>,
+    options: Option<cucumber_runner::RunOptions>,
+) -> serde_json::Value {
+    let options = options.unwrap_or_default();
+    if let Err(e) = require_preflight(&base_path, &options).await {
+        return json!({ "err": e });
+    }
+    let result = run_selection::resolve_targets(&base_path, &selections).and_then(|resolved| {
+        let run_id = cucumber_runner::run_cucumber_targets(
+            &base_path,
+            &resolved.targets,
+            &options,
+            emit_run_events(&app),
+        )?;
+        Ok((run_id, resolved.warnings))
+    });
+    match result {
+        Ok((run_id, warnings)) => json!({ "ok": { "run_id": run_id, "warnings": warnings } }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Rerun the scenarios and example rows that failed in `run_id` with the same options,
+/// the new run is linked to it in the history. The history keeps env names only, `env`
+/// passes the values again.
+#[tauri::command(async)]
+async fn rerun_failed(
+    app: tauri::AppHandle,
+    run_id: String,
+    env: Option<BTreeMap<String, String>>,
+) -> serde_json::Value {
+    let mut run = match open_database(&app).and_then(|connection| {
+        run_history::get_run(&connection, &run_id)?
+            .ok_or_else(|| format!("Run not found: {}", run_id))
+    }) {
+        Ok(run) => run,
+        Err(e) => return json!({ "err": e }),
+    };
+    run.options.env = env.unwrap_or_default();
+    if let Err(e) = require_preflight(&run.repo_path, &run.options).await {
+        return json!({ "err": e });
+    }
+    let rerun = open_database(&app).and_then(|connection| {
+        let targets = run_history::failed_targets(&connection, &run_id)?;
+        if targets.is_empty() {
+            return Err(format!("Run {} has no failed scenarios", run_id));
+        }
+        let rerun_id = cucumber_runner::run_cucumber_targets(
+            &run.repo_path,
+            &targets,
+            &run.options,
+            emit_run_events(&app),
+        )?;
+        run_history::set_parent(&connection, &rerun_id, &run_id)?;
+        Ok(rerun_id)
+    });
+    match rerun {
+        Ok(rerun_id) => json!({ "ok": rerun_id }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// How Node tools are found for the e2e repo, with the binaries the user configured
+#[tauri::command(async)]
+async fn get_toolchain(base_path: String) -> serde_json::Value {
+    json!({ "ok": toolchain::resolve(std::path::Path::new(&base_path)) })
+}
+
+#[tauri::command]
+fn save_toolchain_settings(
+    app: tauri::AppHandle,
+    settings: toolchain::ToolchainSettings,
+) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| toolchain::save_settings(&connection, &settings))
+    {
+        Ok(()) => {
+            toolchain::configure(settings);
+            json!({ "ok": true })
+        }
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+/// Profiles defined in the e2e repo's cucumber config
+#[tauri::command]
+fn get_cucumber_profiles(base_path: &str) -> serde_json::Value {
+    match cucumber_config::list_profiles(base_path) {
+        Ok(profiles) => json!({ "ok": profiles }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn get_run_presets(app: tauri::AppHandle, base_path: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_presets::list_presets(&connection, base_path))
+    {
+        Ok(presets) => json!({ "ok": presets }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn save_run_preset(
+    app: tauri::AppHandle,
+    base_path: &str,
+    preset: run_presets::RunPreset,
+) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_presets::save_preset(&connection, base_path, &preset))
+    {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
+#[tauri::command]
+fn delete_run_preset(app: tauri::AppHandle, base_path: &str, name: &str) -> serde_json::Value {
+    match open_database(&app)
+        .and_then(|connection| run_presets::delete_preset(&connection, base_path, name))
+    {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "err": e }),
+    }
+}
+
 #[tauri::command]
-fn get_steps(base_path: String) -> serde_json::Value {
-    match steps_reader::parse_step_definitions(&base_path) {
+fn get_steps(base_path: String, git_ref: Option<String>) -> serde_json::Value {
+    let steps = repo_source::RepoSource::new(&base_path, git_ref.as_deref())
+        .and_then(|source| steps_reader::parse_step_definitions_from(&source));
+    match steps {
         Ok(steps) => json!({ "ok": steps }),
         Err(e) => json!({ "err": e }),
     }
@@ -91,14 +948,75 @@ pub fn run() {
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_window_state::Builder::default().build())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            let history = run_history::RunHistory::new(
+                data_dir.join("protestify.db"),
+                data_dir.join("logs"),
+                data_dir.join("artifacts"),
+            )?;
+            app.manage(Arc::new(history));
+            let connection = open_database(app.handle())?;
+            toolchain::configure(toolchain::load_settings(&connection)?);
+            browser_env::load_all(&connection)?;
+            tauri::async_runtime::spawn_blocking(toolchain::load_shell_path);
+            let queue = create_run_queue(app.handle())?;
+            app.manage(queue);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
+            backfill_metrics,
+            cancel_run,
+            delete_run_preset,
+            delete_suite,
+            detect_changed_scenarios,
             detect_organization_clones,
+            diff_catalogs,
+            enqueue_runs,
+            export_cucumber_messages,
+            export_run_report,
             find_e2e_repo,
+            get_artifact_dirs,
+            get_browser_env,
+            get_catalog_release_notes,
+            get_cucumber_profiles,
+            get_expired_quarantines,
             get_features,
             get_file_contents,
+            get_flaky_scenarios,
+            get_last_results,
+            get_metric_series,
             get_organizations,
+            get_parallel_plan,
+            get_preflight,
+            get_queue,
+            get_run_artifacts,
+            get_run_history,
+            get_run_presets,
+            get_run_results,
             get_steps,
+            get_suites,
+            get_ticket_index,
+            get_toolchain,
+            list_runs,
+            mark_broken,
+            move_queued_run,
+            plan_shards,
+            record_metrics_snapshot,
+            remove_queued_run,
+            rerun_failed,
+            resolve_suite,
             run_e2e,
+            run_selected,
+            run_suite,
+            save_run_preset,
+            save_suite,
+            save_toolchain_settings,
+            set_artifact_dirs,
+            set_browser_env,
+            set_queue_concurrency,
+            set_queue_paused,
+            unmark_broken,
             validate_e2e_repo
         ])
         .run(tauri::generate_context!())
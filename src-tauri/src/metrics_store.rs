@@ -0,0 +1,499 @@
+use crate::catalog_diff::{load_catalog, Catalog};
+use crate::git_reader::run_git;
+use crate::quarantine::BROKEN_TAG;
+use crate::repo_source::RepoSource;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const UNASSIGNED: &str = "Unassigned";
+const SUITE_METRICS: [&str; 6] = [
+    "features",
+    "scenarios",
+    "broken",
+    "organizations",
+    "teams",
+    "owners",
+];
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct GroupMetrics {
+    pub name: String,
+    pub scenarios: u32,
+    pub broken: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub recorded_at: i64,
+    pub commit_sha: Option<String>,
+    pub features: u32,
+    pub scenarios: u32,
+    pub broken: u32,
+    pub organizations: u32,
+    pub teams: u32,
+    pub owners: u32,
+    pub by_team: Vec<GroupMetrics>,
+    pub by_owner: Vec<GroupMetrics>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SeriesPoint {
+    pub period: String,
+    pub value: u32,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SkippedCommit {
+    pub commit: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Backfill {
+    pub recorded: usize,
+    /// Commits whose catalog could not be loaded, e.g. with a feature file that fails to parse
+    pub skipped: Vec<SkippedCommit>,
+}
+
+fn is_broken(tags: &[String]) -> bool {
+    tags.iter().any(|t| t == BROKEN_TAG)
+}
+
+/// Same numbers the dashboard shows, a scenario is broken when it or its feature is
+/// tagged `@broken`.
+pub fn compute_snapshot(
+    catalog: &Catalog,
+    recorded_at: i64,
+    commit_sha: Option<String>,
+) -> MetricsSnapshot {
+    let broken_features: HashSet<&str> = catalog
+        .features
+        .iter()
+        .filter(|f| is_broken(&f.tags))
+        .map(|f| f.id.as_str())
+        .collect();
+    let organizations: HashMap<&str, (&str, &str)> = catalog
+        .organizations
+        .iter()
+        .map(|o| (o.name.as_str(), (o.team.as_str(), o.blame.as_str())))
+        .collect();
+
+    let mut by_team: BTreeMap<String, GroupMetrics> = BTreeMap::new();
+    let mut by_owner: BTreeMap<String, GroupMetrics> = BTreeMap::new();
+    let mut broken = 0;
+
+    for scenario in &catalog.scenarios {
+        let scenario_broken =
+            is_broken(&scenario.tags) || broken_features.contains(scenario.feature_id.as_str());
+        let (team, owner) = organizations
+            .get(scenario.organization_name.as_str())
+            .copied()
+            .unwrap_or((UNASSIGNED, UNASSIGNED));
+
+        for (groups, name) in [(&mut by_team, team), (&mut by_owner, owner)] {
+            let group = groups
+                .entry(name.to_string())
+                .or_insert_with(|| GroupMetrics {
+                    name: name.to_string(),
+                    scenarios: 0,
+                    broken: 0,
+                });
+            group.scenarios += 1;
+            group.broken += scenario_broken as u32;
+        }
+        broken += scenario_broken as u32;
+    }
+
+    MetricsSnapshot {
+        recorded_at,
+        commit_sha,
+        features: catalog.features.len() as u32,
+        scenarios: catalog.scenarios.len() as u32,
+        broken,
+        organizations: catalog.organizations.len() as u32,
+        teams: catalog.teams.len() as u32,
+        owners: catalog.people.len() as u32,
+        by_team: by_team.into_values().collect(),
+        by_owner: by_owner.into_values().collect(),
+    }
+}
+
+/// Store a snapshot. A snapshot identical to the latest one for the same commit is
+/// skipped, so reloading the catalog does not flood the history. Returns whether a
+/// row was written.
+pub fn record_snapshot(
+    connection: &Connection,
+    repo_path: &str,
+    source: &str,
+    snapshot: &MetricsSnapshot,
+) -> Result<bool, String> {
+    let latest: Option<(Option<String>, [u32; 6])> = connection
+        .query_row(
+            "SELECT commit_sha, features, scenarios, broken, organizations, teams, owners
+             FROM metric_snapshots WHERE repo_path = ?1 ORDER BY recorded_at DESC, id DESC LIMIT 1",
+            params![repo_path],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    [
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ],
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let counts = [
+        snapshot.features,
+        snapshot.scenarios,
+        snapshot.broken,
+        snapshot.organizations,
+        snapshot.teams,
+        snapshot.owners,
+    ];
+    if source != "backfill" && latest == Some((snapshot.commit_sha.clone(), counts)) {
+        return Ok(false);
+    }
+
+    connection
+        .execute(
+            "INSERT INTO metric_snapshots
+             (repo_path, recorded_at, commit_sha, source, features, scenarios, broken, organizations, teams, owners)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                repo_path,
+                snapshot.recorded_at,
+                snapshot.commit_sha,
+                source,
+                counts[0],
+                counts[1],
+                counts[2],
+                counts[3],
+                counts[4],
+                counts[5]
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    let snapshot_id = connection.last_insert_rowid();
+
+    let groups = [("team", &snapshot.by_team), ("owner", &snapshot.by_owner)];
+    for (dimension, group) in groups {
+        for metrics in group.iter() {
+            connection
+                .execute(
+                    "INSERT INTO metric_breakdowns (snapshot_id, dimension, name, scenarios, broken)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![snapshot_id, dimension, metrics.name, metrics.scenarios, metrics.broken],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Snapshot the working directory catalog as of now.
+pub async fn record_current(connection: Connection, repo_path: &str) -> Result<bool, String> {
+    let catalog = load_catalog(&RepoSource::working_dir(repo_path)).await?;
+    let commit = crate::git_reader::head_commit(repo_path).ok();
+    let snapshot = compute_snapshot(&catalog, Utc::now().timestamp(), commit);
+    record_snapshot(&connection, repo_path, "load", &snapshot)
+}
+
+/// Walk first-parent history and store a snapshot for every commit that has none yet,
+/// timestamped with the commit date. Commits whose catalog fails to load are skipped
+/// and reported.
+pub async fn backfill(
+    connection: Connection,
+    repo_path: &str,
+    since: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Backfill, String> {
+    let mut args = vec![
+        "log".to_string(),
+        "--first-parent".into(),
+        "--format=%H %ct".into(),
+    ];
+    if let Some(since) = since {
+        args.push(format!("--since={}", since));
+    }
+    if let Some(limit) = limit {
+        args.push(format!("--max-count={}", limit));
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let log = run_git(repo_path, &args)?;
+
+    let mut result = Backfill {
+        recorded: 0,
+        skipped: Vec::new(),
+    };
+    for line in log.lines() {
+        let Some((commit, timestamp)) = line.split_once(' ') else {
+            continue;
+        };
+        let exists: bool = connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM metric_snapshots
+                 WHERE repo_path = ?1 AND commit_sha = ?2 AND source = 'backfill')",
+                params![repo_path, commit],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if exists {
+            continue;
+        }
+
+        let source = RepoSource::new(repo_path, Some(commit))?;
+        let catalog = match load_catalog(&source).await {
+            Ok(catalog) => catalog,
+            Err(error) => {
+                result.skipped.push(SkippedCommit {
+                    commit: commit.to_string(),
+                    error,
+                });
+                continue;
+            }
+        };
+        let snapshot = compute_snapshot(
+            &catalog,
+            timestamp.trim().parse().unwrap_or_default(),
+            Some(commit.to_string()),
+        );
+        record_snapshot(&connection, repo_path, "backfill", &snapshot)?;
+        result.recorded += 1;
+    }
+
+    Ok(result)
+}
+
+fn period_label(timestamp: i64, bucket: &str) -> Result<String, String> {
+    let date = DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .ok_or_else(|| format!("Invalid timestamp: {}", timestamp))?
+        .date_naive();
+
+    match bucket {
+        "day" => Ok(date.format("%Y-%m-%d").to_string()),
+        "week" => {
+            let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+            Ok(monday.format("%Y-%m-%d").to_string())
+        }
+        "month" => Ok(date.format("%Y-%m").to_string()),
+        _ => Err(format!("Unknown bucket: {}", bucket)),
+    }
+}
+
+/// Time series of `metric` per day, week (starting Monday) or month, using the last
+/// snapshot in each period. With a `dimension` ("team" or "owner") and `name`, only
+/// `scenarios` and `broken` are available and refer to that group.
+pub fn query_series(
+    connection: &Connection,
+    repo_path: &str,
+    metric: &str,
+    bucket: &str,
+    dimension: Option<(&str, &str)>,
+) -> Result<Vec<SeriesPoint>, String> {
+    let rows: Vec<(i64, u32)> = match dimension {
+        None => {
+            if !SUITE_METRICS.contains(&metric) {
+                return Err(format!("Unknown metric: {}", metric));
+            }
+            // The column name is checked against SUITE_METRICS above
+            let sql = format!(
+                "SELECT recorded_at, {} FROM metric_snapshots
+                 WHERE repo_path = ?1 ORDER BY recorded_at, id",
+                metric
+            );
+            let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+            let rows = statement
+                .query_map(params![repo_path], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?;
+            rows
+        }
+        Some((dimension, name)) => {
+            if !["scenarios", "broken"].contains(&metric) {
+                return Err(format!("Unknown metric for {}: {}", dimension, metric));
+            }
+            let sql = format!(
+                "SELECT s.recorded_at, COALESCE(b.{}, 0) FROM metric_snapshots s
+                 LEFT JOIN metric_breakdowns b
+                   ON b.snapshot_id = s.id AND b.dimension = ?2 AND b.name = ?3
+                 WHERE s.repo_path = ?1 ORDER BY s.recorded_at, s.id",
+                metric
+            );
+            let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+            let rows = statement
+                .query_map(params![repo_path, dimension, name], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| e.to_string())?
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?;
+            rows
+        }
+    };
+
+    let mut points: Vec<SeriesPoint> = Vec::new();
+    for (recorded_at, value) in rows {
+        let period = period_label(recorded_at, bucket)?;
+        match points.last_mut() {
+            Some(last) if last.period == period => last.value = value,
+            _ => points.push(SeriesPoint { period, value }),
+        }
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::open_in_memory;
+    use std::fs;
+    use tempfile::tempdir;
+
+    async fn catalog() -> Catalog {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("features")).unwrap();
+        fs::create_dir(dir.path().join("data")).unwrap();
+        fs::write(
+            dir.path().join("features/a.feature"),
+            r#"Feature: A
+  @broken
+  Scenario: One
+    Given current organization is "Org A"
+  Scenario: Two
+    Given current organization is "Org A"
+  @not-broken
+  Scenario: Three
+    Given something else
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("data/organizations.yml"),
+            "Org A:\n  id: \"1\"\n  blame: ana\n  team: core\n  users: {}\n",
+        )
+        .unwrap();
+        load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compute_snapshot() {
+        let snapshot = compute_snapshot(&catalog().await, 0, None);
+
+        assert_eq!(snapshot.features, 1);
+        assert_eq!(snapshot.scenarios, 3);
+        assert_eq!(snapshot.broken, 1);
+        assert_eq!(snapshot.organizations, 1);
+        assert_eq!(
+            snapshot.by_team,
+            vec![
+                GroupMetrics {
+                    name: "Unassigned".into(),
+                    scenarios: 1,
+                    broken: 0
+                },
+                GroupMetrics {
+                    name: "core".into(),
+                    scenarios: 2,
+                    broken: 1
+                },
+            ]
+        );
+        assert_eq!(snapshot.by_owner[1].name, "ana");
+    }
+
+    #[tokio::test]
+    async fn test_record_and_query_series() {
+        let connection = open_in_memory();
+        let catalog = catalog().await;
+        // 2026-10-05 (Monday), 2026-10-07 and 2026-10-13
+        let days = [1_791_158_400, 1_791_331_200, 1_791_849_600];
+
+        let mut snapshot = compute_snapshot(&catalog, days[0], Some("a".into()));
+        assert!(record_snapshot(&connection, "/repo", "load", &snapshot).unwrap());
+        // Same commit and numbers, nothing new to store
+        assert!(!record_snapshot(&connection, "/repo", "load", &snapshot).unwrap());
+
+        snapshot.recorded_at = days[1];
+        snapshot.broken = 2;
+        record_snapshot(&connection, "/repo", "load", &snapshot).unwrap();
+        snapshot.recorded_at = days[2];
+        snapshot.broken = 0;
+        snapshot.commit_sha = Some("b".into());
+        record_snapshot(&connection, "/repo", "load", &snapshot).unwrap();
+
+        let weekly = query_series(&connection, "/repo", "broken", "week", None).unwrap();
+        assert_eq!(
+            weekly,
+            vec![
+                SeriesPoint {
+                    period: "2026-10-05".into(),
+                    value: 2
+                },
+                SeriesPoint {
+                    period: "2026-10-12".into(),
+                    value: 0
+                },
+            ]
+        );
+
+        let daily = query_series(
+            &connection,
+            "/repo",
+            "broken",
+            "day",
+            Some(("team", "core")),
+        )
+        .unwrap();
+        assert_eq!(daily.len(), 3);
+        assert_eq!(daily[0].value, 1);
+
+        assert!(query_series(&connection, "/repo", "id; DROP", "day", None).is_err());
+        assert!(query_series(&connection, "/repo", "broken", "year", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backfill() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let git = |args: &[&str]| {
+            let mut full = vec!["-c", "user.name=Ana", "-c", "user.email=ana@example.com"];
+            full.extend_from_slice(args);
+            run_git(repo, &full).unwrap();
+        };
+        fs::create_dir(dir.path().join("features")).unwrap();
+        fs::write(
+            dir.path().join("features/a.feature"),
+            "Feature: A\n  Scenario: One\n    Given x\n",
+        )
+        .unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "first"]);
+        fs::write(
+            dir.path().join("features/b.feature"),
+            "Feature: B\n  @broken\n  Scenario: Two\n    Given y\n",
+        )
+        .unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "second"]);
+
+        let connection = open_in_memory();
+        let result = backfill(connection, repo, None, None).await.unwrap();
+        assert_eq!(result.recorded, 2);
+        assert!(result.skipped.is_empty());
+    }
+}
@@ -1,8 +1,7 @@
+use crate::repo_source::RepoSource;
 use serde::{Deserialize, Serialize};
-use serde_yml::from_reader;
+use serde_yml::from_str;
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::path::Path;
 
 pub type People = Vec<Person>;
 pub type Teams = Vec<Team>;
@@ -10,23 +9,23 @@ pub type Organizations = Vec<Organization>;
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct Person {
-    name: String,
-    avatar: String,
-    teams: HashSet<String>,
+    pub name: String,
+    pub avatar: String,
+    pub teams: HashSet<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Clone)]
 pub struct Team {
-    name: String,
+    pub name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Organization {
-    id: String,
-    name: String,
-    blame: String,
-    team: String,
-    users: HashMap<String, String>,
+    pub id: String,
+    pub name: String,
+    pub blame: String,
+    pub team: String,
+    pub users: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,16 +39,18 @@ type Avatars = HashMap<String, String>;
 type Orgs = HashMap<String, Org>;
 
 pub fn parse(folder_path: &str) -> Result<(People, Teams, Organizations), String> {
-    let org_file_path = Path::new(folder_path).join("organizations.yml");
-    let avatars_file_path = Path::new(folder_path).join("avatars.yml");
+    parse_from(&RepoSource::working_dir(folder_path))
+}
 
-    let org_file =
-        File::open(&org_file_path).map_err(|_| "organization file not found".to_string())?;
+pub fn parse_from(source: &RepoSource) -> Result<(People, Teams, Organizations), String> {
+    let org_file = source
+        .read_to_string("organizations.yml")
+        .map_err(|_| "organization file not found".to_string())?;
     let orgs: Orgs =
-        from_reader(org_file).map_err(|_| "organization file invalid YAML".to_string())?;
+        from_str(&org_file).map_err(|_| "organization file invalid YAML".to_string())?;
 
-    let avatars: Avatars = if let Ok(avatars_file) = File::open(&avatars_file_path) {
-        from_reader(avatars_file).map_err(|_| "avatars file invalid YAML".to_string())?
+    let avatars: Avatars = if let Ok(avatars_file) = source.read_to_string("avatars.yml") {
+        from_str(&avatars_file).map_err(|_| "avatars file invalid YAML".to_string())?
     } else {
         HashMap::new()
     };
@@ -0,0 +1,415 @@
+use crate::cucumber_config;
+use crate::cucumber_runner::{RunOptions, DEFAULT_PROFILE};
+use crate::e2e_locator::parse_env;
+use crate::toolchain::{self, version_matches, Toolchain};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Preflight {
+    /// No check ended in an error, warnings do not block a run
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Preflight {
+    /// Failed checks with their hints, one per line
+    pub fn errors(&self) -> String {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Error)
+            .map(|check| match &check.hint {
+                Some(hint) => format!("{}: {} ({})", check.label, check.detail, hint),
+                None => format!("{}: {}", check.label, check.detail),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn check(
+    id: &'static str,
+    label: &'static str,
+    status: CheckStatus,
+    detail: impl Into<String>,
+    hint: Option<&str>,
+) -> Check {
+    Check {
+        id,
+        label,
+        status,
+        detail: detail.into(),
+        hint: hint.map(str::to_string),
+    }
+}
+
+fn check_node_modules(repo: &Path) -> Check {
+    let label = "Dependencies installed";
+    if !repo.join("node_modules").is_dir() {
+        return check(
+            "node_modules",
+            label,
+            CheckStatus::Error,
+            "node_modules is missing",
+            Some("Run `npm install` in the e2e repo"),
+        );
+    }
+    let cucumber = repo.join("node_modules/.bin").join(if cfg!(windows) {
+        "cucumber-js.cmd"
+    } else {
+        "cucumber-js"
+    });
+    if !cucumber.is_file() {
+        return check(
+            "node_modules",
+            label,
+            CheckStatus::Error,
+            "cucumber-js is not installed",
+            Some("Run `npm install` in the e2e repo"),
+        );
+    }
+    check(
+        "node_modules",
+        label,
+        CheckStatus::Ok,
+        "node_modules is present",
+        None,
+    )
+}
+
+fn check_npx(toolchain: &Toolchain) -> Check {
+    let (runner, _) = toolchain.package_runner();
+    let found = if toolchain.use_pnpm {
+        &toolchain.pnpm
+    } else {
+        &toolchain.npx
+    };
+    match found {
+        Some(path) => check(
+            "npx",
+            "Package runner",
+            CheckStatus::Ok,
+            path.to_string_lossy(),
+            None,
+        ),
+        None => check(
+            "npx",
+            "Package runner",
+            CheckStatus::Error,
+            format!("{} was not found on PATH", runner),
+            Some("Install Node.js or pick the binary in the toolchain settings"),
+        ),
+    }
+}
+
+fn check_node_version(repo: &Path, toolchain: &Toolchain) -> Check {
+    let label = "Node version";
+    let output = toolchain
+        .blocking_command("node")
+        .arg("--version")
+        .current_dir(repo)
+        .output();
+    let actual = match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => {
+            return check(
+                "node_version",
+                label,
+                CheckStatus::Error,
+                "node could not be started",
+                Some("Install Node.js and make sure it is on PATH"),
+            )
+        }
+    };
+
+    let Some(pinned) = &toolchain.pinned else {
+        return check("node_version", label, CheckStatus::Ok, actual, None);
+    };
+    let (required, source) = (&pinned.version, &pinned.source);
+    match version_matches(required, &actual) {
+        Some(true) => check("node_version", label, CheckStatus::Ok, actual, None),
+        Some(false) => check(
+            "node_version",
+            label,
+            CheckStatus::Error,
+            format!("{} is running, {} asks for {}", actual, source, required),
+            Some("Install the pinned Node version with your version manager"),
+        ),
+        None => check(
+            "node_version",
+            label,
+            CheckStatus::Warning,
+            format!(
+                "{} is running, {} alias {} can't be verified",
+                actual, source, required
+            ),
+            None,
+        ),
+    }
+}
+
+/// PASSWORD may also come from the run's environment or the app's own, cucumber
+/// inherits both
+fn check_env(repo: &Path, run_env: &BTreeMap<String, String>) -> Check {
+    let label = ".env configured";
+    let passed_in = run_env
+        .get("PASSWORD")
+        .cloned()
+        .or_else(|| env::var("PASSWORD").ok())
+        .is_some_and(|password| !password.is_empty());
+    let file = fs::read_to_string(repo.join(".env"))
+        .ok()
+        .map(|content| parse_env(&content));
+    match file {
+        None if passed_in => check(
+            "env",
+            label,
+            CheckStatus::Warning,
+            ".env is missing, PASSWORD comes from the environment",
+            None,
+        ),
+        None => check(
+            "env",
+            label,
+            CheckStatus::Error,
+            ".env is missing",
+            Some("Copy .env.example to .env and fill in PASSWORD"),
+        ),
+        Some(env) if env.password.is_empty() && !passed_in => check(
+            "env",
+            label,
+            CheckStatus::Error,
+            "PASSWORD is not set",
+            Some("Add PASSWORD=... to .env"),
+        ),
+        Some(env) if env.app_url.is_empty() || env.api_url.is_empty() => check(
+            "env",
+            label,
+            CheckStatus::Warning,
+            "DEFAULT_APP_HOST or DEFAULT_API_HOST is not set, the test defaults apply",
+            None,
+        ),
+        Some(_) => check("env", label, CheckStatus::Ok, "PASSWORD is set", None),
+    }
+}
+
+fn dependencies(repo: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(repo.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(package) = serde_json::from_str::<Value>(&content) else {
+        return Vec::new();
+    };
+    ["dependencies", "devDependencies"]
+        .iter()
+        .filter_map(|key| package.get(key).and_then(Value::as_object))
+        .flat_map(|deps| deps.keys().cloned())
+        .collect()
+}
+
+/// Browser download folder of the automation library the repo depends on
+fn browser_cache(
+    repo: &Path,
+    dependencies: &[String],
+) -> Option<(&'static str, PathBuf, &'static str)> {
+    let uses = |name: &str| dependencies.iter().any(|d| d == name);
+    if uses("playwright") || uses("@playwright/test") || uses("playwright-core") {
+        let dir = match env::var("PLAYWRIGHT_BROWSERS_PATH") {
+            Ok(path) if path == "0" => repo.join("node_modules/playwright-core/.local-browsers"),
+            Ok(path) if !path.is_empty() => PathBuf::from(path),
+            _ => dirs::cache_dir()?.join("ms-playwright"),
+        };
+        return Some(("Playwright", dir, "Run `npx playwright install`"));
+    }
+    if uses("puppeteer") {
+        let dir = match env::var("PUPPETEER_CACHE_DIR") {
+            Ok(path) if !path.is_empty() => PathBuf::from(path),
+            _ => dirs::home_dir()?.join(".cache/puppeteer"),
+        };
+        return Some((
+            "Puppeteer",
+            dir,
+            "Run `npx puppeteer browsers install chrome`",
+        ));
+    }
+    None
+}
+
+fn check_browsers(repo: &Path) -> Check {
+    let label = "Browsers installed";
+    let Some((library, dir, hint)) = browser_cache(repo, &dependencies(repo)) else {
+        return check(
+            "browsers",
+            label,
+            CheckStatus::Skipped,
+            "No Playwright or Puppeteer dependency",
+            None,
+        );
+    };
+    let installed = fs::read_dir(&dir)
+        .map(|entries| {
+            entries.filter_map(Result::ok).any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                name.starts_with("chrom")
+                    || name.starts_with("firefox")
+                    || name.starts_with("webkit")
+            })
+        })
+        .unwrap_or(false);
+    if installed {
+        check(
+            "browsers",
+            label,
+            CheckStatus::Ok,
+            format!("{} browsers in {}", library, dir.display()),
+            None,
+        )
+    } else {
+        check(
+            "browsers",
+            label,
+            CheckStatus::Error,
+            format!("No {} browsers in {}", library, dir.display()),
+            Some(hint),
+        )
+    }
+}
+
+fn check_cucumber_config(repo: &Path, profile: &str) -> Check {
+    let label = "Cucumber profile";
+    match cucumber_config::list_profiles(&repo.to_string_lossy()) {
+        Err(e) => check("cucumber_config", label, CheckStatus::Error, e, None),
+        Ok(profiles) if profiles.is_empty() => check(
+            "cucumber_config",
+            label,
+            CheckStatus::Warning,
+            "No profiles found in the cucumber config",
+            None,
+        ),
+        // Profiles in JS configs are read without running them and may be missed
+        Ok(profiles) if !profiles.iter().any(|p| p == profile) => check(
+            "cucumber_config",
+            label,
+            CheckStatus::Warning,
+            format!(
+                "Profile {} is not defined, found {}",
+                profile,
+                profiles.join(", ")
+            ),
+            Some("Pick one of the defined profiles"),
+        ),
+        Ok(_) => check(
+            "cucumber_config",
+            label,
+            CheckStatus::Ok,
+            format!("Profile {} is defined", profile),
+            None,
+        ),
+    }
+}
+
+/// Check that the e2e repo at `repo_path` can run cucumber with `options`. Starts
+/// `node`, so keep it off the async workers.
+pub fn run_preflight(repo_path: &str, options: &RunOptions) -> Preflight {
+    let repo = Path::new(repo_path);
+    let toolchain = toolchain::resolve(repo);
+    let profile = options.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+    let checks = vec![
+        check_node_modules(repo),
+        check_npx(&toolchain),
+        check_node_version(repo, &toolchain),
+        check_env(repo, &options.env),
+        check_browsers(repo),
+        check_cucumber_config(repo, profile),
+    ];
+    Preflight {
+        ok: checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Error),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn status(preflight: &Preflight, id: &str) -> Option<CheckStatus> {
+        preflight
+            .checks
+            .iter()
+            .find(|check| check.id == id)
+            .map(|check| check.status)
+    }
+
+    #[test]
+    fn test_run_preflight() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join(".env"),
+            "DEFAULT_APP_HOST=https://app.example.com\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("cucumber.js"),
+            "module.exports = { default: '', ci: '' };\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"devDependencies": {"@cucumber/cucumber": "^10.0.0"}}"#,
+        )
+        .unwrap();
+
+        let repo = dir.path().to_str().unwrap();
+        let preflight = run_preflight(repo, &RunOptions::default());
+
+        assert!(!preflight.ok);
+        assert_eq!(status(&preflight, "node_modules"), Some(CheckStatus::Error));
+        assert_eq!(status(&preflight, "env"), Some(CheckStatus::Error));
+        assert_eq!(status(&preflight, "browsers"), Some(CheckStatus::Skipped));
+        // Only a warning, JS configs can define profiles the reader doesn't see
+        assert_eq!(
+            status(&preflight, "cucumber_config"),
+            Some(CheckStatus::Warning)
+        );
+        assert!(preflight
+            .errors()
+            .contains("PASSWORD is not set (Add PASSWORD=... to .env)"));
+
+        // The run passes PASSWORD itself, DEFAULT_API_HOST is still missing from .env
+        let options = RunOptions {
+            profile: Some("ci".into()),
+            env: BTreeMap::from([("PASSWORD".to_string(), "secret".to_string())]),
+            ..RunOptions::default()
+        };
+        let preflight = run_preflight(repo, &options);
+        assert_eq!(status(&preflight, "env"), Some(CheckStatus::Warning));
+        assert_eq!(status(&preflight, "cucumber_config"), Some(CheckStatus::Ok));
+    }
+}
@@ -0,0 +1,390 @@
+use crate::cucumber_messages::feature_files;
+use crate::gherkin_parser::{self, IdGenerator, Tag};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+pub const BROKEN_TAG: &str = "@broken";
+const TICKET_PREFIX: &str = "@ticket:";
+const UNTIL_PREFIX: &str = "@until:";
+const REASON_PREFIX: &str = "# broken:";
+
+#[derive(Debug, Default)]
+pub struct BrokenMetadata {
+    pub reason: Option<String>,
+    pub ticket: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ExpiredQuarantine {
+    pub file_path: String,
+    pub line: u32,
+    pub scenario_name: String,
+    pub examples_index: Option<usize>,
+    pub examples_name: Option<String>,
+    pub ticket: Option<String>,
+    pub reason: Option<String>,
+    pub until: String,
+    pub days_overdue: i64,
+}
+
+fn is_quarantine_tag(tag: &str) -> bool {
+    tag == BROKEN_TAG || tag.starts_with(TICKET_PREFIX) || tag.starts_with(UNTIL_PREFIX)
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Split a tag line into its tags and a trailing `# comment`, if any.
+fn split_comment(tag_line: &str) -> (&str, Option<&str>) {
+    match tag_line.find(" #") {
+        Some(position) => (
+            &tag_line[..position],
+            Some(tag_line[position..].trim_start()),
+        ),
+        None => (tag_line, None),
+    }
+}
+
+/// First line of the tags and comments directly above the keyword line.
+fn block_start(lines: &[String], keyword: usize) -> usize {
+    let mut start = keyword;
+    while start > 0 {
+        let trimmed = lines[start - 1].trim_start();
+        if !trimmed.starts_with('@') && !trimmed.starts_with('#') {
+            break;
+        }
+        start -= 1;
+    }
+    start
+}
+
+/// 0-based index of the keyword line of the scenario starting at `line`, or of its
+/// nth examples block.
+fn find_keyword_line(
+    content: &str,
+    line: u32,
+    examples_index: Option<usize>,
+) -> Result<usize, String> {
+    let document = gherkin_parser::parse("", content, &mut IdGenerator::new())?;
+    let scenarios = document
+        .feature
+        .as_ref()
+        .map(|feature| feature.scenarios())
+        .unwrap_or_default();
+    let scenario = scenarios
+        .into_iter()
+        .find(|scenario| scenario.location.line == line)
+        .ok_or_else(|| format!("No scenario at line {}", line))?;
+
+    let location = match examples_index {
+        None => &scenario.location,
+        Some(index) => {
+            &scenario
+                .examples
+                .get(index)
+                .ok_or_else(|| format!("Scenario at line {} has no examples #{}", line, index))?
+                .location
+        }
+    };
+    Ok(location.line as usize - 1)
+}
+
+/// Remove `@broken`, `@ticket:` and `@until:` tags and the reason comment above the
+/// keyword line. Returns the new index of the keyword line.
+fn strip_quarantine(lines: &mut Vec<String>, mut keyword: usize) -> usize {
+    let mut index = block_start(lines, keyword);
+    while index < keyword {
+        let trimmed = lines[index].trim_start();
+
+        if trimmed.starts_with(REASON_PREFIX) {
+            lines.remove(index);
+            keyword -= 1;
+            continue;
+        }
+
+        if trimmed.starts_with('@') {
+            let (tags, comment) = split_comment(trimmed);
+            let kept: Vec<&str> = tags
+                .split_whitespace()
+                .filter(|tag| !is_quarantine_tag(tag))
+                .collect();
+
+            if kept.len() != tags.split_whitespace().count() {
+                if kept.is_empty() && comment.is_none() {
+                    lines.remove(index);
+                    keyword -= 1;
+                    continue;
+                }
+                let indent = indent_of(&lines[index]);
+                let parts: Vec<&str> = kept.into_iter().chain(comment).collect();
+                lines[index] = format!("{}{}", indent, parts.join(" "));
+            }
+        }
+        index += 1;
+    }
+    keyword
+}
+
+/// Apply `edit` to the lines of a feature file, keeping its line endings.
+fn edit_file(
+    file_path: &str,
+    line: u32,
+    examples_index: Option<usize>,
+    edit: impl FnOnce(&mut Vec<String>, usize),
+) -> Result<(), String> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let keyword = find_keyword_line(&content, line, examples_index)?;
+
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    edit(&mut lines, keyword);
+
+    let mut updated = lines.join(newline);
+    if content.ends_with('\n') {
+        updated.push_str(newline);
+    }
+    fs::write(file_path, updated).map_err(|e| format!("Failed to write {}: {}", file_path, e))
+}
+
+/// Tag the scenario at `line` (or one of its examples blocks) as `@broken`, replacing
+/// any earlier quarantine tags and reason.
+pub fn mark_broken(
+    file_path: &str,
+    line: u32,
+    examples_index: Option<usize>,
+    metadata: &BrokenMetadata,
+) -> Result<(), String> {
+    let mut tags = vec![BROKEN_TAG.to_string()];
+    if let Some(ticket) = &metadata.ticket {
+        if ticket.is_empty() || ticket.contains(char::is_whitespace) {
+            return Err(format!("Invalid ticket: {}", ticket));
+        }
+        tags.push(format!("{}{}", TICKET_PREFIX, ticket));
+    }
+    if let Some(until) = &metadata.until {
+        NaiveDate::parse_from_str(until, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date, expected YYYY-MM-DD: {}", until))?;
+        tags.push(format!("{}{}", UNTIL_PREFIX, until));
+    }
+    let reason = metadata
+        .reason
+        .as_deref()
+        .map(|reason| reason.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|reason| !reason.is_empty());
+
+    edit_file(file_path, line, examples_index, |lines, keyword| {
+        let mut keyword = strip_quarantine(lines, keyword);
+        let indent = indent_of(&lines[keyword]).to_string();
+
+        let previous = keyword.checked_sub(1).map(|i| lines[i].trim_start());
+        match previous {
+            Some(tag_line) if tag_line.starts_with('@') && split_comment(tag_line).1.is_none() => {
+                let tag_line = &mut lines[keyword - 1];
+                *tag_line = format!("{} {}", tag_line.trim_end(), tags.join(" "));
+            }
+            _ => {
+                lines.insert(keyword, format!("{}{}", indent, tags.join(" ")));
+                keyword += 1;
+            }
+        }
+
+        if let Some(reason) = reason {
+            let start = block_start(lines, keyword);
+            lines.insert(start, format!("{}{} {}", indent, REASON_PREFIX, reason));
+        }
+    })
+}
+
+/// Remove the quarantine tags and reason from the scenario at `line` or its examples block.
+pub fn unmark_broken(
+    file_path: &str,
+    line: u32,
+    examples_index: Option<usize>,
+) -> Result<(), String> {
+    edit_file(file_path, line, examples_index, |lines, keyword| {
+        strip_quarantine(lines, keyword);
+    })
+}
+
+fn tag_value<'a>(tags: &'a [Tag], prefix: &str) -> Option<&'a str> {
+    tags.iter().find_map(|tag| tag.name.strip_prefix(prefix))
+}
+
+/// Scenarios and examples blocks tagged `@broken` whose `@until:` date is before `today`.
+pub fn expired_quarantines(
+    base_path: &str,
+    today: NaiveDate,
+) -> Result<Vec<ExpiredQuarantine>, String> {
+    let base = Path::new(base_path);
+    if !base.is_dir() {
+        return Err(format!("Not a directory: {}", base_path));
+    }
+
+    let mut expired = Vec::new();
+    for path in feature_files(base) {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(document) = gherkin_parser::parse("", &content, &mut IdGenerator::new()) else {
+            continue;
+        };
+        let Some(feature) = &document.feature else {
+            continue;
+        };
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+
+        for scenario in feature.scenarios() {
+            let blocks = std::iter::once((None, &scenario.tags, scenario.location.line)).chain(
+                scenario
+                    .examples
+                    .iter()
+                    .enumerate()
+                    .map(|(index, examples)| (Some(index), &examples.tags, examples.location.line)),
+            );
+
+            for (examples_index, tags, keyword_line) in blocks {
+                if !tags.iter().any(|tag| tag.name == BROKEN_TAG) {
+                    continue;
+                }
+                let Some(until) = tag_value(tags, UNTIL_PREFIX) else {
+                    continue;
+                };
+                let Ok(date) = NaiveDate::parse_from_str(until, "%Y-%m-%d") else {
+                    continue;
+                };
+                if date >= today {
+                    continue;
+                }
+
+                let keyword = keyword_line as usize - 1;
+                let reason = lines[block_start(&lines, keyword)..keyword]
+                    .iter()
+                    .find_map(|line| line.trim_start().strip_prefix(REASON_PREFIX))
+                    .map(|reason| reason.trim().to_string());
+
+                expired.push(ExpiredQuarantine {
+                    file_path: path.to_string_lossy().into_owned(),
+                    line: scenario.location.line,
+                    scenario_name: scenario.name.clone(),
+                    examples_index,
+                    examples_name: examples_index.map(|i| scenario.examples[i].name.clone()),
+                    ticket: tag_value(tags, TICKET_PREFIX).map(String::from),
+                    reason,
+                    until: until.to_string(),
+                    days_overdue: (today - date).num_days(),
+                });
+            }
+        }
+    }
+
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const FEATURE: &str = "Feature: Login
+
+  @smoke
+  Scenario: Valid user
+    Given a user
+
+  Scenario Outline: Roles
+    Given a <role>
+
+    Examples: Admins
+      | role  |
+      | admin |
+
+    @slow # flaky on CI
+    Examples: Guests
+      | role  |
+      | guest |
+";
+
+    #[test]
+    fn test_mark_and_unmark_broken() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("login.feature");
+        let file = path.to_str().unwrap();
+        fs::write(&path, FEATURE).unwrap();
+
+        let metadata = BrokenMetadata {
+            reason: Some("Session  expires\ntoo early".into()),
+            ticket: Some("ABC-123".into()),
+            until: Some("2026-11-01".into()),
+        };
+        mark_broken(file, 4, None, &metadata).unwrap();
+        // The reason comment moved the outline one line down
+        mark_broken(file, 8, Some(0), &BrokenMetadata::default()).unwrap();
+        mark_broken(file, 8, Some(1), &BrokenMetadata::default()).unwrap();
+
+        let marked = fs::read_to_string(&path).unwrap();
+        assert!(marked.contains(
+            "  # broken: Session expires too early\n  @smoke @broken @ticket:ABC-123 @until:2026-11-01\n  Scenario: Valid user"
+        ));
+        assert!(marked.contains("    @broken\n    Examples: Admins"));
+        assert!(marked.contains("    @slow # flaky on CI\n    @broken\n    Examples: Guests"));
+
+        // Marking again replaces the old metadata instead of stacking tags
+        mark_broken(file, 5, None, &BrokenMetadata::default()).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\n  @smoke @broken\n  Scenario: Valid user"));
+
+        unmark_broken(file, 4, None).unwrap();
+        unmark_broken(file, 7, Some(0)).unwrap();
+        unmark_broken(file, 7, Some(1)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), FEATURE);
+
+        assert!(mark_broken(file, 5, None, &BrokenMetadata::default()).is_err());
+        let invalid = BrokenMetadata {
+            until: Some("next week".into()),
+            ..Default::default()
+        };
+        assert!(mark_broken(file, 4, None, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_expired_quarantines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("login.feature");
+        fs::write(&path, FEATURE).unwrap();
+
+        let expiring = |until: &str| BrokenMetadata {
+            reason: Some("Waiting for fix".into()),
+            ticket: Some("ABC-1".into()),
+            until: Some(until.into()),
+        };
+        mark_broken(path.to_str().unwrap(), 4, None, &expiring("2026-10-01")).unwrap();
+        mark_broken(path.to_str().unwrap(), 8, Some(1), &expiring("2026-12-01")).unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let expired = expired_quarantines(dir.path().to_str().unwrap(), today).unwrap();
+        assert_eq!(
+            expired,
+            vec![ExpiredQuarantine {
+                file_path: path.to_string_lossy().into_owned(),
+                line: 5,
+                scenario_name: "Valid user".into(),
+                examples_index: None,
+                examples_name: None,
+                ticket: Some("ABC-1".into()),
+                reason: Some("Waiting for fix".into()),
+                until: "2026-10-01".into(),
+                days_overdue: 18,
+            }]
+        );
+    }
+}
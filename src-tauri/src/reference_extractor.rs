@@ -0,0 +1,233 @@
+use crate::features_reader::{Feature, Scenario};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Jira style issue keys, used when no patterns are configured
+pub const DEFAULT_TICKET_PATTERN: &str = r"\b[A-Z][A-Z0-9]+-\d+\b";
+const TICKET_TAG_PREFIX: &str = "@ticket:";
+
+lazy_static! {
+    static ref URL_RE: Regex = Regex::new(r#"https?://[^\s<>"'`|]+"#).unwrap();
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct References {
+    pub tickets: Vec<String>,
+    pub links: Vec<String>,
+    /// `@ticket:` tags, the quarantine metadata of `@broken`. They track why a test is
+    /// broken, so they are not coverage of the ticket.
+    pub quarantine_tickets: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CoveringScenario {
+    pub file_path: String,
+    pub line: u32,
+    pub name: String,
+    /// Referenced by the feature rather than the scenario itself
+    pub inherited: bool,
+}
+
+pub struct ReferenceExtractor {
+    ticket_patterns: Vec<Regex>,
+}
+
+impl ReferenceExtractor {
+    /// Patterns with a capture group use the first group as the ticket, otherwise the
+    /// whole match.
+    pub fn new(ticket_patterns: &[String]) -> Result<Self, String> {
+        let ticket_patterns = if ticket_patterns.is_empty() {
+            vec![Regex::new(DEFAULT_TICKET_PATTERN).unwrap()]
+        } else {
+            ticket_patterns
+                .iter()
+                .map(|p| Regex::new(p).map_err(|e| format!("Invalid ticket pattern {}: {}", p, e)))
+                .collect::<Result<_, _>>()?
+        };
+        Ok(ReferenceExtractor { ticket_patterns })
+    }
+
+    fn find_tickets(&self, text: &str, tickets: &mut BTreeSet<String>) {
+        for pattern in &self.ticket_patterns {
+            for captures in pattern.captures_iter(text) {
+                if let Some(ticket) = captures.get(1).or_else(|| captures.get(0)) {
+                    tickets.insert(ticket.as_str().to_string());
+                }
+            }
+        }
+    }
+
+    pub fn extract(&self, tags: &[String], texts: &[&str]) -> References {
+        let mut tickets = BTreeSet::new();
+        let mut links = BTreeSet::new();
+        let mut quarantine_tickets = BTreeSet::new();
+
+        for tag in tags {
+            match tag.strip_prefix(TICKET_TAG_PREFIX) {
+                Some(ticket) => {
+                    quarantine_tickets.insert(ticket.to_string());
+                }
+                None => self.find_tickets(tag.trim_start_matches('@'), &mut tickets),
+            }
+        }
+
+        for text in texts {
+            for link in URL_RE.find_iter(text) {
+                // Punctuation closing a sentence is not part of the link
+                links.insert(
+                    link.as_str()
+                        .trim_end_matches(['.', ',', ';', ':', ')', ']'])
+                        .to_string(),
+                );
+            }
+            self.find_tickets(text, &mut tickets);
+        }
+
+        References {
+            tickets: tickets.into_iter().collect(),
+            links: links.into_iter().collect(),
+            quarantine_tickets: quarantine_tickets.into_iter().collect(),
+        }
+    }
+}
+
+/// Fill `references` of features and scenarios from their tags, descriptions and comments.
+pub fn attach_references(
+    extractor: &ReferenceExtractor,
+    features: &mut [Feature],
+    scenarios: &mut [Scenario],
+) {
+    let texts = |description: &str, comments: &[String]| {
+        let mut texts = vec![description.to_string()];
+        texts.extend(comments.iter().cloned());
+        texts
+    };
+
+    for feature in features.iter_mut() {
+        let texts = texts(&feature.description, &feature.comments);
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        feature.references = extractor.extract(&feature.tags, &texts);
+    }
+    for scenario in scenarios.iter_mut() {
+        let texts = texts(&scenario.description, &scenario.comments);
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        scenario.references = extractor.extract(&scenario.tags, &texts);
+    }
+}
+
+/// Reverse index from ticket to the scenarios that cover it. A ticket referenced by a
+/// feature is covered by all of its scenarios, quarantine tickets don't count.
+pub fn ticket_index(
+    features: &[Feature],
+    scenarios: &[Scenario],
+) -> BTreeMap<String, Vec<CoveringScenario>> {
+    let features: HashMap<&str, &Feature> = features.iter().map(|f| (f.id.as_str(), f)).collect();
+    let mut index: BTreeMap<String, Vec<CoveringScenario>> = BTreeMap::new();
+
+    for scenario in scenarios {
+        let feature = features.get(scenario.feature_id.as_str());
+        let mut tickets: BTreeMap<&str, bool> = BTreeMap::new();
+        if let Some(feature) = feature {
+            for ticket in &feature.references.tickets {
+                tickets.insert(ticket, true);
+            }
+        }
+        for ticket in &scenario.references.tickets {
+            tickets.insert(ticket, false);
+        }
+
+        for (ticket, inherited) in tickets {
+            index
+                .entry(ticket.to_string())
+                .or_default()
+                .push(CoveringScenario {
+                    file_path: feature.map(|f| f.file_path.clone()).unwrap_or_default(),
+                    line: scenario.line,
+                    name: scenario.name.clone(),
+                    inherited,
+                });
+        }
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features_reader::get_all_features;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_extract() {
+        let extractor = ReferenceExtractor::new(&[]).unwrap();
+        let references = extractor.extract(
+            &["@smoke".into(), "@ticket:abc-1".into(), "@PAY-12".into()],
+            &[
+                "See https://jira.example.com/browse/PAY-7, and PAY-12.",
+                "no refs here",
+            ],
+        );
+        assert_eq!(references.tickets, vec!["PAY-12", "PAY-7"]);
+        assert_eq!(references.quarantine_tickets, vec!["abc-1"]);
+        assert_eq!(
+            references.links,
+            vec!["https://jira.example.com/browse/PAY-7"]
+        );
+
+        let extractor = ReferenceExtractor::new(&[r"#(\d+)".into()]).unwrap();
+        let references = extractor.extract(&[], &["Fixed in #42, not PAY-1"]);
+        assert_eq!(references.tickets, vec!["42"]);
+
+        assert!(ReferenceExtractor::new(&["(".into()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ticket_index() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("a.feature"),
+            r#"@PAY-1
+Feature: Payments
+  Covers https://wiki.example.com/payments
+
+  # Regression for PAY-2
+  Scenario: Refund
+    Given a payment
+
+  @broken @ticket:PAY-3
+  Scenario: Charge
+    Given a card
+"#,
+        )
+        .unwrap();
+
+        let (mut features, mut scenarios) =
+            get_all_features(dir.path().to_str().unwrap().to_string())
+                .await
+                .unwrap();
+        let extractor = ReferenceExtractor::new(&[]).unwrap();
+        attach_references(&extractor, &mut features, &mut scenarios);
+
+        assert_eq!(
+            features[0].references.links,
+            vec!["https://wiki.example.com/payments"]
+        );
+        assert_eq!(scenarios[0].references.tickets, vec!["PAY-2"]);
+
+        let index = ticket_index(&features, &scenarios);
+        let covering = |ticket: &str| -> Vec<(&str, bool)> {
+            index[ticket]
+                .iter()
+                .map(|c| (c.name.as_str(), c.inherited))
+                .collect()
+        };
+        assert_eq!(covering("PAY-1"), vec![("Refund", true), ("Charge", true)]);
+        assert_eq!(covering("PAY-2"), vec![("Refund", false)]);
+        assert_eq!(index["PAY-2"][0].line, 6);
+        assert!(!index.contains_key("PAY-3"));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::git_reader::run_git;
+use crate::toolchain;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// Where catalog files are read from: the checked out working directory, a git tree
+/// object so another revision can be loaded without touching the checkout, or files
+/// of a git tree already read into memory.
+#[derive(Debug, Clone)]
+pub enum RepoSource {
+    WorkingDir(PathBuf),
+    GitTree {
+        repo_path: PathBuf,
+        commit: String,
+    },
+    Snapshot {
+        base_path: PathBuf,
+        /// Path of the base below the root of `files`, empty for the root
+        prefix: String,
+        files: Arc<BTreeMap<String, String>>,
+    },
+}
+
+fn join(prefix: &str, path: &str) -> String {
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => path.to_string(),
+        (_, true) => prefix.to_string(),
+        _ => format!("{}/{}", prefix, path),
+    }
+}
+
+impl RepoSource {
+    pub fn working_dir(base_path: &str) -> Self {
+        RepoSource::WorkingDir(PathBuf::from(base_path))
+    }
+
+    /// Resolve `git_ref` (branch, tag or sha) to a commit, or use the working directory
+    /// when no ref is given.
+    pub fn new(base_path: &str, git_ref: Option<&str>) -> Result<Self, String> {
+        match git_ref {
+            None => Ok(Self::working_dir(base_path)),
+            Some(git_ref) => {
+                let commit = run_git(
+                    base_path,
+                    &[
+                        "rev-parse",
+                        "--verify",
+                        "--quiet",
+                        &format!("{}^{{commit}}", git_ref),
+                    ],
+                )
+                .map_err(|_| format!("Unknown git ref: {}", git_ref))?;
+                Ok(RepoSource::GitTree {
+                    repo_path: PathBuf::from(base_path),
+                    commit: commit.trim().to_string(),
+                })
+            }
+        }
+    }
+
+    pub fn base_path(&self) -> &Path {
+        match self {
+            RepoSource::WorkingDir(path) => path,
+            RepoSource::GitTree { repo_path, .. } => repo_path,
+            RepoSource::Snapshot { base_path, .. } => base_path,
+        }
+    }
+
+    /// The same revision rooted at `dir`, relative to the base path
+    pub fn subdir(&self, dir: &str) -> Self {
+        match self {
+            RepoSource::WorkingDir(path) => RepoSource::WorkingDir(path.join(dir)),
+            RepoSource::GitTree { repo_path, commit } => RepoSource::GitTree {
+                repo_path: repo_path.join(dir),
+                commit: commit.clone(),
+            },
+            RepoSource::Snapshot {
+                base_path,
+                prefix,
+                files,
+            } => RepoSource::Snapshot {
+                base_path: base_path.join(dir),
+                prefix: join(prefix, dir),
+                files: files.clone(),
+            },
+        }
+    }
+
+    /// Read every file under `dirs` of a git tree with one `git ls-tree` and one
+    /// `git cat-file --batch`, so loading a revision doesn't start a process per file.
+    /// The working directory is read as before.
+    pub fn snapshot(&self, dirs: &[&str]) -> Result<Self, String> {
+        let RepoSource::GitTree { repo_path, commit } = self else {
+            return Ok(self.clone());
+        };
+
+        let mut args = vec!["ls-tree", "-r", "-z", commit.as_str(), "--"];
+        args.extend_from_slice(dirs);
+        let listing = run_git(&repo_path.to_string_lossy(), &args)?;
+        let (paths, objects): (Vec<String>, Vec<String>) = listing
+            .split('\0')
+            .filter_map(|entry| {
+                let (info, path) = entry.split_once('\t')?;
+                match info.split(' ').collect::<Vec<_>>()[..] {
+                    [_, "blob", object] => Some((path.to_string(), object.to_string())),
+                    _ => None,
+                }
+            })
+            .unzip();
+        let contents = cat_file_batch(repo_path, &objects)?;
+
+        Ok(RepoSource::Snapshot {
+            base_path: repo_path.clone(),
+            prefix: String::new(),
+            files: Arc::new(paths.into_iter().zip(contents).collect()),
+        })
+    }
+
+    /// Path reported in catalog entries for a file relative to the base path
+    pub fn display_path(&self, relative: &str) -> String {
+        self.base_path()
+            .join(relative)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn dir_exists(&self, dir: &str) -> bool {
+        match self {
+            RepoSource::WorkingDir(path) => path.join(dir).is_dir(),
+            RepoSource::GitTree { repo_path, commit } => run_git(
+                &repo_path.to_string_lossy(),
+                &["cat-file", "-t", &format!("{}:./{}", commit, dir)],
+            )
+            .map(|kind| kind.trim() == "tree")
+            .unwrap_or(false),
+            RepoSource::Snapshot { prefix, files, .. } => {
+                let dir = format!("{}/", join(prefix, dir));
+                files.keys().any(|path| path.starts_with(&dir))
+            }
+        }
+    }
+
+    /// Relative paths of all files under `dir` (empty for the base path) with one of
+    /// the given extensions, in sorted order.
+    pub fn list_files(&self, dir: &str, extensions: &[&str]) -> Result<Vec<String>, String> {
+        let has_extension = |path: &str| {
+            Path::new(path)
+                .extension()
+                .and_then(std::ffi::OsStr::to_str)
+                .is_some_and(|ext| extensions.contains(&ext))
+        };
+
+        let mut files: Vec<String> = match self {
+            RepoSource::WorkingDir(base) => WalkDir::new(base.join(dir))
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_file())
+                .filter_map(|e| {
+                    e.path()
+                        .strip_prefix(base)
+                        .ok()
+                        .map(|p| p.to_string_lossy().into_owned())
+                })
+                .filter(|p| has_extension(p))
+                .collect(),
+            RepoSource::GitTree { repo_path, commit } => {
+                let tree = if dir.is_empty() { "." } else { dir };
+                run_git(
+                    &repo_path.to_string_lossy(),
+                    &["ls-tree", "-r", "--name-only", commit, "--", tree],
+                )?
+                .lines()
+                .filter(|p| has_extension(p))
+                .map(String::from)
+                .collect()
+            }
+            RepoSource::Snapshot { prefix, files, .. } => {
+                let dir = join(prefix, dir);
+                let under = |path: &str| dir.is_empty() || path.starts_with(&format!("{}/", dir));
+                files
+                    .keys()
+                    .filter(|path| under(path) && has_extension(path))
+                    .map(|path| {
+                        if prefix.is_empty() {
+                            path.clone()
+                        } else {
+                            path[prefix.len() + 1..].to_string()
+                        }
+                    })
+                    .collect()
+            }
+        };
+
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn read_to_string(&self, relative: &str) -> Result<String, String> {
+        match self {
+            RepoSource::WorkingDir(base) => fs::read_to_string(base.join(relative))
+                .map_err(|e| format!("Failed to read {}: {}", relative, e)),
+            RepoSource::GitTree { repo_path, commit } => run_git(
+                &repo_path.to_string_lossy(),
+                &["show", &format!("{}:./{}", commit, relative)],
+            ),
+            RepoSource::Snapshot { prefix, files, .. } => files
+                .get(&join(prefix, relative))
+                .cloned()
+                .ok_or_else(|| format!("Failed to read {}: not found", relative)),
+        }
+    }
+
+    /// Contents of `relatives` in order. A git tree is read with one `git cat-file --batch`
+    /// instead of a process per file.
+    pub fn read_files(&self, relatives: &[String]) -> Result<Vec<String>, String> {
+        let RepoSource::GitTree { repo_path, commit } = self else {
+            return relatives.iter().map(|r| self.read_to_string(r)).collect();
+        };
+        let objects: Vec<String> = relatives
+            .iter()
+            .map(|relative| format!("{}:./{}", commit, relative))
+            .collect();
+        cat_file_batch(repo_path, &objects)
+    }
+}
+
+/// Contents of `objects` (shas or `rev:path` names) read with one `git cat-file --batch`.
+fn cat_file_batch(repo_path: &Path, objects: &[String]) -> Result<Vec<String>, String> {
+    if objects.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut child = toolchain::std_command("git")
+        .args(["cat-file", "--batch"])
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute git: {}", e))?;
+    let input: String = objects
+        .iter()
+        .map(|object| format!("{}\n", object))
+        .collect();
+    let mut stdin = child.stdin.take().unwrap();
+    // Written from another thread, git blocks on a full stdout pipe otherwise
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to execute git: {}", e))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!(
+            "git cat-file failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    parse_batch(&output.stdout, objects)
+}
+
+/// Split `git cat-file --batch` output into one string per requested file.
+fn parse_batch(mut output: &[u8], objects: &[String]) -> Result<Vec<String>, String> {
+    let mut contents = Vec::with_capacity(objects.len());
+    for object in objects {
+        let newline = output
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| format!("Failed to read {}: truncated output", object))?;
+        let header = String::from_utf8_lossy(&output[..newline]).into_owned();
+        let size: usize = match header.split(' ').collect::<Vec<_>>()[..] {
+            [_, "blob", size] => size
+                .parse()
+                .map_err(|_| format!("Bad header: {}", header))?,
+            _ => return Err(format!("Failed to read {}: {}", object, header)),
+        };
+        let body = output
+            .get(newline + 1..newline + 1 + size)
+            .ok_or_else(|| format!("Failed to read {}: truncated output", object))?;
+        contents.push(String::from_utf8_lossy(body).into_owned());
+        // Every object is followed by a newline
+        output = output.get(newline + 2 + size..).unwrap_or_default();
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_git_tree_source_reads_committed_files() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let git = |args: &[&str]| {
+            let mut full = vec!["-c", "user.name=Ana", "-c", "user.email=ana@example.com"];
+            full.extend_from_slice(args);
+            run_git(repo, &full).unwrap();
+        };
+
+        fs::create_dir(dir.path().join("features")).unwrap();
+        fs::write(dir.path().join("features/a.feature"), "Feature: Old").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "first"]);
+        git(&["tag", "v1"]);
+
+        fs::write(dir.path().join("features/a.feature"), "Feature: New").unwrap();
+        fs::write(dir.path().join("features/b.feature"), "Feature: B").unwrap();
+
+        let source = RepoSource::new(repo, Some("v1")).unwrap();
+        assert_eq!(
+            source.list_files("", &["feature"]).unwrap(),
+            vec!["features/a.feature"]
+        );
+        assert_eq!(
+            source.read_to_string("features/a.feature").unwrap(),
+            "Feature: Old"
+        );
+        assert_eq!(
+            source
+                .read_files(&["features/a.feature".to_string()])
+                .unwrap(),
+            vec!["Feature: Old"]
+        );
+        assert!(source
+            .read_files(&["features/b.feature".to_string()])
+            .is_err());
+        let snapshot = source.snapshot(&["features"]).unwrap().subdir("features");
+        assert_eq!(
+            snapshot.list_files("", &["feature"]).unwrap(),
+            vec!["a.feature"]
+        );
+        assert_eq!(
+            snapshot.read_to_string("a.feature").unwrap(),
+            "Feature: Old"
+        );
+        assert!(source.dir_exists("features"));
+        assert!(!source.dir_exists("step-definitions"));
+        assert_eq!(
+            source
+                .subdir("features")
+                .list_files("", &["feature"])
+                .unwrap(),
+            vec!["a.feature"]
+        );
+
+        let working = RepoSource::new(repo, None).unwrap();
+        assert_eq!(
+            working.list_files("features", &["feature"]).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            working.read_to_string("features/a.feature").unwrap(),
+            "Feature: New"
+        );
+
+        assert!(RepoSource::new(repo, Some("missing")).is_err());
+    }
+}
@@ -0,0 +1,540 @@
+use crate::cucumber_report_reader::{read_results, ResultStatus, ScenarioResult, StepResult};
+use crate::run_artifacts::{run_artifacts, Artifact};
+use crate::run_history::{get_run, run_results, RunRecord};
+use chrono::{TimeZone, Utc};
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Junit,
+    Html,
+}
+
+/// Results of a stored run with their steps when its report was kept, otherwise the
+/// scenario outcomes recorded in the history.
+pub fn load_results(
+    connection: &Connection,
+    run: &RunRecord,
+) -> Result<Vec<ScenarioResult>, String> {
+    if let Some(report_path) = run
+        .report_path
+        .as_deref()
+        .filter(|p| Path::new(p).is_file())
+    {
+        return read_results(report_path);
+    }
+    Ok(run_results(connection, &run.id)?
+        .into_iter()
+        .map(|stored| {
+            let status = ResultStatus::parse(&stored.status);
+            // One placeholder step carries the stored error message
+            let steps = stored
+                .error_message
+                .map(|message| StepResult {
+                    keyword: String::new(),
+                    name: "Steps were not kept for this run".to_string(),
+                    line: None,
+                    status,
+                    seconds: stored.seconds,
+                    error_message: Some(message),
+                    stack_trace: None,
+                    attachments: Vec::new(),
+                })
+                .into_iter()
+                .collect();
+            ScenarioResult {
+                key: format!("{}:{}", stored.uri, stored.line),
+                scenario_key: stored
+                    .scenario_line
+                    .map(|line| format!("{}:{}", stored.uri, line)),
+                feature_name: stored.uri.clone(),
+                uri: stored.uri,
+                line: stored.line,
+                name: stored.name,
+                status,
+                seconds: stored.seconds,
+                steps,
+                scenario_line: stored.scenario_line,
+            }
+        })
+        .collect())
+}
+
+/// Escape text for XML and HTML, dropping control characters XML 1.0 can't hold
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn timestamp(ms: i64) -> String {
+    Utc.timestamp_millis_opt(ms)
+        .single()
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Results grouped by feature file, in report order
+fn by_feature(results: &[ScenarioResult]) -> Vec<(&str, Vec<&ScenarioResult>)> {
+    let mut features: Vec<(&str, Vec<&ScenarioResult>)> = Vec::new();
+    for result in results {
+        match features.iter_mut().find(|(uri, _)| *uri == result.uri) {
+            Some((_, scenarios)) => scenarios.push(result),
+            None => features.push((&result.uri, vec![result])),
+        }
+    }
+    features
+}
+
+/// Error of the first failing step, with its stack trace
+fn failure(result: &ScenarioResult) -> Option<String> {
+    let step = result
+        .steps
+        .iter()
+        .find(|step| step.error_message.is_some())?;
+    let message = step.error_message.clone().unwrap_or_default();
+    Some(match &step.stack_trace {
+        Some(stack) => format!("{}\n{}", message, stack),
+        None => message,
+    })
+}
+
+fn step_text(step: &StepResult) -> String {
+    format!("{} {}", step.keyword, step.name).trim().to_string()
+}
+
+#[derive(Default)]
+struct Counts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    seconds: f64,
+}
+
+impl Counts {
+    fn of<'a>(results: impl IntoIterator<Item = &'a ScenarioResult>) -> Self {
+        let mut counts = Counts::default();
+        for result in results {
+            counts.tests += 1;
+            counts.seconds += result.seconds;
+            match result.status {
+                ResultStatus::Passed => {}
+                ResultStatus::Failed => counts.failures += 1,
+                ResultStatus::Ambiguous => counts.errors += 1,
+                _ => counts.skipped += 1,
+            }
+        }
+        counts
+    }
+
+    fn attributes(&self) -> String {
+        format!(
+            r#"tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}""#,
+            self.tests, self.failures, self.errors, self.skipped, self.seconds
+        )
+    }
+}
+
+/// JUnit XML with one testsuite per feature and one testcase per scenario or example row.
+pub fn junit_xml(run: &RunRecord, results: &[ScenarioResult]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        r#"<testsuites name="{}" {}>"#,
+        escape(&run.target),
+        Counts::of(results).attributes()
+    );
+    for (uri, scenarios) in by_feature(results) {
+        let feature = &scenarios[0].feature_name;
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{}" file="{}" timestamp="{}" {}>"#,
+            escape(feature),
+            escape(uri),
+            timestamp(run.started_at),
+            Counts::of(scenarios.iter().copied()).attributes()
+        );
+
+        // Example rows share their outline's name, test names have to be unique
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        for scenario in &scenarios {
+            *names.entry(&scenario.name).or_default() += 1;
+        }
+        for scenario in scenarios {
+            let name = if names[scenario.name.as_str()] > 1 {
+                format!("{} (line {})", scenario.name, scenario.line)
+            } else {
+                scenario.name.clone()
+            };
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" file="{}" line="{}" time="{:.3}""#,
+                escape(&name),
+                escape(feature),
+                escape(uri),
+                scenario.line,
+                scenario.seconds
+            );
+            let message = failure(scenario).unwrap_or_default();
+            let summary = message.lines().next().unwrap_or_default();
+            let body = match scenario.status {
+                ResultStatus::Passed => String::new(),
+                ResultStatus::Failed | ResultStatus::Ambiguous => {
+                    let element = if scenario.status == ResultStatus::Failed {
+                        "failure"
+                    } else {
+                        "error"
+                    };
+                    format!(
+                        "      <{0} message=\"{1}\" type=\"{2}\">{3}</{0}>\n",
+                        element,
+                        escape(summary),
+                        scenario.status.as_str(),
+                        escape(&message)
+                    )
+                }
+                status => format!("      <skipped message=\"{}\"/>\n", status.as_str()),
+            };
+            let steps: Vec<String> = scenario
+                .steps
+                .iter()
+                .map(|step| format!("{} ... {}", step_text(step), step.status.as_str()))
+                .collect();
+            if body.is_empty() && steps.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            xml.push_str(&body);
+            if !steps.is_empty() {
+                let _ = writeln!(
+                    xml,
+                    "      <system-out>{}</system-out>",
+                    escape(&steps.join("\n"))
+                );
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// `file://` URL of a local path, so links keep working when the report is moved
+fn file_url(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut url = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for c in path.chars() {
+        match c {
+            ' ' => url.push_str("%20"),
+            '#' => url.push_str("%23"),
+            '%' => url.push_str("%25"),
+            '?' => url.push_str("%3F"),
+            c => url.push(c),
+        }
+    }
+    url
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+h1{font-size:1.4rem}h2{font-size:1.1rem;margin-top:2rem}\
+table{border-collapse:collapse}td,th{padding:.2rem .6rem;text-align:left;vertical-align:top}\
+.scenario{border-left:4px solid #999;margin:.8rem 0;padding:.2rem .8rem}\
+.passed{border-color:#2e7d32}.failed,.ambiguous{border-color:#c62828}\
+.skipped,.pending,.undefined{border-color:#f9a825}\
+.status{font-weight:600;text-transform:uppercase;font-size:.8rem}\
+pre{background:#fbe9e7;padding:.6rem;overflow:auto;white-space:pre-wrap}\
+.steps td:last-child{color:#666}";
+
+/// Standalone HTML report with failure messages and links to the run's artifacts.
+pub fn html_report(run: &RunRecord, results: &[ScenarioResult], artifacts: &[Artifact]) -> String {
+    let counts = Counts::of(results);
+    let mut by_scenario: BTreeMap<&str, Vec<&Artifact>> = BTreeMap::new();
+    let mut unassigned = Vec::new();
+    for artifact in artifacts {
+        match &artifact.scenario_key {
+            Some(key) => by_scenario.entry(key).or_default().push(artifact),
+            None => unassigned.push(artifact),
+        }
+    }
+    let links = |artifacts: &[&Artifact]| {
+        artifacts
+            .iter()
+            .map(|a| {
+                let name = Path::new(&a.path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| a.path.clone());
+                format!(
+                    "<li><a href=\"{}\">{}</a> ({})</li>",
+                    escape(&file_url(&a.path)),
+                    escape(&name),
+                    a.kind.as_str()
+                )
+            })
+            .collect::<String>()
+    };
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n<table>\n",
+        escape(&run.target),
+        HTML_STYLE
+    );
+    let mut summary = vec![
+        ("Status", run.status.clone()),
+        ("Command", run.command_line.clone()),
+        ("Started", timestamp(run.started_at)),
+        (
+            "Scenarios",
+            format!(
+                "{} total, {} passed, {} failed, {} errors, {} skipped",
+                counts.tests,
+                counts.tests - counts.failures - counts.errors - counts.skipped,
+                counts.failures,
+                counts.errors,
+                counts.skipped
+            ),
+        ),
+    ];
+    if let Some(ended_at) = run.ended_at {
+        summary.push((
+            "Duration",
+            format!("{:.1}s", (ended_at - run.started_at) as f64 / 1000.0),
+        ));
+    }
+    if let Some(commit) = &run.git.commit {
+        let branch = run.git.branch.as_deref().unwrap_or("detached");
+        let dirty = if run.git.dirty {
+            ", uncommitted changes"
+        } else {
+            ""
+        };
+        summary.push(("Commit", format!("{} on {}{}", commit, branch, dirty)));
+    }
+    for (label, value) in summary {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            label,
+            escape(&value)
+        );
+    }
+    html.push_str("</table>\n");
+
+    for (uri, scenarios) in by_feature(results) {
+        let _ = writeln!(
+            html,
+            "<h2>{} <small>{}</small></h2>",
+            escape(&scenarios[0].feature_name),
+            escape(uri)
+        );
+        for scenario in scenarios {
+            let status = scenario.status.as_str();
+            let _ = write!(
+                html,
+                "<div class=\"scenario {0}\">\n<p><span class=\"status\">{0}</span> {1} \
+                 <small>line {2}, {3:.1}s</small></p>\n",
+                status,
+                escape(&scenario.name),
+                scenario.line,
+                scenario.seconds
+            );
+            if !scenario.steps.is_empty() {
+                html.push_str("<table class=\"steps\">\n");
+                for step in &scenario.steps {
+                    let _ = writeln!(
+                        html,
+                        "<tr><td>{}</td><td>{}</td></tr>",
+                        escape(&step_text(step)),
+                        step.status.as_str()
+                    );
+                }
+                html.push_str("</table>\n");
+            }
+            if let Some(message) = failure(scenario) {
+                let _ = writeln!(html, "<pre>{}</pre>", escape(&message));
+            }
+            if let Some(artifacts) = by_scenario.get(scenario.key.as_str()) {
+                let _ = writeln!(html, "<ul>{}</ul>", links(artifacts));
+            }
+            html.push_str("</div>\n");
+        }
+    }
+    if !unassigned.is_empty() {
+        let _ = writeln!(
+            html,
+            "<h2>Other artifacts</h2>\n<ul>{}</ul>",
+            links(&unassigned)
+        );
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Render a run from the history as JUnit XML or HTML. With `output_path` the report
+/// is written there and the path returned, otherwise the report itself.
+pub fn export_run(
+    connection: &Connection,
+    run_id: &str,
+    format: ReportFormat,
+    output_path: Option<&str>,
+) -> Result<String, String> {
+    let run = get_run(connection, run_id)?.ok_or_else(|| format!("Run not found: {}", run_id))?;
+    let results = load_results(connection, &run)?;
+    let report = match format {
+        ReportFormat::Junit => junit_xml(&run, &results),
+        ReportFormat::Html => html_report(&run, &results, &run_artifacts(connection, run_id)?),
+    };
+
+    match output_path {
+        Some(output_path) => {
+            fs::write(output_path, &report)
+                .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+            Ok(output_path.to_string())
+        }
+        None => Ok(report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_report_reader::parse_results;
+    use crate::cucumber_runner::{RunExit, RunOptions, RunStart};
+    use crate::database;
+    use crate::run_history::{finish_run, insert_run, GitState};
+    use tempfile::tempdir;
+
+    const REPORT: &str = r#"[{"uri": "features/invoice.feature", "name": "Invoices", "elements": [
+        {"line": 3, "name": "Create <invoice>", "steps": [
+            {"keyword": "Given ", "name": "I am logged in", "line": 4,
+             "result": {"status": "passed", "duration": 1000000000}},
+            {"keyword": "When ", "name": "I save", "line": 5,
+             "result": {"status": "failed", "duration": 500000000,
+                        "error_message": "Expected \"saved\" & got <nothing>\n    at step.ts:9"}}
+        ]},
+        {"line": 12, "name": "Pay", "steps": [{"keyword": "Given ", "name": "x", "result": {"status": "passed"}}]},
+        {"line": 13, "name": "Pay", "steps": [{"keyword": "Given ", "name": "x", "result": {"status": "undefined"}}]}
+    ]}]"#;
+
+    fn stored_run(connection: &Connection, report_path: Option<String>) -> RunRecord {
+        let start = RunStart {
+            run_id: "run".into(),
+            folder_path: "/repo".into(),
+            target: "features/invoice.feature".into(),
+            command_line: "npx cucumber-js features/invoice.feature".into(),
+            options: RunOptions::default(),
+            started_at: 0,
+        };
+        insert_run(connection, &start, &GitState::default(), None).unwrap();
+        let exit = RunExit {
+            run_id: "run".into(),
+            success: false,
+            code: Some(1),
+            status: String::new(),
+            duration_ms: 2000,
+            cancelled: false,
+            report_path,
+            results: None,
+            report_error: None,
+        };
+        finish_run(connection, &exit, 2000, &parse_results(REPORT).unwrap()).unwrap();
+        get_run(connection, "run").unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let connection = database::open_in_memory();
+        let run = stored_run(&connection, None);
+
+        let xml = junit_xml(&run, &parse_results(REPORT).unwrap());
+
+        assert!(xml.contains(
+            r#"<testsuites name="features/invoice.feature" tests="3" failures="1" errors="0" skipped="1""#
+        ));
+        assert!(xml.contains(r#"<testsuite name="Invoices" file="features/invoice.feature""#));
+        assert!(xml.contains(r#"<testcase name="Create &lt;invoice&gt;" classname="Invoices""#));
+        assert!(xml.contains(
+            r#"<failure message="Expected &quot;saved&quot; &amp; got &lt;nothing&gt;" type="failed">"#
+        ));
+        assert!(xml.contains(r#"<testcase name="Pay (line 12)""#));
+        assert!(xml.contains(r#"<skipped message="undefined"/>"#));
+        assert!(xml.contains("When I save ... failed"));
+    }
+
+    #[test]
+    fn test_export_run_html() {
+        let dir = tempdir().unwrap();
+        let report = dir.path().join("run.json");
+        fs::write(&report, REPORT).unwrap();
+        let connection = database::open_in_memory();
+        stored_run(&connection, Some(report.to_string_lossy().to_string()));
+        connection
+            .execute(
+                "INSERT INTO run_artifacts (run_id, path, kind, size, modified_at, scenario_key,
+                    shared)
+                 VALUES ('run', '/repo/screenshots/create invoice.png', 'screenshot', 3, 1,
+                    'features/invoice.feature:3', 0)",
+                [],
+            )
+            .unwrap();
+
+        let output = dir.path().join("report.html");
+        let written = export_run(
+            &connection,
+            "run",
+            ReportFormat::Html,
+            Some(output.to_str().unwrap()),
+        )
+        .unwrap();
+
+        let html = fs::read_to_string(written).unwrap();
+        assert!(html.contains("<pre>Expected &quot;saved&quot; &amp; got &lt;nothing&gt;"));
+        assert!(html.contains(r#"<a href="file:///repo/screenshots/create%20invoice.png">"#));
+        assert!(html.contains("When I save</td><td>failed"));
+        assert!(html.contains("3 total, 1 passed, 1 failed, 0 errors, 1 skipped"));
+    }
+
+    #[test]
+    fn test_results_without_kept_report() {
+        let connection = database::open_in_memory();
+        let run = stored_run(&connection, Some("/gone/run.json".into()));
+
+        let results = load_results(&connection, &run).unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status, ResultStatus::Failed);
+        assert_eq!(results[0].steps.len(), 1);
+        assert!(results[1].steps.is_empty());
+        let xml = export_run(&connection, "run", ReportFormat::Junit, None).unwrap();
+        assert!(xml.contains(
+            r#"<failure message="Expected &quot;saved&quot; &amp; got &lt;nothing&gt;" type="failed">"#
+        ));
+    }
+}
@@ -0,0 +1,510 @@
+use crate::cucumber_report_reader::{ResultStatus, ScenarioResult};
+use crate::run_history::RunRecord;
+use base64::Engine;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Folders, relative to the repo, that tests write their output to
+pub const DEFAULT_OUTPUT_DIRS: [&str; 6] = [
+    "reports",
+    "screenshots",
+    "videos",
+    "traces",
+    "test-results",
+    "logs",
+];
+// File systems with coarse timestamps may date a file slightly before the run started
+const MTIME_SLACK_MS: i64 = 2_000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    Screenshot,
+    Video,
+    Trace,
+    Log,
+    Other,
+}
+
+impl ArtifactKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtifactKind::Screenshot => "screenshot",
+            ArtifactKind::Video => "video",
+            ArtifactKind::Trace => "trace",
+            ArtifactKind::Log => "log",
+            ArtifactKind::Other => "other",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Self {
+        match kind {
+            "screenshot" => ArtifactKind::Screenshot,
+            "video" => ArtifactKind::Video,
+            "trace" => ArtifactKind::Trace,
+            "log" => ArtifactKind::Log,
+            _ => ArtifactKind::Other,
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let extension = name.rsplit('.').next().unwrap_or_default();
+        match extension {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" => ArtifactKind::Screenshot,
+            "webm" | "mp4" | "mov" => ArtifactKind::Video,
+            "zip" if name.contains("trace") => ArtifactKind::Trace,
+            "log" | "txt" => ArtifactKind::Log,
+            _ => ArtifactKind::Other,
+        }
+    }
+}
+
+/// A file a run left behind, the UI loads it through the asset protocol.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub run_id: String,
+    /// Absolute path
+    pub path: String,
+    pub kind: ArtifactKind,
+    pub size: u64,
+    /// Unix timestamp in milliseconds
+    pub modified_at: i64,
+    /// `uri:line` of the scenario or example row it belongs to, when known
+    pub scenario_key: Option<String>,
+    /// Written while another run of the repo was active, it may belong to that run
+    pub shared: bool,
+}
+
+/// Artifacts found for a run, with the attachments that could not be saved.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct IndexedRun {
+    pub artifacts: Vec<Artifact>,
+    pub errors: Vec<String>,
+}
+
+fn output_dirs_key(repo_path: &str) -> String {
+    format!("artifacts.output_dirs.{}", repo_path)
+}
+
+/// Output folders scanned after each run of the repo.
+pub fn output_dirs(connection: &Connection, repo_path: &str) -> Result<Vec<String>, String> {
+    let value: Option<String> = connection
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![output_dirs_key(repo_path)],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_else(|| DEFAULT_OUTPUT_DIRS.iter().map(|d| d.to_string()).collect()))
+}
+
+pub fn set_output_dirs(
+    connection: &Connection,
+    repo_path: &str,
+    dirs: &[String],
+) -> Result<(), String> {
+    if let Some(dir) = dirs.iter().find(|dir| dir.trim().is_empty()) {
+        return Err(format!("Invalid output folder: {:?}", dir));
+    }
+    let value = serde_json::to_string(dirs).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![output_dirs_key(repo_path), value],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn modified_ms(metadata: &fs::Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(modified.as_millis() as i64)
+}
+
+fn artifact(run_id: &str, path: &Path, kind: ArtifactKind) -> Option<Artifact> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(Artifact {
+        run_id: run_id.to_string(),
+        path: path.to_string_lossy().to_string(),
+        kind,
+        size: metadata.len(),
+        modified_at: modified_ms(&metadata)?,
+        scenario_key: None,
+        shared: false,
+    })
+}
+
+/// Files in the output folders written between `since` and `until` (ms).
+fn scan(run_id: &str, repo: &Path, dirs: &[String], since: i64, until: i64) -> Vec<Artifact> {
+    let mut found = Vec::new();
+    for dir in dirs {
+        for entry in WalkDir::new(repo.join(dir))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let Some(modified) = entry.metadata().ok().as_ref().and_then(modified_ms) else {
+                continue;
+            };
+            if modified < since - MTIME_SLACK_MS || modified > until + MTIME_SLACK_MS {
+                continue;
+            }
+            let kind = ArtifactKind::from_path(entry.path());
+            found.extend(artifact(run_id, entry.path(), kind));
+        }
+    }
+    found
+}
+
+/// Lowercase words joined by dashes, how most hooks and Playwright name output files
+fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The scenario whose name appears in the artifact's path. Example rows share their
+/// outline's name, so a tie goes to the only failed one, if there is exactly one.
+fn match_scenario(path: &str, results: &[ScenarioResult]) -> Option<String> {
+    let path = slug(path);
+    let mut best: Vec<&ScenarioResult> = Vec::new();
+    let mut best_len = 0;
+    for result in results {
+        let name = slug(&result.name);
+        if name.is_empty() || !path.contains(&name) {
+            continue;
+        }
+        if name.len() > best_len {
+            best_len = name.len();
+            best.clear();
+        }
+        if name.len() == best_len {
+            best.push(result);
+        }
+    }
+    match best.as_slice() {
+        [only] => Some(only.key.clone()),
+        _ => {
+            let failed: Vec<_> = best
+                .iter()
+                .filter(|r| r.status == ResultStatus::Failed)
+                .collect();
+            match failed.as_slice() {
+                [only] => Some(only.key.clone()),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn media_extension(media_type: &str) -> Option<(&'static str, ArtifactKind)> {
+    Some(match media_type {
+        "image/png" => ("png", ArtifactKind::Screenshot),
+        "image/jpeg" => ("jpg", ArtifactKind::Screenshot),
+        "image/gif" => ("gif", ArtifactKind::Screenshot),
+        "image/webp" => ("webp", ArtifactKind::Screenshot),
+        "video/webm" => ("webm", ArtifactKind::Video),
+        "video/mp4" => ("mp4", ArtifactKind::Video),
+        "application/zip" => ("zip", ArtifactKind::Trace),
+        _ => return None,
+    })
+}
+
+/// Write base64 media embedded in the report to `path`
+fn save_embedded(data: &str, path: &Path) -> Result<(), String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, data).map_err(|e| e.to_string())
+}
+
+/// Media embedded in the report is saved to `dir`, text attachments naming an existing
+/// file point at that file. Both belong to the attaching scenario.
+fn attachments(
+    run_id: &str,
+    repo: &Path,
+    results: &[ScenarioResult],
+    dir: &Path,
+    errors: &mut Vec<String>,
+) -> Vec<Artifact> {
+    let mut found = Vec::new();
+    for result in results {
+        let attached = result.steps.iter().flat_map(|step| &step.attachments);
+        for (index, attachment) in attached.enumerate() {
+            let data = attachment.data.trim();
+            let artifact = match media_extension(&attachment.media_type) {
+                Some((extension, kind)) => {
+                    let name = format!("{}-{}-{}", slug(&result.uri), result.line, index);
+                    let path = dir.join(name).with_extension(extension);
+                    if let Err(e) = save_embedded(data, &path) {
+                        errors.push(format!(
+                            "Failed to save attachment {}: {}",
+                            path.display(),
+                            e
+                        ));
+                        continue;
+                    }
+                    artifact(run_id, &path, kind)
+                }
+                None if attachment.media_type == "text/plain" && !data.contains('\n') => {
+                    let path = repo.join(data);
+                    if !path.is_file() {
+                        continue;
+                    }
+                    artifact(run_id, &path, ArtifactKind::from_path(&path))
+                }
+                None => None,
+            };
+            found.extend(artifact.map(|artifact| Artifact {
+                scenario_key: Some(result.key.clone()),
+                ..artifact
+            }));
+        }
+    }
+    found
+}
+
+/// `(started_at, ended_at)` of the other runs of the repo that overlap `since..until`,
+/// unfinished ones end at `i64::MAX`
+fn overlapping_runs(
+    connection: &Connection,
+    run: &RunRecord,
+    since: i64,
+    until: i64,
+) -> Result<Vec<(i64, i64)>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT started_at, COALESCE(ended_at, ?4) FROM runs
+             WHERE repo_path = ?1 AND id != ?2 AND started_at <= ?3
+                AND COALESCE(ended_at, ?4) >= ?5",
+        )
+        .map_err(|e| e.to_string())?;
+    let windows = statement
+        .query_map(
+            params![run.repo_path, run.id, until, i64::MAX, since],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(windows)
+}
+
+/// Find the artifacts of a finished run and store them. Embedded attachments are saved
+/// to `attachment_dir`.
+pub fn index_run(
+    connection: &Connection,
+    run: &RunRecord,
+    ended_at: i64,
+    results: &[ScenarioResult],
+    attachment_dir: &Path,
+) -> Result<IndexedRun, String> {
+    let repo = Path::new(&run.repo_path);
+    let dirs = output_dirs(connection, &run.repo_path)?;
+    let (since, until) = (run.started_at - MTIME_SLACK_MS, ended_at + MTIME_SLACK_MS);
+    let others = overlapping_runs(connection, run, since, until)?;
+
+    let mut artifacts: BTreeMap<String, Artifact> = BTreeMap::new();
+    for mut artifact in scan(&run.id, repo, &dirs, run.started_at, ended_at) {
+        let relative = Path::new(&artifact.path)
+            .strip_prefix(repo)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|_| artifact.path.clone());
+        artifact.scenario_key = match_scenario(&relative, results);
+        artifact.shared = others.iter().any(|(started, ended)| {
+            (started - MTIME_SLACK_MS..=ended.saturating_add(MTIME_SLACK_MS))
+                .contains(&artifact.modified_at)
+        });
+        artifacts.insert(artifact.path.clone(), artifact);
+    }
+    // Attachments know their run and scenario for sure
+    let mut errors = Vec::new();
+    for artifact in attachments(&run.id, repo, results, attachment_dir, &mut errors) {
+        artifacts.insert(artifact.path.clone(), artifact);
+    }
+
+    let transaction = connection
+        .unchecked_transaction()
+        .map_err(|e| e.to_string())?;
+    for artifact in artifacts.values() {
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO run_artifacts (run_id, path, kind, size, modified_at,
+                    scenario_key, shared)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    artifact.run_id,
+                    artifact.path,
+                    artifact.kind.as_str(),
+                    artifact.size as i64,
+                    artifact.modified_at,
+                    artifact.scenario_key,
+                    artifact.shared
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    transaction.commit().map_err(|e| e.to_string())?;
+    Ok(IndexedRun {
+        artifacts: artifacts.into_values().collect(),
+        errors,
+    })
+}
+
+pub fn run_artifacts(connection: &Connection, run_id: &str) -> Result<Vec<Artifact>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT run_id, path, kind, size, modified_at, scenario_key, shared
+             FROM run_artifacts WHERE run_id = ?1 ORDER BY modified_at, path",
+        )
+        .map_err(|e| e.to_string())?;
+    let artifacts = statement
+        .query_map(params![run_id], |row| {
+            let kind: String = row.get(2)?;
+            let size: i64 = row.get(3)?;
+            Ok(Artifact {
+                run_id: row.get(0)?,
+                path: row.get(1)?,
+                kind: ArtifactKind::parse(&kind),
+                size: size as u64,
+                modified_at: row.get(4)?,
+                scenario_key: row.get(5)?,
+                shared: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_report_reader::parse_results;
+    use crate::cucumber_runner::{RunOptions, RunStart};
+    use crate::database;
+    use crate::run_history::{get_run, insert_run, GitState};
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_match_scenario() {
+        let results = parse_results(
+            r#"[{"uri": "features/invoice.feature", "elements": [
+                {"line": 3, "name": "Create invoice", "steps": []},
+                {"line": 8, "name": "Create invoice draft", "steps": []},
+                {"line": 14, "name": "Pay invoice", "steps": [{"result": {"status": "passed"}}]},
+                {"line": 15, "name": "Pay invoice", "steps": [{"result": {"status": "failed"}}]}
+            ]}]"#,
+        )
+        .unwrap();
+
+        let key = |path: &str| match_scenario(path, &results);
+        assert_eq!(
+            key("screenshots/Create invoice draft 1.png"),
+            Some("features/invoice.feature:8".into())
+        );
+        assert_eq!(
+            key("test-results/create-invoice-chromium/video.webm"),
+            Some("features/invoice.feature:3".into())
+        );
+        assert_eq!(
+            key("screenshots/pay_invoice.png"),
+            Some("features/invoice.feature:15".into())
+        );
+        assert_eq!(key("logs/browser.log"), None);
+    }
+
+    #[test]
+    fn test_index_run() {
+        let repo = tempdir().unwrap();
+        let data = tempdir().unwrap();
+        let connection = database::open_in_memory();
+        let started_at = Utc::now().timestamp_millis();
+        let start = RunStart {
+            run_id: "run".into(),
+            folder_path: repo.path().to_string_lossy().to_string(),
+            target: "features/a.feature".into(),
+            command_line: String::new(),
+            options: RunOptions::default(),
+            started_at,
+        };
+        insert_run(&connection, &start, &GitState::default(), None).unwrap();
+        let run = get_run(&connection, "run").unwrap().unwrap();
+        // Still running next to it
+        let other = RunStart {
+            run_id: "other".into(),
+            ..start.clone()
+        };
+        insert_run(&connection, &other, &GitState::default(), None).unwrap();
+
+        fs::create_dir_all(repo.path().join("screenshots")).unwrap();
+        fs::create_dir_all(repo.path().join("traces/nested")).unwrap();
+        fs::write(repo.path().join("screenshots/log-in.png"), "png").unwrap();
+        fs::write(repo.path().join("traces/nested/trace-1.zip"), "zip").unwrap();
+        fs::write(repo.path().join("browser.log"), "not in an output folder").unwrap();
+        let results = parse_results(
+            r#"[{"uri": "features/a.feature", "elements": [
+                {"line": 2, "name": "Log in", "steps": [{"embeddings": [
+                    {"mime_type": "image/png", "data": "iVBORw0KGgo="},
+                    {"mime_type": "text/plain", "data": "traces/nested/trace-1.zip"},
+                    {"mime_type": "image/png", "data": "not base64"}
+                ]}]}
+            ]}]"#,
+        )
+        .unwrap();
+
+        let ended_at = Utc::now().timestamp_millis();
+        let indexed = index_run(&connection, &run, ended_at, &results, data.path()).unwrap();
+        assert_eq!(indexed.errors.len(), 1);
+        assert!(indexed.errors[0].starts_with("Failed to save attachment"));
+
+        let artifacts = run_artifacts(&connection, "run").unwrap();
+        let summary: Vec<(ArtifactKind, Option<&str>)> = artifacts
+            .iter()
+            .map(|a| (a.kind, a.scenario_key.as_deref()))
+            .collect();
+        assert_eq!(artifacts.len(), 3);
+        assert!(summary
+            .iter()
+            .all(|(_, key)| *key == Some("features/a.feature:2")));
+        let saved = artifacts
+            .iter()
+            .find(|a| a.path.starts_with(&*data.path().to_string_lossy()))
+            .unwrap();
+        assert_eq!(saved.kind, ArtifactKind::Screenshot);
+        assert_eq!(fs::read(&saved.path).unwrap(), b"\x89PNG\r\n\x1a\n");
+        assert!(artifacts
+            .iter()
+            .any(|a| a.kind == ArtifactKind::Trace && a.path.ends_with("trace-1.zip")));
+        // Only the scanned file could have come from the other run
+        let shared: Vec<bool> = artifacts.iter().map(|a| a.shared).collect();
+        assert_eq!(shared.iter().filter(|shared| **shared).count(), 1);
+        assert!(artifacts
+            .iter()
+            .any(|a| a.shared && a.path.ends_with("log-in.png")));
+
+        let dirs = vec!["out".to_string()];
+        set_output_dirs(&connection, &run.repo_path, &dirs).unwrap();
+        assert_eq!(output_dirs(&connection, &run.repo_path).unwrap(), dirs);
+    }
+}
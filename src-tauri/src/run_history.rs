@@ -0,0 +1,804 @@
+use crate::cucumber_report_reader::{ResultStatus, ScenarioDuration, ScenarioResult};
+use crate::cucumber_runner::{RunEvent, RunExit, RunOptions, RunStart};
+use crate::database;
+use crate::git_reader::run_git;
+use crate::run_artifacts::index_run;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// Emitted with a `HistoryError` when a run could not be recorded completely
+pub const ERROR_EVENT: &str = "e2e-run-history-error";
+/// Stored in place of env values, a run's env may carry secrets like `PASSWORD`
+pub const REDACTED: &str = "<redacted>";
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct GitState {
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    /// Uncommitted changes in the working tree when the run started
+    pub dirty: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub id: String,
+    pub repo_path: String,
+    pub target: String,
+    pub command_line: String,
+    pub options: RunOptions,
+    pub git: GitState,
+    /// Unix timestamps in milliseconds
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub exit_code: Option<i32>,
+    /// `running`, `passed`, `failed`, `cancelled` or `interrupted`
+    pub status: String,
+    pub log_path: Option<String>,
+    pub report_path: Option<String>,
+    /// The run this one reran the failures of
+    pub parent_run_id: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HistoryError {
+    pub run_id: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StoredResult {
+    pub run_id: String,
+    pub uri: String,
+    pub line: u32,
+    pub scenario_line: Option<u32>,
+    pub name: String,
+    pub status: String,
+    pub seconds: f64,
+    pub error_message: Option<String>,
+}
+
+/// Most recent result of a scenario, outline rows are folded into their outline.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LastResult {
+    pub uri: String,
+    pub scenario_line: u32,
+    pub name: String,
+    pub status: String,
+    pub seconds: f64,
+    pub run_id: String,
+    pub ended_at: i64,
+}
+
+pub fn git_state(repo_path: &str) -> GitState {
+    let output = |args: &[&str]| {
+        run_git(repo_path, args)
+            .ok()
+            .map(|out| out.trim().to_string())
+    };
+    GitState {
+        commit: output(&["rev-parse", "HEAD"]),
+        branch: output(&["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD"),
+        dirty: output(&["status", "--porcelain"]).is_some_and(|status| !status.is_empty()),
+    }
+}
+
+pub fn insert_run(
+    connection: &Connection,
+    start: &RunStart,
+    git: &GitState,
+    log_path: Option<&str>,
+) -> Result<(), String> {
+    let env: BTreeMap<&str, &str> = start
+        .options
+        .env
+        .keys()
+        .map(|key| (key.as_str(), REDACTED))
+        .collect();
+    let env = serde_json::to_string(&env).map_err(|e| e.to_string())?;
+    let extra_args = serde_json::to_string(&start.options.extra_args).map_err(|e| e.to_string())?;
+    let browser = serde_json::to_string(&start.options.browser).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO runs (id, repo_path, target, command_line, profile, env, extra_args,
+                browser, commit_sha, branch, dirty, started_at, status, log_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 'running', ?13)",
+            params![
+                start.run_id,
+                start.folder_path,
+                start.target,
+                start.command_line,
+                start.options.profile,
+                env,
+                extra_args,
+                browser,
+                git.commit,
+                git.branch,
+                git.dirty,
+                start.started_at,
+                log_path
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn finish_run(
+    connection: &Connection,
+    exit: &RunExit,
+    ended_at: i64,
+    results: &[ScenarioResult],
+) -> Result<(), String> {
+    let status = if exit.cancelled {
+        "cancelled"
+    } else if exit.success {
+        "passed"
+    } else {
+        "failed"
+    };
+
+    let transaction = connection
+        .unchecked_transaction()
+        .map_err(|e| e.to_string())?;
+    transaction
+        .execute(
+            "UPDATE runs SET ended_at = ?2, exit_code = ?3, status = ?4, report_path = ?5
+             WHERE id = ?1",
+            params![exit.run_id, ended_at, exit.code, status, exit.report_path],
+        )
+        .map_err(|e| e.to_string())?;
+    for result in results {
+        let error_message = result
+            .steps
+            .iter()
+            .find_map(|step| step.error_message.as_deref());
+        transaction
+            .execute(
+                "INSERT INTO run_results (run_id, uri, line, scenario_line, name, status, seconds,
+                    error_message)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    exit.run_id,
+                    result.uri,
+                    result.line,
+                    result.scenario_line,
+                    result.name,
+                    result.status.as_str(),
+                    result.seconds,
+                    error_message
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    transaction.commit().map_err(|e| e.to_string())
+}
+
+const RUN_COLUMNS: &str = "id, repo_path, target, command_line, profile, env, commit_sha, branch,
+    dirty, started_at, ended_at, exit_code, status, log_path, report_path, extra_args,
+    parent_run_id, browser";
+
+fn run_record(row: &rusqlite::Row) -> rusqlite::Result<RunRecord> {
+    let env: String = row.get(5)?;
+    let extra_args: String = row.get(15)?;
+    let browser: String = row.get(17)?;
+    Ok(RunRecord {
+        id: row.get(0)?,
+        repo_path: row.get(1)?,
+        target: row.get(2)?,
+        command_line: row.get(3)?,
+        options: RunOptions {
+            profile: row.get(4)?,
+            extra_args: serde_json::from_str(&extra_args).unwrap_or_default(),
+            env: serde_json::from_str(&env).unwrap_or_default(),
+            browser: serde_json::from_str(&browser).unwrap_or_default(),
+        },
+        git: GitState {
+            commit: row.get(6)?,
+            branch: row.get(7)?,
+            dirty: row.get(8)?,
+        },
+        started_at: row.get(9)?,
+        ended_at: row.get(10)?,
+        exit_code: row.get(11)?,
+        status: row.get(12)?,
+        log_path: row.get(13)?,
+        report_path: row.get(14)?,
+        parent_run_id: row.get(16)?,
+    })
+}
+
+pub fn get_run(connection: &Connection, run_id: &str) -> Result<Option<RunRecord>, String> {
+    connection
+        .query_row(
+            &format!("SELECT {} FROM runs WHERE id = ?1", RUN_COLUMNS),
+            params![run_id],
+            run_record,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Runs of a repo, newest first, optionally limited to a start time range (ms) and
+/// a branch.
+pub fn query_runs(
+    connection: &Connection,
+    repo_path: &str,
+    since: Option<i64>,
+    until: Option<i64>,
+    branch: Option<&str>,
+) -> Result<Vec<RunRecord>, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT {} FROM runs
+             WHERE repo_path = ?1 AND started_at >= ?2 AND started_at <= ?3
+                AND (?4 IS NULL OR branch = ?4)
+             ORDER BY started_at DESC",
+            RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let runs = statement
+        .query_map(
+            params![
+                repo_path,
+                since.unwrap_or(i64::MIN),
+                until.unwrap_or(i64::MAX),
+                branch
+            ],
+            run_record,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(runs)
+}
+
+pub fn run_results(connection: &Connection, run_id: &str) -> Result<Vec<StoredResult>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT run_id, uri, line, scenario_line, name, status, seconds, error_message
+             FROM run_results WHERE run_id = ?1 ORDER BY uri, line",
+        )
+        .map_err(|e| e.to_string())?;
+    let results = statement
+        .query_map(params![run_id], |row| {
+            Ok(StoredResult {
+                run_id: row.get(0)?,
+                uri: row.get(1)?,
+                line: row.get(2)?,
+                scenario_line: row.get(3)?,
+                name: row.get(4)?,
+                status: row.get(5)?,
+                seconds: row.get(6)?,
+                error_message: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(results)
+}
+
+/// `file:line:line` targets for the scenarios and example rows that did not pass in
+/// a run, skipped ones excluded.
+pub fn failed_targets(connection: &Connection, run_id: &str) -> Result<Vec<String>, String> {
+    let mut failed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for result in run_results(connection, run_id)? {
+        if !matches!(result.status.as_str(), "passed" | "skipped") {
+            failed
+                .entry(result.uri)
+                .or_default()
+                .push(result.line.to_string());
+        }
+    }
+    Ok(failed
+        .into_iter()
+        .map(|(uri, lines)| format!("{}:{}", uri, lines.join(":")))
+        .collect())
+}
+
+pub fn set_parent(
+    connection: &Connection,
+    run_id: &str,
+    parent_run_id: &str,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE runs SET parent_run_id = ?2 WHERE id = ?1",
+            params![run_id, parent_run_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The last finished result of every scenario in a repo, keyed by `uri:scenario_line`.
+/// Example rows of the same run fold into their outline with the worst status.
+pub fn last_results(
+    connection: &Connection,
+    repo_path: &str,
+) -> Result<BTreeMap<String, LastResult>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT r.uri, COALESCE(r.scenario_line, r.line), r.name, r.status, r.seconds,
+                runs.id, runs.ended_at
+             FROM run_results r JOIN runs ON runs.id = r.run_id
+             WHERE runs.repo_path = ?1 AND runs.ended_at IS NOT NULL
+             ORDER BY runs.ended_at, runs.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(params![repo_path], |row| {
+            Ok(LastResult {
+                uri: row.get(0)?,
+                scenario_line: row.get(1)?,
+                name: row.get(2)?,
+                status: row.get(3)?,
+                seconds: row.get(4)?,
+                run_id: row.get(5)?,
+                ended_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut last: BTreeMap<String, LastResult> = BTreeMap::new();
+    for row in rows {
+        let row = row.map_err(|e| e.to_string())?;
+        let key = format!("{}:{}", row.uri, row.scenario_line);
+        match last.get_mut(&key) {
+            Some(existing) if existing.run_id == row.run_id => {
+                existing.seconds += row.seconds;
+                if ResultStatus::parse(&row.status) > ResultStatus::parse(&existing.status) {
+                    existing.status = row.status;
+                }
+            }
+            _ => {
+                last.insert(key, row);
+            }
+        }
+    }
+    Ok(last)
+}
+
+/// Durations of the scenarios and example rows that passed or failed in the last `runs`
+/// finished runs of a repo. Skipped and undefined ones never ran their steps.
+pub fn recent_durations(
+    connection: &Connection,
+    repo_path: &str,
+    runs: usize,
+) -> Result<Vec<ScenarioDuration>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT r.uri, r.line, r.seconds
+             FROM run_results r JOIN (
+                 SELECT id FROM runs WHERE repo_path = ?1 AND ended_at IS NOT NULL
+                 ORDER BY ended_at DESC, id DESC LIMIT ?2
+             ) recent ON recent.id = r.run_id
+             WHERE r.status IN ('passed', 'failed')
+             ORDER BY r.uri, r.line",
+        )
+        .map_err(|e| e.to_string())?;
+    let durations = statement
+        .query_map(params![repo_path, runs as i64], |row| {
+            Ok(ScenarioDuration {
+                uri: row.get(0)?,
+                line: row.get(1)?,
+                seconds: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(durations)
+}
+
+/// Problems found after the run go to the end of its log, next to its output
+fn append_to_log(run: &RunRecord, lines: &[String]) -> Result<(), String> {
+    let Some(log_path) = run.log_path.as_ref().filter(|_| !lines.is_empty()) else {
+        return Ok(());
+    };
+    fs::OpenOptions::new()
+        .append(true)
+        .open(log_path)
+        .and_then(|mut log| lines.iter().try_for_each(|line| writeln!(log, "{}", line)))
+        .map_err(|e| format!("Failed to write run log {}: {}", log_path, e))
+}
+
+/// Records every run into the local database, its output into a log file and the
+/// artifacts it leaves behind. Env values are stored redacted.
+pub struct RunHistory {
+    db_path: PathBuf,
+    log_dir: PathBuf,
+    /// Attachments embedded in reports are saved here, one folder per run
+    artifact_dir: PathBuf,
+    logs: Mutex<HashMap<String, File>>,
+    /// Events are recorded on one thread, in order and off the async workers
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl RunHistory {
+    /// Runs still marked as running belong to an earlier session that never saw them
+    /// exit, they are marked interrupted.
+    pub fn new(db_path: PathBuf, log_dir: PathBuf, artifact_dir: PathBuf) -> Result<Self, String> {
+        database::open(&db_path)?
+            .execute(
+                "UPDATE runs SET status = 'interrupted' WHERE status = 'running'",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        thread::spawn(move || receiver.into_iter().for_each(|job| job()));
+        Ok(RunHistory {
+            db_path,
+            log_dir,
+            artifact_dir,
+            logs: Mutex::new(HashMap::new()),
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    pub fn connection(&self) -> Result<Connection, String> {
+        database::open(&self.db_path)
+    }
+
+    /// Record `event` and hand it to `then` with the problems recording it ran into. An
+    /// exit has its results stored and its report moved next to the log by then, so the
+    /// run is complete in the history by the time the exit is emitted.
+    pub fn record(
+        self: &Arc<Self>,
+        mut event: RunEvent,
+        then: impl FnOnce(RunEvent, Vec<HistoryError>) + Send + 'static,
+    ) {
+        let history = self.clone();
+        let job: Job = Box::new(move || {
+            let run_id = match &event {
+                RunEvent::Start(start) => start.run_id.clone(),
+                RunEvent::Output(output) => output.run_id.clone(),
+                RunEvent::Exit(exit) => exit.run_id.clone(),
+            };
+            let errors = history
+                .apply(&mut event)
+                .into_iter()
+                .map(|message| HistoryError {
+                    run_id: run_id.clone(),
+                    message,
+                })
+                .collect();
+            then(event, errors);
+        });
+        // The thread only stops with a panicking job, record in place from then on
+        if let Err(mpsc::SendError(job)) = self.jobs.lock().unwrap().send(job) {
+            job();
+        }
+    }
+
+    fn apply(&self, event: &mut RunEvent) -> Vec<String> {
+        let mut errors = Vec::new();
+        let recorded = match event {
+            RunEvent::Start(start) => self.start(start, &mut errors),
+            RunEvent::Output(output) => {
+                if let Some(log) = self.logs.lock().unwrap().get_mut(&output.run_id) {
+                    let _ = writeln!(log, "{}", output.line);
+                }
+                Ok(())
+            }
+            RunEvent::Exit(exit) => {
+                self.logs.lock().unwrap().remove(&exit.run_id);
+                if let Err(e) = self.keep_report(exit) {
+                    errors.push(e);
+                }
+                self.finish(exit, &mut errors)
+            }
+        };
+        if let Err(e) = recorded {
+            errors.push(format!("Failed to record run history: {}", e));
+        }
+        errors
+    }
+
+    fn start(&self, start: &RunStart, errors: &mut Vec<String>) -> Result<(), String> {
+        let log_path = self.log_dir.join(format!("{}.log", start.run_id));
+        let log = fs::create_dir_all(&self.log_dir).and_then(|_| File::create(&log_path));
+        let log_path = match log {
+            Ok(log) => {
+                self.logs.lock().unwrap().insert(start.run_id.clone(), log);
+                Some(log_path.to_string_lossy().to_string())
+            }
+            Err(e) => {
+                errors.push(format!(
+                    "Failed to create run log {}: {}",
+                    log_path.display(),
+                    e
+                ));
+                None
+            }
+        };
+
+        insert_run(
+            &self.connection()?,
+            start,
+            &git_state(&start.folder_path),
+            log_path.as_deref(),
+        )
+    }
+
+    /// Reports are written to the temp dir, keep them next to the log for exports
+    fn keep_report(&self, exit: &mut RunExit) -> Result<(), String> {
+        let Some(report_path) = &exit.report_path else {
+            return Ok(());
+        };
+        let kept = self.log_dir.join(format!("{}.json", exit.run_id));
+        // The temp dir may be on another file system
+        fs::create_dir_all(&self.log_dir)
+            .and_then(|_| {
+                fs::rename(report_path, &kept).or_else(|_| {
+                    fs::copy(report_path, &kept).and_then(|_| fs::remove_file(report_path))
+                })
+            })
+            .map_err(|e| format!("Failed to keep report {}: {}", report_path, e))?;
+        exit.report_path = Some(kept.to_string_lossy().to_string());
+        Ok(())
+    }
+
+    fn finish(&self, exit: &RunExit, errors: &mut Vec<String>) -> Result<(), String> {
+        let connection = self.connection()?;
+        let ended_at = Utc::now().timestamp_millis();
+        let run = get_run(&connection, &exit.run_id)?;
+        let attachment_dir = self.artifact_dir.join(&exit.run_id);
+
+        let results = exit.results.as_deref().unwrap_or_default();
+        finish_run(&connection, exit, ended_at, results)?;
+        if let Some(run) = &run {
+            let logged = match index_run(&connection, run, ended_at, results, &attachment_dir) {
+                Ok(indexed) => append_to_log(run, &indexed.errors),
+                Err(e) => Err(format!("Failed to index run artifacts: {}", e)),
+            };
+            if let Err(e) = logged {
+                errors.push(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_report_reader::parse_results;
+    use crate::cucumber_runner::BrowserFlags;
+    use crate::cucumber_runner::RunOutput;
+    use crate::cucumber_runner::Stream;
+    use tempfile::tempdir;
+
+    fn start(run_id: &str, folder_path: &str, started_at: i64) -> RunStart {
+        RunStart {
+            run_id: run_id.into(),
+            folder_path: folder_path.into(),
+            target: "features/a.feature".into(),
+            command_line: "npx cucumber-js features/a.feature -p local".into(),
+            options: RunOptions {
+                profile: Some("local".into()),
+                extra_args: vec!["--retry".into(), "1".into()],
+                env: BTreeMap::from([("HEADLESS".to_string(), "1".to_string())]),
+                browser: BrowserFlags {
+                    trace: true,
+                    ..BrowserFlags::default()
+                },
+            },
+            started_at,
+        }
+    }
+
+    fn exit(run_id: &str, success: bool) -> RunExit {
+        RunExit {
+            run_id: run_id.into(),
+            success,
+            code: Some(if success { 0 } else { 1 }),
+            status: String::new(),
+            duration_ms: 10,
+            cancelled: false,
+            report_path: None,
+            results: None,
+            report_error: None,
+        }
+    }
+
+    fn results(rows: &[(u32, &str)]) -> Vec<ScenarioResult> {
+        let elements: Vec<String> = rows
+            .iter()
+            .map(|(line, status)| {
+                format!(
+                    r#"{{"line": {}, "name": "S", "steps": [{{"result": {{"status": "{}", "duration": 1000000000}}}}]}}"#,
+                    line, status
+                )
+            })
+            .collect();
+        let mut results = parse_results(&format!(
+            r#"[{{"uri": "features/a.feature", "elements": [{}]}}]"#,
+            elements.join(",")
+        ))
+        .unwrap();
+        for result in &mut results {
+            // Rows 7 and 8 belong to an outline on line 4
+            result.scenario_line = Some(if result.line > 4 { 4 } else { result.line });
+        }
+        results
+    }
+
+    #[test]
+    fn test_record_and_query_runs() {
+        let connection = database::open_in_memory();
+        let git = GitState {
+            commit: Some("abc".into()),
+            branch: Some("main".into()),
+            dirty: true,
+        };
+
+        insert_run(
+            &connection,
+            &start("r1", "/repo", 1_000),
+            &git,
+            Some("r1.log"),
+        )
+        .unwrap();
+        finish_run(
+            &connection,
+            &exit("r1", false),
+            2_000,
+            &results(&[(2, "failed"), (7, "passed"), (8, "passed")]),
+        )
+        .unwrap();
+        let feature_branch = GitState {
+            branch: Some("feature".into()),
+            ..git.clone()
+        };
+        insert_run(
+            &connection,
+            &start("r2", "/repo", 3_000),
+            &feature_branch,
+            None,
+        )
+        .unwrap();
+        finish_run(
+            &connection,
+            &exit("r2", false),
+            4_000,
+            &results(&[(7, "passed"), (8, "failed")]),
+        )
+        .unwrap();
+        insert_run(&connection, &start("r3", "/repo", 5_000), &git, None).unwrap();
+
+        let run = get_run(&connection, "r1").unwrap().unwrap();
+        assert_eq!(run.git, git);
+        let mut options = start("r1", "/repo", 0).options;
+        options.env = BTreeMap::from([("HEADLESS".to_string(), REDACTED.to_string())]);
+        assert_eq!(run.options, options);
+        assert_eq!((run.status.as_str(), run.ended_at), ("failed", Some(2_000)));
+        assert_eq!(run.log_path.as_deref(), Some("r1.log"));
+        assert_eq!(run_results(&connection, "r1").unwrap().len(), 3);
+
+        let ids = |runs: Vec<RunRecord>| runs.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        let all = query_runs(&connection, "/repo", None, None, None).unwrap();
+        assert_eq!(ids(all), vec!["r3", "r2", "r1"]);
+        let range = query_runs(&connection, "/repo", Some(2_000), Some(4_000), None).unwrap();
+        assert_eq!(ids(range), vec!["r2"]);
+        let main = query_runs(&connection, "/repo", None, None, Some("main")).unwrap();
+        assert_eq!(ids(main), vec!["r3", "r1"]);
+
+        let last = last_results(&connection, "/repo").unwrap();
+        assert_eq!(last["features/a.feature:2"].run_id, "r1");
+        assert_eq!(last["features/a.feature:2"].status, "failed");
+        let outline = &last["features/a.feature:4"];
+        assert_eq!(
+            (outline.run_id.as_str(), outline.status.as_str()),
+            ("r2", "failed")
+        );
+        assert_eq!(outline.seconds, 2.0);
+
+        let durations = recent_durations(&connection, "/repo", 1).unwrap();
+        let lines: Vec<u32> = durations.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![7, 8]);
+        assert_eq!(durations[0].seconds, 1.0);
+        assert_eq!(recent_durations(&connection, "/repo", 10).unwrap().len(), 5);
+
+        assert_eq!(
+            failed_targets(&connection, "r1").unwrap(),
+            vec!["features/a.feature:2"]
+        );
+        assert_eq!(
+            failed_targets(&connection, "r2").unwrap(),
+            vec!["features/a.feature:8"]
+        );
+        set_parent(&connection, "r3", "r2").unwrap();
+        let rerun = get_run(&connection, "r3").unwrap().unwrap();
+        assert_eq!(rerun.parent_run_id.as_deref(), Some("r2"));
+    }
+
+    /// Record `event` and wait until it is handed on
+    fn record(history: &Arc<RunHistory>, event: RunEvent) -> (RunEvent, Vec<HistoryError>) {
+        let (sender, receiver) = mpsc::channel();
+        history.record(event, move |event, errors| {
+            let _ = sender.send((event, errors));
+        });
+        receiver.recv().unwrap()
+    }
+
+    #[test]
+    fn test_history_records_events() {
+        let dir = tempdir().unwrap();
+        let history = Arc::new(
+            RunHistory::new(
+                dir.path().join("protestify.db"),
+                dir.path().join("logs"),
+                dir.path().join("artifacts"),
+            )
+            .unwrap(),
+        );
+        let repo = dir.path().to_str().unwrap();
+
+        record(&history, RunEvent::Start(start("r1", repo, 1_000)));
+        record(
+            &history,
+            RunEvent::Output(RunOutput {
+                run_id: "r1".into(),
+                stream: Stream::Stdout,
+                line: "1 scenario (1 passed)".into(),
+            }),
+        );
+        let report = dir.path().join("report.json");
+        fs::write(&report, "[]").unwrap();
+        let (event, errors) = record(
+            &history,
+            RunEvent::Exit(RunExit {
+                report_path: Some(report.to_string_lossy().to_string()),
+                ..exit("r1", true)
+            }),
+        );
+        assert!(errors.is_empty());
+
+        let connection = history.connection().unwrap();
+        let run = get_run(&connection, "r1").unwrap().unwrap();
+        assert_eq!(run.status, "passed");
+        // Not a git repository
+        assert_eq!(run.git, GitState::default());
+        let log = fs::read_to_string(run.log_path.unwrap()).unwrap();
+        assert_eq!(log, "1 scenario (1 passed)\n");
+        let kept = dir.path().join("logs").join("r1.json");
+        assert_eq!(run.report_path, Some(kept.to_string_lossy().to_string()));
+        let RunEvent::Exit(exited) = event else {
+            panic!("expected the exit");
+        };
+        assert_eq!(exited.report_path, run.report_path);
+        assert!(kept.is_file());
+        assert!(!report.exists());
+
+        // A run that never exited is interrupted for the next session
+        record(&history, RunEvent::Start(start("r2", repo, 2_000)));
+        let (_, errors) = record(
+            &history,
+            RunEvent::Exit(RunExit {
+                report_path: Some(report.to_string_lossy().to_string()),
+                ..exit("r3", false)
+            }),
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].run_id, "r3");
+        assert!(errors[0].message.starts_with("Failed to keep report"));
+        let history = RunHistory::new(
+            dir.path().join("protestify.db"),
+            dir.path().join("logs"),
+            dir.path().join("artifacts"),
+        )
+        .unwrap();
+        let run = get_run(&history.connection().unwrap(), "r2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.status, "interrupted");
+    }
+}
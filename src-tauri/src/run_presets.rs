@@ -0,0 +1,115 @@
+use crate::cucumber_runner::RunOptions;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Run options saved under a name, per e2e repo.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunPreset {
+    pub name: String,
+    pub options: RunOptions,
+}
+
+pub fn save_preset(
+    connection: &Connection,
+    repo_path: &str,
+    preset: &RunPreset,
+) -> Result<(), String> {
+    if preset.name.trim().is_empty() {
+        return Err("Preset name is required".to_string());
+    }
+    preset.options.validate()?;
+    let options = serde_json::to_string(&preset.options).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO run_presets (repo_path, name, options, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (repo_path, name) DO UPDATE
+             SET options = excluded.options, updated_at = excluded.updated_at",
+            params![
+                repo_path,
+                preset.name,
+                options,
+                Utc::now().timestamp_millis()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn delete_preset(connection: &Connection, repo_path: &str, name: &str) -> Result<(), String> {
+    let deleted = connection
+        .execute(
+            "DELETE FROM run_presets WHERE repo_path = ?1 AND name = ?2",
+            params![repo_path, name],
+        )
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err(format!("Preset not found: {}", name));
+    }
+    Ok(())
+}
+
+pub fn list_presets(connection: &Connection, repo_path: &str) -> Result<Vec<RunPreset>, String> {
+    let mut statement = connection
+        .prepare("SELECT name, options FROM run_presets WHERE repo_path = ?1 ORDER BY name")
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(params![repo_path], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(name, options)| {
+            Ok(RunPreset {
+                name,
+                options: serde_json::from_str(&options).map_err(|e| e.to_string())?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_runner::BrowserFlags;
+    use crate::database;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_save_list_and_delete_presets() {
+        let connection = database::open_in_memory();
+        let mut staging = RunPreset {
+            name: "Staging".into(),
+            options: RunOptions {
+                profile: Some("ci".into()),
+                extra_args: vec!["--retry".into(), "1".into()],
+                env: BTreeMap::from([(
+                    "DEFAULT_APP_HOST".to_string(),
+                    "https://app.staging.example.com".to_string(),
+                )]),
+                browser: BrowserFlags {
+                    headed: true,
+                    ..BrowserFlags::default()
+                },
+            },
+        };
+
+        save_preset(&connection, "/repo", &staging).unwrap();
+        staging.options.extra_args.clear();
+        save_preset(&connection, "/repo", &staging).unwrap();
+        assert_eq!(list_presets(&connection, "/repo").unwrap(), vec![staging]);
+        assert!(list_presets(&connection, "/other").unwrap().is_empty());
+
+        let unnamed = RunPreset {
+            name: " ".into(),
+            options: RunOptions::default(),
+        };
+        assert!(save_preset(&connection, "/repo", &unnamed).is_err());
+
+        delete_preset(&connection, "/repo", "Staging").unwrap();
+        assert!(delete_preset(&connection, "/repo", "Staging").is_err());
+    }
+}
@@ -0,0 +1,618 @@
+use crate::cucumber_runner::{EventSink, RunEvent, RunOptions};
+use crate::database;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+pub const DEFAULT_MAX_CONCURRENCY: usize = 1;
+const MAX_CONCURRENCY_SETTING: &str = "queue.max_concurrency";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QueueRequest {
+    pub folder_path: String,
+    pub feature_file: String,
+    pub scenario_name: Option<String>,
+    #[serde(default)]
+    pub options: RunOptions,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QueueItem {
+    pub id: String,
+    pub folder_path: String,
+    pub feature_file: String,
+    pub scenario_name: Option<String>,
+    pub options: RunOptions,
+    /// Unix timestamp in milliseconds
+    pub enqueued_at: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunningItem {
+    pub item: QueueItem,
+    pub run_id: String,
+}
+
+/// An item taken out of the queue because it failed its check
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FailedItem {
+    pub item: QueueItem,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueueStatus {
+    pub paused: bool,
+    pub max_concurrency: usize,
+    pub running: Vec<RunningItem>,
+    pub pending: Vec<QueueItem>,
+    /// Items that failed their check since the app started, oldest first
+    pub failed: Vec<FailedItem>,
+    /// Why the queue paused itself, cleared on resume
+    pub error: Option<String>,
+}
+
+/// Checked before an item starts, e.g. a preflight. An item that fails it leaves the
+/// queue on its own, the rest keeps going.
+pub type Check = Arc<dyn Fn(&QueueItem) -> Result<(), String> + Send + Sync>;
+
+/// Starts a queued item and returns its run ID, exit events must reach the sink.
+pub type Starter = Arc<dyn Fn(&QueueItem, EventSink) -> Result<String, String> + Send + Sync>;
+
+fn db_error(e: rusqlite::Error) -> String {
+    e.to_string()
+}
+
+pub fn pending_items(connection: &Connection) -> Result<Vec<QueueItem>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT id, folder_path, feature_file, scenario_name, enqueued_at, options
+             FROM run_queue WHERE run_id IS NULL ORDER BY position",
+        )
+        .map_err(db_error)?;
+    let items = statement
+        .query_map([], |row| {
+            let options: String = row.get(5)?;
+            Ok(QueueItem {
+                id: row.get(0)?,
+                folder_path: row.get(1)?,
+                feature_file: row.get(2)?,
+                scenario_name: row.get(3)?,
+                options: serde_json::from_str(&options).unwrap_or_default(),
+                enqueued_at: row.get(4)?,
+            })
+        })
+        .map_err(db_error)?
+        .collect::<Result<_, _>>()
+        .map_err(db_error)?;
+    Ok(items)
+}
+
+pub fn enqueue(
+    connection: &Connection,
+    requests: &[QueueRequest],
+) -> Result<Vec<QueueItem>, String> {
+    // All or nothing, the UI would otherwise show a failed enqueue of a partly queued batch
+    for request in requests {
+        request.options.validate()?;
+    }
+    let transaction = connection.unchecked_transaction().map_err(db_error)?;
+    let last: i64 = transaction
+        .query_row(
+            "SELECT COALESCE(MAX(position), 0) FROM run_queue",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+
+    let mut items = Vec::new();
+    for (offset, request) in requests.iter().enumerate() {
+        let item = QueueItem {
+            id: Uuid::new_v4().to_string(),
+            folder_path: request.folder_path.clone(),
+            feature_file: request.feature_file.clone(),
+            scenario_name: request.scenario_name.clone(),
+            options: request.options.clone(),
+            enqueued_at: Utc::now().timestamp_millis(),
+        };
+        let options = serde_json::to_string(&item.options).map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "INSERT INTO run_queue (id, position, folder_path, feature_file, scenario_name, enqueued_at, options)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    item.id,
+                    last + 1 + offset as i64,
+                    item.folder_path,
+                    item.feature_file,
+                    item.scenario_name,
+                    item.enqueued_at,
+                    options
+                ],
+            )
+            .map_err(db_error)?;
+        items.push(item);
+    }
+    transaction.commit().map_err(db_error)?;
+    Ok(items)
+}
+
+/// Remove a pending item, running ones leave the queue when they finish.
+pub fn remove(connection: &Connection, id: &str) -> Result<(), String> {
+    let removed = connection
+        .execute(
+            "DELETE FROM run_queue WHERE id = ?1 AND run_id IS NULL",
+            params![id],
+        )
+        .map_err(db_error)?;
+    if removed == 0 {
+        return Err(format!("Queued run not found: {}", id));
+    }
+    Ok(())
+}
+
+/// Move a pending item to `index` in the queue, later items shift back.
+pub fn move_item(connection: &Connection, id: &str, index: usize) -> Result<(), String> {
+    let mut ids: Vec<String> = pending_items(connection)?
+        .into_iter()
+        .map(|item| item.id)
+        .collect();
+    let current = ids
+        .iter()
+        .position(|item| item == id)
+        .ok_or_else(|| format!("Queued run not found: {}", id))?;
+    let moved = ids.remove(current);
+    ids.insert(index.min(ids.len()), moved);
+
+    let transaction = connection.unchecked_transaction().map_err(db_error)?;
+    for (position, id) in ids.iter().enumerate() {
+        transaction
+            .execute(
+                "UPDATE run_queue SET position = ?1 WHERE id = ?2",
+                params![position as i64 + 1, id],
+            )
+            .map_err(db_error)?;
+    }
+    transaction.commit().map_err(db_error)
+}
+
+fn mark_running(connection: &Connection, id: &str, run_id: &str) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE run_queue SET run_id = ?1 WHERE id = ?2",
+            params![run_id, id],
+        )
+        .map_err(db_error)?;
+    Ok(())
+}
+
+fn delete_item(connection: &Connection, id: &str) -> Result<(), String> {
+    connection
+        .execute("DELETE FROM run_queue WHERE id = ?1", params![id])
+        .map_err(db_error)?;
+    Ok(())
+}
+
+/// Put items that were running when the app stopped back at the front of the queue,
+/// in the order they were started.
+fn requeue_interrupted(connection: &Connection) -> Result<(), String> {
+    let last: i64 = connection
+        .query_row(
+            "SELECT COALESCE(MAX(position), 0) FROM run_queue",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    connection
+        .execute(
+            "UPDATE run_queue SET run_id = NULL, position = position - ?1
+             WHERE run_id IS NOT NULL",
+            params![last],
+        )
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub fn max_concurrency(connection: &Connection) -> Result<usize, String> {
+    let value: Option<String> = connection
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![MAX_CONCURRENCY_SETTING],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_error)?;
+    Ok(value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENCY))
+}
+
+pub fn set_max_concurrency(connection: &Connection, value: usize) -> Result<(), String> {
+    if value == 0 {
+        return Err("Max concurrency must be at least 1".to_string());
+    }
+    connection
+        .execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![MAX_CONCURRENCY_SETTING, value.to_string()],
+        )
+        .map_err(db_error)?;
+    Ok(())
+}
+
+struct QueueState {
+    paused: bool,
+    /// In the order they were started
+    running: Vec<RunningItem>,
+    /// Items being started outside the lock, they count toward the limit
+    starting: Vec<String>,
+    /// Items whose run exited before their start returned
+    exited: Vec<String>,
+    failed: Vec<FailedItem>,
+    error: Option<String>,
+}
+
+/// Runs queued items with at most `max_concurrency` at a time. Items live in the
+/// database until their run exits, so a queue left behind by a restart comes back
+/// paused, with the interrupted runs first.
+pub struct RunQueue {
+    db_path: PathBuf,
+    check: Check,
+    start: Starter,
+    sink: EventSink,
+    state: Mutex<QueueState>,
+}
+
+impl RunQueue {
+    pub fn new(
+        db_path: PathBuf,
+        check: Check,
+        start: Starter,
+        sink: EventSink,
+    ) -> Result<Arc<Self>, String> {
+        let connection = database::open(&db_path)?;
+        requeue_interrupted(&connection)?;
+        let has_pending = !pending_items(&connection)?.is_empty();
+        Ok(Arc::new(RunQueue {
+            db_path,
+            check,
+            start,
+            sink,
+            state: Mutex::new(QueueState {
+                paused: has_pending,
+                running: Vec::new(),
+                starting: Vec::new(),
+                exited: Vec::new(),
+                failed: Vec::new(),
+                error: None,
+            }),
+        }))
+    }
+
+    pub fn connection(&self) -> Result<Connection, String> {
+        database::open(&self.db_path)
+    }
+
+    pub fn status(&self) -> Result<QueueStatus, String> {
+        let connection = self.connection()?;
+        let state = self.state.lock().unwrap();
+
+        Ok(QueueStatus {
+            paused: state.paused,
+            max_concurrency: max_concurrency(&connection)?,
+            running: state.running.clone(),
+            pending: pending_items(&connection)?,
+            failed: state.failed.clone(),
+            error: state.error.clone(),
+        })
+    }
+
+    pub fn set_paused(self: &Arc<Self>, paused: bool) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.paused = paused;
+            if !paused {
+                state.error = None;
+            }
+        }
+        self.pump()
+    }
+
+    /// Start pending items until the concurrency limit is reached. An item that fails
+    /// its check is dropped, one that fails to start stays first in the queue and pauses
+    /// it. Checks and starts block and run without holding the lock, so call this off
+    /// the async workers.
+    pub fn pump(self: &Arc<Self>) -> Result<(), String> {
+        let connection = self.connection()?;
+        let limit = max_concurrency(&connection)?;
+
+        loop {
+            let item = {
+                let mut state = self.state.lock().unwrap();
+                if state.paused || state.running.len() + state.starting.len() >= limit {
+                    break;
+                }
+                let next = pending_items(&connection)?
+                    .into_iter()
+                    .find(|item| !state.starting.contains(&item.id));
+                let Some(item) = next else {
+                    break;
+                };
+                state.starting.push(item.id.clone());
+                item
+            };
+
+            if let Err(error) = (self.check)(&item) {
+                delete_item(&connection, &item.id)?;
+                let mut state = self.state.lock().unwrap();
+                state.starting.retain(|id| id != &item.id);
+                state.failed.push(FailedItem { item, error });
+                continue;
+            }
+            let started = (self.start)(&item, self.on_event(&item.id));
+
+            let mut state = self.state.lock().unwrap();
+            state.starting.retain(|id| id != &item.id);
+            match started {
+                Ok(_) if state.exited.contains(&item.id) => {
+                    state.exited.retain(|id| id != &item.id);
+                }
+                Ok(run_id) => {
+                    mark_running(&connection, &item.id, &run_id)?;
+                    state.running.push(RunningItem { item, run_id });
+                }
+                Err(e) => {
+                    state.paused = true;
+                    state.error = Some(format!("Failed to start {}: {}", item.feature_file, e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_event(self: &Arc<Self>, item_id: &str) -> EventSink {
+        let queue = Arc::clone(self);
+        let sink = Arc::clone(&self.sink);
+        let item_id = item_id.to_string();
+        Arc::new(move |event: RunEvent| {
+            let finished = matches!(event, RunEvent::Exit(_));
+            sink(event);
+            if finished {
+                queue.finished(&item_id);
+            }
+        })
+    }
+
+    fn finished(self: &Arc<Self>, item_id: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.starting.iter().any(|id| id == item_id) {
+                state.exited.push(item_id.to_string());
+            }
+            state.running.retain(|running| running.item.id != item_id);
+        }
+        let result = self
+            .connection()
+            .and_then(|connection| delete_item(&connection, item_id))
+            .and_then(|_| self.pump());
+        if let Err(e) = result {
+            let mut state = self.state.lock().unwrap();
+            state.paused = true;
+            state.error = Some(format!("Failed to continue the run queue: {}", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_runner::RunExit;
+    use tempfile::tempdir;
+
+    fn request(feature_file: &str) -> QueueRequest {
+        QueueRequest {
+            folder_path: "/repo".into(),
+            feature_file: feature_file.into(),
+            scenario_name: None,
+            options: RunOptions::default(),
+        }
+    }
+
+    fn files(items: &[QueueItem]) -> Vec<&str> {
+        items.iter().map(|i| i.feature_file.as_str()).collect()
+    }
+
+    fn pass() -> Check {
+        Arc::new(|_| Ok(()))
+    }
+
+    #[test]
+    fn test_enqueue_move_and_remove() {
+        let connection = database::open_in_memory();
+        let items = enqueue(&connection, &[request("a"), request("b"), request("c")]).unwrap();
+
+        move_item(&connection, &items[2].id, 0).unwrap();
+        assert_eq!(
+            files(&pending_items(&connection).unwrap()),
+            vec!["c", "a", "b"]
+        );
+        move_item(&connection, &items[2].id, 10).unwrap();
+        assert_eq!(
+            files(&pending_items(&connection).unwrap()),
+            vec!["a", "b", "c"]
+        );
+
+        remove(&connection, &items[1].id).unwrap();
+        enqueue(&connection, &[request("d")]).unwrap();
+        assert_eq!(
+            files(&pending_items(&connection).unwrap()),
+            vec!["a", "c", "d"]
+        );
+        assert!(remove(&connection, &items[1].id).is_err());
+
+        assert_eq!(
+            max_concurrency(&connection).unwrap(),
+            DEFAULT_MAX_CONCURRENCY
+        );
+        set_max_concurrency(&connection, 3).unwrap();
+        assert_eq!(max_concurrency(&connection).unwrap(), 3);
+        assert!(set_max_concurrency(&connection, 0).is_err());
+    }
+
+    #[test]
+    fn test_enqueue_rejects_the_whole_batch() {
+        let connection = database::open_in_memory();
+        let mut invalid = request("b");
+        invalid.options.env.insert("A=B".into(), "1".into());
+
+        assert!(enqueue(&connection, &[request("a"), invalid, request("c")]).is_err());
+        assert!(pending_items(&connection).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_queue_respects_concurrency_and_resumes_paused() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("protestify.db");
+        let connection = database::open(&db_path).unwrap();
+        enqueue(&connection, &[request("a"), request("b"), request("c")]).unwrap();
+        set_max_concurrency(&connection, 2).unwrap();
+
+        // Runs finish only when the test says so
+        let started: Arc<Mutex<Vec<(String, EventSink)>>> = Arc::new(Mutex::new(Vec::new()));
+        let starter: Starter = {
+            let started = Arc::clone(&started);
+            Arc::new(move |item: &QueueItem, on_event: EventSink| {
+                started
+                    .lock()
+                    .unwrap()
+                    .push((item.feature_file.clone(), on_event));
+                Ok(format!("run-{}", item.feature_file))
+            })
+        };
+        let queue =
+            RunQueue::new(db_path.clone(), pass(), starter.clone(), Arc::new(|_| {})).unwrap();
+
+        // Pending items from an earlier session wait for an explicit resume
+        queue.pump().unwrap();
+        assert!(queue.status().unwrap().paused);
+        assert!(started.lock().unwrap().is_empty());
+
+        queue.set_paused(false).unwrap();
+        let status = queue.status().unwrap();
+        assert_eq!(status.running.len(), 2);
+        assert_eq!(files(&status.pending), vec!["c"]);
+
+        let (_, on_event) = started.lock().unwrap().remove(0);
+        on_event(RunEvent::Exit(RunExit {
+            run_id: "run-a".into(),
+            success: true,
+            code: Some(0),
+            status: "exit status: 0".into(),
+            duration_ms: 1,
+            cancelled: false,
+            report_path: None,
+            results: None,
+            report_error: None,
+        }));
+
+        let status = queue.status().unwrap();
+        let running: Vec<&str> = status.running.iter().map(|r| r.run_id.as_str()).collect();
+        assert_eq!(running, vec!["run-b", "run-c"]);
+        assert!(status.pending.is_empty());
+
+        // Runs cut off by a restart go back to the front of the paused queue
+        enqueue(&connection, &[request("d")]).unwrap();
+        let queue = RunQueue::new(db_path, pass(), starter, Arc::new(|_| {})).unwrap();
+        let status = queue.status().unwrap();
+        assert!(status.paused);
+        assert_eq!(files(&status.pending), vec!["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_queue_handles_runs_that_exit_while_starting() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("protestify.db");
+        // The run is over before the starter returns, the queue isn't locked meanwhile
+        let starter: Starter = Arc::new(|item: &QueueItem, on_event: EventSink| {
+            let run_id = format!("run-{}", item.feature_file);
+            on_event(RunEvent::Exit(RunExit {
+                run_id: run_id.clone(),
+                success: true,
+                code: Some(0),
+                status: "exit status: 0".into(),
+                duration_ms: 1,
+                cancelled: false,
+                report_path: None,
+                results: None,
+                report_error: None,
+            }));
+            Ok(run_id)
+        });
+        let queue = RunQueue::new(db_path, pass(), starter, Arc::new(|_| {})).unwrap();
+        enqueue(&queue.connection().unwrap(), &[request("a"), request("b")]).unwrap();
+
+        queue.pump().unwrap();
+        let status = queue.status().unwrap();
+        assert!(status.running.is_empty());
+        assert!(status.pending.is_empty());
+        assert!(status.error.is_none());
+    }
+
+    #[test]
+    fn test_queue_keeps_items_that_fail_to_start() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("protestify.db");
+        let starter: Starter = Arc::new(|item: &QueueItem, _| match item.feature_file.as_str() {
+            "a" => Err("npx was not found".to_string()),
+            _ => Ok(format!("run-{}", item.feature_file)),
+        });
+        let queue = RunQueue::new(db_path, pass(), starter, Arc::new(|_| {})).unwrap();
+        enqueue(&queue.connection().unwrap(), &[request("a"), request("b")]).unwrap();
+
+        queue.pump().unwrap();
+        let status = queue.status().unwrap();
+        assert!(status.paused);
+        assert!(status.running.is_empty());
+        assert_eq!(files(&status.pending), vec!["a", "b"]);
+        assert_eq!(
+            status.error.as_deref(),
+            Some("Failed to start a: npx was not found")
+        );
+
+        remove(&queue.connection().unwrap(), &status.pending[0].id).unwrap();
+        queue.set_paused(false).unwrap();
+        let status = queue.status().unwrap();
+        assert!(status.error.is_none());
+        assert_eq!(status.running[0].run_id, "run-b");
+        // Running items can't be removed, they leave the queue when they exit
+        assert!(remove(&queue.connection().unwrap(), &status.running[0].item.id).is_err());
+    }
+
+    #[test]
+    fn test_queue_drops_items_that_fail_their_check() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("protestify.db");
+        let check: Check = Arc::new(|item: &QueueItem| match item.feature_file.as_str() {
+            "a" => Err("Preflight failed".to_string()),
+            _ => Ok(()),
+        });
+        let starter: Starter =
+            Arc::new(|item: &QueueItem, _| Ok(format!("run-{}", item.feature_file)));
+        let queue = RunQueue::new(db_path, check, starter, Arc::new(|_| {})).unwrap();
+        enqueue(&queue.connection().unwrap(), &[request("a"), request("b")]).unwrap();
+
+        queue.pump().unwrap();
+        let status = queue.status().unwrap();
+        assert!(!status.paused);
+        assert!(status.error.is_none());
+        assert_eq!(status.running[0].run_id, "run-b");
+        assert!(status.pending.is_empty());
+        assert_eq!(status.failed.len(), 1);
+        assert_eq!(status.failed[0].item.feature_file, "a");
+        assert_eq!(status.failed[0].error, "Preflight failed");
+    }
+}
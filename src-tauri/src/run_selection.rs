@@ -0,0 +1,413 @@
+use crate::catalog_diff::FEATURES_DIR;
+use crate::cucumber_report_reader::ScenarioResult;
+use crate::gherkin_parser::{self, IdGenerator, Scenario, Tag};
+use crate::repo_source::RepoSource;
+use crate::tag_expression;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Part of a run. Files are relative to the e2e repo root (absolute paths inside the
+/// repo are accepted too), lines come from the parsed feature source.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Selection {
+    Feature {
+        file: String,
+    },
+    /// A scenario, or all rows of an outline, by the line of its keyword
+    Scenario {
+        file: String,
+        line: u32,
+    },
+    /// One row of an outline's examples table, by the row's line
+    ExampleRow {
+        file: String,
+        line: u32,
+    },
+    Tags {
+        expression: String,
+    },
+}
+
+/// Targets for a single cucumber invocation.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub targets: Vec<String>,
+    /// Feature files a tag expression could not look into
+    pub warnings: Vec<String>,
+}
+
+/// One test case cucumber would run: a scenario or a single example row.
+struct Pickle {
+    line: u32,
+    scenario_line: u32,
+    tags: Vec<String>,
+}
+
+struct FeatureSource {
+    scenarios: Vec<(u32, String)>,
+    pickles: Vec<Pickle>,
+}
+
+fn tag_names(tags: &[Tag]) -> impl Iterator<Item = String> + '_ {
+    tags.iter().map(|tag| tag.name.clone())
+}
+
+fn add_pickles(pickles: &mut Vec<Pickle>, scenario: &Scenario, inherited: &[String]) {
+    let mut tags = inherited.to_vec();
+    tags.extend(tag_names(&scenario.tags));
+    if scenario.examples.is_empty() {
+        pickles.push(Pickle {
+            line: scenario.location.line,
+            scenario_line: scenario.location.line,
+            tags,
+        });
+        return;
+    }
+    for examples in &scenario.examples {
+        for row in &examples.table_body {
+            pickles.push(Pickle {
+                line: row.location.line,
+                scenario_line: scenario.location.line,
+                tags: tags
+                    .iter()
+                    .cloned()
+                    .chain(tag_names(&examples.tags))
+                    .collect(),
+            });
+        }
+    }
+}
+
+fn parse_feature(repo_path: &str, file: &str) -> Result<FeatureSource, String> {
+    let content = RepoSource::working_dir(repo_path).read_to_string(file)?;
+    let document = gherkin_parser::parse(file, &content, &mut IdGenerator::new())
+        .map_err(|e| format!("{}: {}", file, e))?;
+
+    let mut source = FeatureSource {
+        scenarios: Vec::new(),
+        pickles: Vec::new(),
+    };
+    let Some(feature) = document.feature else {
+        return Ok(source);
+    };
+    let feature_tags: Vec<String> = tag_names(&feature.tags).collect();
+    for child in &feature.children {
+        if let Some(scenario) = &child.scenario {
+            add_pickles(&mut source.pickles, scenario, &feature_tags);
+        }
+        if let Some(rule) = &child.rule {
+            let rule_tags: Vec<String> = feature_tags
+                .iter()
+                .cloned()
+                .chain(tag_names(&rule.tags))
+                .collect();
+            for scenario in rule.children.iter().filter_map(|c| c.scenario.as_ref()) {
+                add_pickles(&mut source.pickles, scenario, &rule_tags);
+            }
+        }
+    }
+    source.scenarios = feature
+        .scenarios()
+        .iter()
+        .map(|s| (s.location.line, s.name.clone()))
+        .collect();
+    Ok(source)
+}
+
+fn relative_path(repo_path: &str, file: &str) -> String {
+    Path::new(file)
+        .strip_prefix(repo_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| file.to_string())
+        .replace('\\', "/")
+}
+
+/// Lines of the scenarios in `file` named exactly `name`.
+pub fn scenario_lines(repo_path: &str, file: &str, name: &str) -> Result<Vec<u32>, String> {
+    let source = parse_feature(repo_path, &relative_path(repo_path, file))?;
+    let lines: Vec<u32> = source
+        .scenarios
+        .iter()
+        .filter(|(_, scenario)| scenario == name)
+        .map(|(line, _)| *line)
+        .collect();
+    if lines.is_empty() {
+        return Err(format!("Scenario not found in {}: {}", file, name));
+    }
+    Ok(lines)
+}
+
+type Sources = BTreeMap<String, FeatureSource>;
+
+fn load<'a>(
+    sources: &'a mut Sources,
+    repo_path: &str,
+    file: &str,
+) -> Result<&'a FeatureSource, String> {
+    if !sources.contains_key(file) {
+        sources.insert(file.to_string(), parse_feature(repo_path, file)?);
+    }
+    Ok(&sources[file])
+}
+
+/// Point every result at the line of the scenario it ran, so example rows share their
+/// outline's line. Result URIs are relative to `repo_path`; files that can't be parsed
+/// anymore leave their results unlinked.
+pub fn link_results(repo_path: &str, results: &mut [ScenarioResult]) {
+    let mut sources = Sources::new();
+    for result in results.iter_mut() {
+        let Ok(source) = load(&mut sources, repo_path, &result.uri) else {
+            continue;
+        };
+        if let Some(pickle) = source.pickles.iter().find(|p| p.line == result.line) {
+            result.scenario_line = Some(pickle.scenario_line);
+        }
+    }
+}
+
+/// Add a line target unless the whole file is already selected
+fn add_line(files: &mut BTreeMap<String, Option<BTreeSet<u32>>>, file: String, line: u32) {
+    if let Some(lines) = files.entry(file).or_insert(Some(BTreeSet::new())) {
+        lines.insert(line);
+    }
+}
+
+/// Turn a mixed selection into `file` and `file:line:line` targets for a single
+/// cucumber invocation. Tag expressions are resolved here rather than passed as
+/// `--tags`, which cucumber would apply to the explicit targets as well. Files that fail
+/// to parse are skipped for tag expressions, with a warning.
+pub fn resolve_targets(repo_path: &str, selections: &[Selection]) -> Result<Resolved, String> {
+    // `None` runs the whole file
+    let mut files: BTreeMap<String, Option<BTreeSet<u32>>> = BTreeMap::new();
+    let mut sources = Sources::new();
+    let mut warnings = Vec::new();
+
+    for selection in selections {
+        match selection {
+            Selection::Feature { file } => {
+                let file = relative_path(repo_path, file);
+                load(&mut sources, repo_path, &file)?;
+                files.insert(file, None);
+            }
+            Selection::Scenario { file, line } => {
+                let file = relative_path(repo_path, file);
+                if !load(&mut sources, repo_path, &file)?
+                    .scenarios
+                    .iter()
+                    .any(|(l, _)| l == line)
+                {
+                    return Err(format!("{}:{} is not a scenario", file, line));
+                }
+                add_line(&mut files, file, *line);
+            }
+            Selection::ExampleRow { file, line } => {
+                let file = relative_path(repo_path, file);
+                let is_row = load(&mut sources, repo_path, &file)?
+                    .pickles
+                    .iter()
+                    .any(|p| p.line == *line && p.line != p.scenario_line);
+                if !is_row {
+                    return Err(format!("{}:{} is not an example row", file, line));
+                }
+                add_line(&mut files, file, *line);
+            }
+            Selection::Tags { expression } => {
+                let expression = tag_expression::parse(expression)?;
+                let features = RepoSource::working_dir(repo_path).subdir(FEATURES_DIR);
+                for relative in features.list_files("", &["feature"])? {
+                    let file = format!("{}/{}", FEATURES_DIR, relative);
+                    let source = match load(&mut sources, repo_path, &file) {
+                        Ok(source) => source,
+                        Err(e) => {
+                            warnings.push(format!("Skipped {}", e));
+                            continue;
+                        }
+                    };
+                    let matching: Vec<u32> = source
+                        .pickles
+                        .iter()
+                        .filter(|p| expression.evaluate(&p.tags))
+                        .map(|p| p.line)
+                        .collect();
+                    for line in matching {
+                        add_line(&mut files, file.clone(), line);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut targets = Vec::new();
+    for (file, lines) in files {
+        let Some(lines) = lines else {
+            targets.push(file);
+            continue;
+        };
+        let source = &sources[&file];
+        // Rows whose outline is selected anyway, and outlines with every row selected
+        let mut compact: BTreeSet<u32> = BTreeSet::new();
+        for (scenario_line, _) in &source.scenarios {
+            let rows: Vec<u32> = source
+                .pickles
+                .iter()
+                .filter(|p| p.scenario_line == *scenario_line && p.line != p.scenario_line)
+                .map(|p| p.line)
+                .collect();
+            let all_rows = !rows.is_empty() && rows.iter().all(|row| lines.contains(row));
+            if lines.contains(scenario_line) || all_rows {
+                compact.insert(*scenario_line);
+            } else {
+                compact.extend(rows.into_iter().filter(|row| lines.contains(row)));
+            }
+        }
+        if compact.is_empty() {
+            continue;
+        }
+        let lines: Vec<String> = compact.iter().map(u32::to_string).collect();
+        targets.push(format!("{}:{}", file, lines.join(":")));
+    }
+    Ok(Resolved { targets, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cucumber_report_reader;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const FEATURE: &str = r#"@billing
+Feature: Invoices
+
+  Scenario: Create invoice
+    Given an invoice
+
+  @smoke
+  Scenario: Create invoice draft
+    Given a draft
+
+  Scenario Outline: Totals
+    Given <amount>
+
+    Examples:
+      | amount |
+      | 1      |
+
+    @smoke
+    Examples:
+      | amount |
+      | 2      |
+      | 3      |
+"#;
+
+    fn repo() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("features")).unwrap();
+        fs::write(dir.path().join("features/invoices.feature"), FEATURE).unwrap();
+        fs::write(
+            dir.path().join("features/other.feature"),
+            "Feature: Other\n  Scenario: Create invoice\n    Given x\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn scenario(file: &str, line: u32) -> Selection {
+        Selection::Scenario {
+            file: file.into(),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_scenario_lines_match_exact_names() {
+        let dir = repo();
+        let repo_path = dir.path().to_str().unwrap();
+
+        let file = dir.path().join("features/invoices.feature");
+        assert_eq!(
+            scenario_lines(repo_path, file.to_str().unwrap(), "Create invoice").unwrap(),
+            vec![4]
+        );
+        assert!(scenario_lines(repo_path, "features/invoices.feature", "Create").is_err());
+    }
+
+    #[test]
+    fn test_resolve_targets() {
+        let dir = repo();
+        let repo_path = dir.path().to_str().unwrap();
+        let row = |line| Selection::ExampleRow {
+            file: "features/invoices.feature".into(),
+            line,
+        };
+
+        let targets = resolve_targets(
+            repo_path,
+            &[
+                scenario("features/invoices.feature", 4),
+                row(16),
+                Selection::Tags {
+                    expression: "@smoke".into(),
+                },
+            ],
+        )
+        .unwrap()
+        .targets;
+        // Both smoke rows plus the explicit one make up the whole outline
+        assert_eq!(targets, vec!["features/invoices.feature:4:8:11"]);
+
+        let targets = resolve_targets(
+            repo_path,
+            &[
+                row(16),
+                Selection::Feature {
+                    file: "features/other.feature".into(),
+                },
+            ],
+        )
+        .unwrap()
+        .targets;
+        assert_eq!(
+            targets,
+            vec!["features/invoices.feature:16", "features/other.feature"]
+        );
+
+        // A file that doesn't parse doesn't stop a tag expression
+        fs::write(
+            dir.path().join("features/broken.feature"),
+            "# language: fr\nFonctionnalité: Cassé\n",
+        )
+        .unwrap();
+        let resolved = resolve_targets(
+            repo_path,
+            &[Selection::Tags {
+                expression: "@billing and not @smoke".into(),
+            }],
+        )
+        .unwrap();
+        assert_eq!(resolved.targets, vec!["features/invoices.feature:4:16"]);
+        assert_eq!(resolved.warnings.len(), 1);
+        assert!(resolved.warnings[0].starts_with("Skipped features/broken.feature: "));
+
+        assert!(resolve_targets(repo_path, &[scenario("features/invoices.feature", 5)]).is_err());
+        assert!(resolve_targets(repo_path, &[row(15)]).is_err());
+    }
+
+    #[test]
+    fn test_link_results() {
+        let dir = repo();
+        let mut results = cucumber_report_reader::parse_results(
+            r#"[{"uri": "features/invoices.feature", "elements": [
+                {"line": 4, "steps": []}, {"line": 21, "steps": []}, {"line": 40, "steps": []}
+            ]}, {"uri": "features/missing.feature", "elements": [{"line": 2, "steps": []}]}]"#,
+        )
+        .unwrap();
+
+        link_results(dir.path().to_str().unwrap(), &mut results);
+
+        let lines: Vec<Option<u32>> = results.iter().map(|r| r.scenario_line).collect();
+        assert_eq!(lines, vec![Some(4), Some(11), None, None]);
+    }
+}
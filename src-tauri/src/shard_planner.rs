@@ -0,0 +1,306 @@
+use crate::catalog_diff::Catalog;
+use crate::cucumber_report_reader::ScenarioDuration;
+use crate::features_reader::Scenario;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Finished runs from history whose durations are averaged into the plan
+pub const HISTORY_RUNS: usize = 20;
+
+// Step-count heuristic for scenarios that never ran
+const SCENARIO_OVERHEAD_SECONDS: f64 = 5.0;
+const SECONDS_PER_STEP: f64 = 3.0;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Scenario,
+    File,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub expected_seconds: f64,
+    pub scenarios: usize,
+    /// `file` or `file:line` arguments for cucumber-js, relative to the repo root
+    pub targets: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ShardPlan {
+    pub shards: Vec<Shard>,
+    pub total_seconds: f64,
+    /// Scenarios timed from reports or run history
+    pub measured: usize,
+    /// Scenarios estimated from their step count
+    pub estimated: usize,
+}
+
+/// A unit that must land on one shard: scenarios (or files) sharing an organization
+/// cannot run on different workers at the same time.
+struct Cluster {
+    seconds: f64,
+    scenarios: usize,
+    targets: Vec<(String, Option<u32>)>,
+}
+
+pub fn estimate_seconds(scenario: &Scenario) -> f64 {
+    let runs = scenario.examples.max(1) as f64;
+    (SCENARIO_OVERHEAD_SECONDS + scenario.steps as f64 * SECONDS_PER_STEP) * runs
+}
+
+fn find(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+/// Assign scenarios to `shard_count` shards so that expected durations are balanced,
+/// longest cluster first onto the least loaded shard.
+pub fn plan_shards(
+    catalog: &Catalog,
+    shard_count: usize,
+    granularity: Granularity,
+    durations: &[ScenarioDuration],
+) -> Result<ShardPlan, String> {
+    if shard_count == 0 {
+        return Err("At least one shard is required".to_string());
+    }
+
+    // Average each reported scenario or example row over all reports
+    let mut reported: HashMap<&str, BTreeMap<u32, (f64, u32)>> = HashMap::new();
+    for duration in durations {
+        let entry = reported
+            .entry(duration.uri.as_str())
+            .or_default()
+            .entry(duration.line)
+            .or_insert((0.0, 0));
+        entry.0 += duration.seconds;
+        entry.1 += 1;
+    }
+
+    let files: HashMap<&str, String> = catalog
+        .features
+        .iter()
+        .map(|f| (f.id.as_str(), catalog.relative(&f.file_path)))
+        .collect();
+
+    // One unit per scenario or per file, before merging by organization
+    let mut units: Vec<Cluster> = Vec::new();
+    let mut unit_index: HashMap<(String, Option<u32>), usize> = HashMap::new();
+    let mut unit_organizations: Vec<Vec<&str>> = Vec::new();
+    let (mut measured, mut estimated) = (0, 0);
+
+    for scenario in &catalog.scenarios {
+        let Some(file) = files.get(scenario.feature_id.as_str()) else {
+            continue;
+        };
+
+        // Outline rows are reported on their own lines, within the scenario
+        let timed: Vec<f64> = reported
+            .get(file.as_str())
+            .map(|lines| {
+                lines
+                    .range(scenario.line..=scenario.end_line.max(scenario.line))
+                    .map(|(_, (sum, count))| sum / *count as f64)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let seconds = if timed.is_empty() {
+            estimated += 1;
+            estimate_seconds(scenario)
+        } else {
+            measured += 1;
+            timed.iter().sum()
+        };
+
+        let key = match granularity {
+            Granularity::Scenario => (file.clone(), Some(scenario.line)),
+            Granularity::File => (file.clone(), None),
+        };
+        let index = *unit_index.entry(key.clone()).or_insert_with(|| {
+            units.push(Cluster {
+                seconds: 0.0,
+                scenarios: 0,
+                targets: vec![key],
+            });
+            unit_organizations.push(Vec::new());
+            units.len() - 1
+        });
+        units[index].seconds += seconds;
+        units[index].scenarios += 1;
+        if !scenario.organization_name.is_empty() {
+            unit_organizations[index].push(&scenario.organization_name);
+        }
+    }
+
+    // Merge units sharing an organization
+    let mut parents: Vec<usize> = (0..units.len()).collect();
+    let mut first_with_organization: HashMap<&str, usize> = HashMap::new();
+    for (index, organizations) in unit_organizations.iter().enumerate() {
+        for organization in organizations {
+            let first = *first_with_organization.entry(organization).or_insert(index);
+            let (a, b) = (find(&mut parents, first), find(&mut parents, index));
+            if a != b {
+                parents[b] = a;
+            }
+        }
+    }
+    let mut clusters: BTreeMap<usize, Cluster> = BTreeMap::new();
+    for (index, unit) in units.into_iter().enumerate() {
+        let root = find(&mut parents, index);
+        match clusters.get_mut(&root) {
+            Some(cluster) => {
+                cluster.seconds += unit.seconds;
+                cluster.scenarios += unit.scenarios;
+                cluster.targets.extend(unit.targets);
+            }
+            None => {
+                clusters.insert(root, unit);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Cluster> = clusters.into_values().collect();
+    clusters.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+
+    let mut shards: Vec<Shard> = (0..shard_count)
+        .map(|index| Shard {
+            index,
+            expected_seconds: 0.0,
+            scenarios: 0,
+            targets: Vec::new(),
+        })
+        .collect();
+    let mut shard_targets: Vec<Vec<(String, Option<u32>)>> = vec![Vec::new(); shard_count];
+    for cluster in clusters {
+        let shard = shards
+            .iter_mut()
+            .min_by(|a, b| a.expected_seconds.total_cmp(&b.expected_seconds))
+            .expect("at least one shard");
+        shard.expected_seconds += cluster.seconds;
+        shard.scenarios += cluster.scenarios;
+        shard_targets[shard.index].extend(cluster.targets);
+    }
+
+    for (shard, mut targets) in shards.iter_mut().zip(shard_targets) {
+        targets.sort();
+        shard.targets = targets
+            .into_iter()
+            .map(|(file, line)| match line {
+                Some(line) => format!("{}:{}", file, line),
+                None => file,
+            })
+            .collect();
+    }
+
+    Ok(ShardPlan {
+        total_seconds: shards.iter().map(|s| s.expected_seconds).sum(),
+        shards,
+        measured,
+        estimated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog_diff::load_catalog;
+    use crate::repo_source::RepoSource;
+    use std::fs;
+    use tempfile::tempdir;
+
+    async fn catalog() -> Catalog {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("features")).unwrap();
+        fs::write(
+            dir.path().join("features/a.feature"),
+            r#"Feature: A
+  Scenario: Slow
+    Given current organization is "Org A"
+
+  Scenario: Same org
+    Given current organization is "Org A"
+
+  Scenario Outline: Rows
+    Given a <x>
+
+    Examples:
+      | x |
+      | 1 |
+      | 2 |
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("features/b.feature"),
+            "Feature: B\n  Scenario: Other\n    Given current organization is \"Org B\"\n",
+        )
+        .unwrap();
+        load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap()
+    }
+
+    fn duration(uri: &str, line: u32, seconds: f64) -> ScenarioDuration {
+        ScenarioDuration {
+            uri: uri.into(),
+            line,
+            seconds,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_shards_by_scenario() {
+        let catalog = catalog().await;
+        let durations = [
+            duration("features/a.feature", 2, 100.0),
+            duration("features/a.feature", 2, 60.0),
+            duration("features/a.feature", 5, 10.0),
+            duration("features/a.feature", 13, 30.0),
+            duration("features/a.feature", 14, 30.0),
+            duration("features/b.feature", 2, 70.0),
+        ];
+
+        let plan = plan_shards(&catalog, 2, Granularity::Scenario, &durations).unwrap();
+
+        assert_eq!((plan.measured, plan.estimated), (4, 0));
+        assert_eq!(plan.total_seconds, 220.0);
+        // Both Org A scenarios stay together on the first shard
+        assert_eq!(
+            plan.shards[0].targets,
+            vec!["features/a.feature:2", "features/a.feature:5"]
+        );
+        assert_eq!(plan.shards[0].expected_seconds, 90.0);
+        assert_eq!(
+            plan.shards[1].targets,
+            vec!["features/a.feature:8", "features/b.feature:2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_shards_by_file_with_estimates() {
+        let catalog = catalog().await;
+
+        let plan = plan_shards(&catalog, 3, Granularity::File, &[]).unwrap();
+
+        assert_eq!(plan.estimated, 4);
+        let targets: Vec<&Vec<String>> = plan.shards.iter().map(|s| &s.targets).collect();
+        assert_eq!(
+            targets,
+            vec![
+                &vec!["features/a.feature".to_string()],
+                &vec!["features/b.feature".to_string()],
+                &vec![],
+            ]
+        );
+        // 2 × 8s for the org scenarios and 2 rows × 8s for the outline
+        assert_eq!(plan.shards[0].expected_seconds, 32.0);
+
+        assert!(plan_shards(&catalog, 0, Granularity::File, &[]).is_err());
+    }
+}
@@ -1,14 +1,16 @@
+use crate::repo_source::RepoSource;
+use lazy_static::lazy_static;
 use regex::Regex;
 use serde::Serialize;
-use std::fs;
-use std::path::Path;
-use walkdir::WalkDir;
+
+pub const STEP_DEFINITIONS_DIR: &str = "step-definitions";
 
 #[derive(Debug, Serialize, Clone)]
 pub struct StepDefinition {
     pub id: String,
     pub keyword: String,
     pub pattern: String,
+    pub is_regex: bool,
     pub file_path: String,
     pub line_number: usize,
     pub category: String,
@@ -16,69 +18,151 @@ pub struct StepDefinition {
     pub problem_reason: Option<String>,
 }
 
+lazy_static! {
+    // Match both regex patterns /.../ and string patterns '...' or "..."
+    // Also handle optional generic type like <ScenarioContext>
+    static ref STEP_RE: Regex =
+        Regex::new(r#"(Given|When|Then|And|But)(?:<[^>]*>)?\s*\(\s*(?:/(.+?)/|['"](.+?)['"])"#).unwrap();
+}
+
 /// Parse all step definition files in the given directory
 pub fn parse_step_definitions(base_path: &str) -> Result<Vec<StepDefinition>, String> {
-    let step_defs_path = Path::new(base_path).join("step-definitions");
+    parse_step_definitions_from(&RepoSource::working_dir(base_path))
+}
 
-    if !step_defs_path.exists() {
+pub fn parse_step_definitions_from(source: &RepoSource) -> Result<Vec<StepDefinition>, String> {
+    if !source.dir_exists(STEP_DEFINITIONS_DIR) {
         return Err(format!(
             "step_definitions folder not found at: {}",
-            step_defs_path.display()
+            source.display_path(STEP_DEFINITIONS_DIR)
         ));
     }
 
     let mut steps = Vec::new();
-    // Match both regex patterns /.../ and string patterns '...' or "..."
-    // Also handle optional generic type like <ScenarioContext>
-    let step_regex = Regex::new(r#"(Given|When|Then|And|But)(?:<[^>]*>)?\s*\(\s*(?:/(.+?)/|['"](.+?)['"])"#).unwrap();
 
-    for entry in WalkDir::new(&step_defs_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-
-        // Only process TypeScript files
-        if path.extension().map_or(false, |ext| ext == "ts" || ext == "js") {
-            if let Ok(content) = fs::read_to_string(path) {
-                let file_path = path.to_string_lossy().to_string();
-
-                for (line_number, line) in content.lines().enumerate() {
-                    if let Some(captures) = step_regex.captures(line) {
-                        let keyword = captures.get(1).unwrap().as_str().to_string();
-                        // Pattern is either in group 2 (regex /.../) or group 3 (string '...' or "...")
-                        let pattern = captures
-                            .get(2)
-                            .or_else(|| captures.get(3))
-                            .map(|m| m.as_str().to_string())
-                            .unwrap_or_default();
-
-                        if pattern.is_empty() {
-                            continue;
-                        }
-
-                        let category = categorize_step(&pattern, &keyword);
-                        let (is_problematic, problem_reason) = check_problematic(&pattern);
-
-                        let id = format!("{}:{}", file_path, line_number + 1);
-
-                        steps.push(StepDefinition {
-                            id,
-                            keyword,
-                            pattern,
-                            file_path: file_path.clone(),
-                            line_number: line_number + 1,
-                            category,
-                            is_problematic,
-                            problem_reason,
-                        });
-                    }
+    // Only process TypeScript and JavaScript files
+    let relatives = source.list_files(STEP_DEFINITIONS_DIR, &["ts", "js"])?;
+    for (relative, content) in relatives.iter().zip(source.read_files(&relatives)?) {
+        steps.extend(parse_step_file(&content, &source.display_path(relative)));
+    }
+
+    Ok(steps)
+}
+
+/// Parse step definitions out of the contents of a single step definition file
+pub fn parse_step_file(content: &str, file_path: &str) -> Vec<StepDefinition> {
+    let mut steps = Vec::new();
+
+    for (line_number, line) in content.lines().enumerate() {
+        if let Some(captures) = STEP_RE.captures(line) {
+            let keyword = captures.get(1).unwrap().as_str().to_string();
+            // Pattern is either in group 2 (regex /.../) or group 3 (string '...' or "...")
+            let is_regex = captures.get(2).is_some();
+            let pattern = captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_default();
+
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let category = categorize_step(&pattern, &keyword);
+            let (is_problematic, problem_reason) = check_problematic(&pattern);
+
+            let id = format!("{}:{}", file_path, line_number + 1);
+
+            steps.push(StepDefinition {
+                id,
+                keyword,
+                pattern,
+                is_regex,
+                file_path: file_path.to_string(),
+                line_number: line_number + 1,
+                category,
+                is_problematic,
+                problem_reason,
+            });
+        }
+    }
+
+    steps
+}
+
+/// Build a regex that matches step text for this definition. Regex patterns are used
+/// as they are, cucumber expressions are translated.
+pub fn step_regex(step: &StepDefinition) -> Option<Regex> {
+    if step.is_regex {
+        return Regex::new(&step.pattern).ok();
+    }
+
+    Regex::new(&format!("^{}$", cucumber_expression_to_regex(&step.pattern))).ok()
+}
+
+fn cucumber_expression_to_regex(expression: &str) -> String {
+    let mut regex = String::new();
+    let chars: Vec<char> = expression.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '{' => {
+                let end = chars[i..].iter().position(|&c| c == '}').map(|p| p + i);
+                let Some(end) = end else {
+                    regex.push_str(&regex::escape("{"));
+                    i += 1;
+                    continue;
+                };
+                let name: String = chars[i + 1..end].iter().collect();
+                regex.push_str(match name.as_str() {
+                    "int" => r"(-?\d+)",
+                    "float" => r"(-?\d*\.?\d+)",
+                    "word" => r"([^\s]+)",
+                    "string" => r#"("[^"]*"|'[^']*')"#,
+                    _ => "(.*)",
+                });
+                i = end + 1;
+            }
+            '(' => {
+                let end = chars[i..].iter().position(|&c| c == ')').map(|p| p + i);
+                let Some(end) = end else {
+                    regex.push_str(&regex::escape("("));
+                    i += 1;
+                    continue;
+                };
+                let optional: String = chars[i + 1..end].iter().collect();
+                regex.push_str(&format!("(?:{})?", regex::escape(&optional)));
+                i = end + 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            _ => {
+                // Alternation binds words separated by "/", e.g. "a/an item"
+                let start = i;
+                while i < chars.len() && !matches!(chars[i], '{' | '(' | '\\' | ' ') {
+                    i += 1;
+                }
+                if start == i {
+                    regex.push_str(&regex::escape(&chars[i].to_string()));
+                    i += 1;
+                    continue;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if word.contains('/') {
+                    let alternatives: Vec<String> =
+                        word.split('/').map(regex::escape).collect();
+                    regex.push_str(&format!("(?:{})", alternatives.join("|")));
+                } else {
+                    regex.push_str(&regex::escape(&word));
                 }
             }
         }
     }
 
-    Ok(steps)
+    regex
 }
 
 fn categorize_step(pattern: &str, keyword: &str) -> String {
@@ -209,6 +293,39 @@ mod tests {
         assert_eq!(categorize_step("type in the input", "When"), "Actions");
     }
 
+    #[test]
+    fn test_parse_step_file() {
+        let content = r#"
+Given('user is on a {string} screen', async function (screen) {});
+When<ScenarioContext>(/^I wait (\d+) ms$/, async function (ms) {});
+"#;
+        let steps = parse_step_file(content, "step-definitions/common.ts");
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].pattern, "user is on a {string} screen");
+        assert!(!steps[0].is_regex);
+        assert_eq!(steps[0].line_number, 2);
+        assert_eq!(steps[1].keyword, "When");
+        assert!(steps[1].is_regex);
+        assert_eq!(steps[1].id, "step-definitions/common.ts:3");
+    }
+
+    #[test]
+    fn test_step_regex() {
+        let steps = parse_step_file(
+            "Given('{user} has {int} item(s) in a/the cart', () => {});\nThen(/^I see (.+)$/, () => {});",
+            "steps.ts",
+        );
+
+        let expression = step_regex(&steps[0]).unwrap();
+        assert!(expression.is_match("Admin has 1 item in a cart"));
+        assert!(expression.is_match("Admin has 12 items in the cart"));
+        assert!(!expression.is_match("Admin has many items in the cart"));
+
+        let regex = step_regex(&steps[1]).unwrap();
+        assert!(regex.is_match("I see invoices"));
+    }
+
     #[test]
     fn test_check_problematic() {
         let (prob, _) = check_problematic("wait for 10000 milliseconds");
@@ -0,0 +1,309 @@
+use crate::catalog_diff::Catalog;
+use crate::tag_expression;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Scenario UUIDs change on every load, so suites pin scenarios by file and name.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScenarioKey {
+    /// Feature file path relative to the e2e repo root
+    pub file: String,
+    pub name: String,
+}
+
+/// A named selection of scenarios. Explicit scenarios are always included, filters
+/// add every scenario matching all of the filters that are set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Suite {
+    pub name: String,
+    pub description: String,
+    pub scenarios: Vec<ScenarioKey>,
+    pub tag_expression: Option<String>,
+    pub teams: Vec<String>,
+    pub owners: Vec<String>,
+    pub organizations: Vec<String>,
+    pub screens: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ResolvedScenario {
+    pub file_path: String,
+    pub line: u32,
+    pub name: String,
+    pub feature_name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResolvedSuite {
+    pub name: String,
+    pub scenarios: Vec<ResolvedScenario>,
+    /// Explicit scenarios that no longer exist in the catalog
+    pub missing: Vec<ScenarioKey>,
+}
+
+impl Suite {
+    fn has_filters(&self) -> bool {
+        self.tag_expression
+            .as_deref()
+            .is_some_and(|e| !e.trim().is_empty())
+            || !self.teams.is_empty()
+            || !self.owners.is_empty()
+            || !self.organizations.is_empty()
+            || !self.screens.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Suite name is required".to_string());
+        }
+        if let Some(expression) = &self.tag_expression {
+            tag_expression::parse(expression)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn save_suite(connection: &Connection, repo_path: &str, suite: &Suite) -> Result<(), String> {
+    suite.validate()?;
+    let definition = serde_json::to_string(suite).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO suites (repo_path, name, definition, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (repo_path, name) DO UPDATE
+             SET definition = excluded.definition, updated_at = excluded.updated_at",
+            params![repo_path, suite.name, definition, Utc::now().timestamp()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn delete_suite(connection: &Connection, repo_path: &str, name: &str) -> Result<(), String> {
+    let deleted = connection
+        .execute(
+            "DELETE FROM suites WHERE repo_path = ?1 AND name = ?2",
+            params![repo_path, name],
+        )
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err(format!("Suite not found: {}", name));
+    }
+    Ok(())
+}
+
+pub fn get_suite(connection: &Connection, repo_path: &str, name: &str) -> Result<Suite, String> {
+    let definition: Option<String> = connection
+        .query_row(
+            "SELECT definition FROM suites WHERE repo_path = ?1 AND name = ?2",
+            params![repo_path, name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let definition = definition.ok_or_else(|| format!("Suite not found: {}", name))?;
+    serde_json::from_str(&definition).map_err(|e| e.to_string())
+}
+
+pub fn list_suites(connection: &Connection, repo_path: &str) -> Result<Vec<Suite>, String> {
+    let mut statement = connection
+        .prepare("SELECT definition FROM suites WHERE repo_path = ?1 ORDER BY name")
+        .map_err(|e| e.to_string())?;
+    let definitions = statement
+        .query_map(params![repo_path], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    definitions
+        .iter()
+        .map(|definition| serde_json::from_str(definition).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Resolve a suite against the current catalog, in catalog order.
+pub fn resolve_suite(catalog: &Catalog, suite: &Suite) -> Result<ResolvedSuite, String> {
+    let expression = tag_expression::parse(suite.tag_expression.as_deref().unwrap_or_default())?;
+    let has_filters = suite.has_filters();
+    let explicit: HashSet<&ScenarioKey> = suite.scenarios.iter().collect();
+    let features: HashMap<&str, _> = catalog
+        .features
+        .iter()
+        .map(|f| (f.id.as_str(), f))
+        .collect();
+    let organizations: HashMap<&str, _> = catalog
+        .organizations
+        .iter()
+        .map(|o| (o.name.as_str(), o))
+        .collect();
+
+    let mut found = HashSet::new();
+    let mut scenarios = Vec::new();
+    for scenario in &catalog.scenarios {
+        let Some(feature) = features.get(scenario.feature_id.as_str()) else {
+            continue;
+        };
+        let key = ScenarioKey {
+            file: catalog.relative(&feature.file_path),
+            name: scenario.name.clone(),
+        };
+
+        let matches_filters = has_filters && {
+            let tags: Vec<String> = feature.tags.iter().chain(&scenario.tags).cloned().collect();
+            let organization = organizations.get(scenario.organization_name.as_str());
+            let in_list = |list: &[String], value: Option<&str>| {
+                list.is_empty() || value.is_some_and(|v| list.iter().any(|item| item == v))
+            };
+
+            expression.evaluate(&tags)
+                && in_list(&suite.teams, organization.map(|o| o.team.as_str()))
+                && in_list(&suite.owners, organization.map(|o| o.blame.as_str()))
+                && in_list(
+                    &suite.organizations,
+                    Some(scenario.organization_name.as_str()),
+                )
+                && (suite.screens.is_empty()
+                    || scenario.targets.iter().any(|target| {
+                        let screen = target.split(';').next().unwrap_or_default();
+                        suite.screens.iter().any(|s| s.eq_ignore_ascii_case(screen))
+                    }))
+        };
+
+        let is_explicit = explicit.contains(&key);
+        if is_explicit || matches_filters {
+            scenarios.push(ResolvedScenario {
+                file_path: feature.file_path.clone(),
+                line: scenario.line,
+                name: scenario.name.clone(),
+                feature_name: feature.name.clone(),
+            });
+        }
+        if is_explicit {
+            found.insert(key);
+        }
+    }
+
+    Ok(ResolvedSuite {
+        name: suite.name.clone(),
+        scenarios,
+        missing: suite
+            .scenarios
+            .iter()
+            .filter(|key| !found.contains(key))
+            .cloned()
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog_diff::load_catalog;
+    use crate::database::open_in_memory;
+    use crate::repo_source::RepoSource;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_list_suites() {
+        let connection = open_in_memory();
+        let mut suite = Suite {
+            name: "Release".into(),
+            tag_expression: Some("@smoke".into()),
+            ..Default::default()
+        };
+        save_suite(&connection, "/repo", &suite).unwrap();
+        suite.teams = vec!["core".into()];
+        save_suite(&connection, "/repo", &suite).unwrap();
+
+        let suites = list_suites(&connection, "/repo").unwrap();
+        assert_eq!(suites.len(), 1);
+        assert_eq!(suites[0].teams, vec!["core"]);
+        assert!(list_suites(&connection, "/other").unwrap().is_empty());
+
+        suite.tag_expression = Some("@smoke and".into());
+        assert!(save_suite(&connection, "/repo", &suite).is_err());
+
+        delete_suite(&connection, "/repo", "Release").unwrap();
+        assert!(get_suite(&connection, "/repo", "Release").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_suite() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("features")).unwrap();
+        fs::create_dir_all(dir.path().join("data")).unwrap();
+        fs::write(
+            dir.path().join("features/a.feature"),
+            r#"@billing
+Feature: Invoices
+  @smoke
+  Scenario: Create
+    Given current organization is "Org A"
+    Given user is on a "invoices" screen
+
+  @smoke @broken
+  Scenario: Delete
+    Given current organization is "Org B"
+
+  Scenario: Export
+    Given current organization is "Org B"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("data/organizations.yml"),
+            "Org A:\n  id: \"1\"\n  blame: ana\n  team: core\n  users: {}\nOrg B:\n  id: \"2\"\n  blame: ivo\n  team: money\n  users: {}\n",
+        )
+        .unwrap();
+        let catalog = load_catalog(&RepoSource::working_dir(dir.path().to_str().unwrap()))
+            .await
+            .unwrap();
+        let names = |suite: &Suite| -> Vec<String> {
+            resolve_suite(&catalog, suite)
+                .unwrap()
+                .scenarios
+                .into_iter()
+                .map(|s| s.name)
+                .collect()
+        };
+
+        let smoke = Suite {
+            name: "Smoke".into(),
+            tag_expression: Some("@billing and @smoke and not @broken".into()),
+            ..Default::default()
+        };
+        assert_eq!(names(&smoke), vec!["Create"]);
+
+        let money = Suite {
+            name: "Money".into(),
+            teams: vec!["money".into()],
+            scenarios: vec![ScenarioKey {
+                file: "features/a.feature".into(),
+                name: "Create".into(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(names(&money), vec!["Create", "Delete", "Export"]);
+
+        let screens = Suite {
+            name: "Screens".into(),
+            screens: vec!["Invoices".into()],
+            ..Default::default()
+        };
+        assert_eq!(names(&screens), vec!["Create"]);
+
+        let pinned = Suite {
+            name: "Pinned".into(),
+            scenarios: vec![ScenarioKey {
+                file: "features/a.feature".into(),
+                name: "Gone".into(),
+            }],
+            ..Default::default()
+        };
+        let resolved = resolve_suite(&catalog, &pinned).unwrap();
+        assert!(resolved.scenarios.is_empty());
+        assert_eq!(resolved.missing, pinned.scenarios);
+    }
+}
@@ -0,0 +1,206 @@
+/// Cucumber tag expression, e.g. `@smoke and not (@broken or @slow)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagExpression {
+    /// An empty expression matches everything, like cucumber-js without `--tags`
+    Any,
+    Tag(String),
+    Not(Box<TagExpression>),
+    And(Box<TagExpression>, Box<TagExpression>),
+    Or(Box<TagExpression>, Box<TagExpression>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+    /// A word with escaped characters is always a tag, never an operator
+    Escaped(String),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut escaped = false;
+    let mut chars = expression.chars();
+
+    let flush = |word: &mut String, escaped: &mut bool, tokens: &mut Vec<Token>| {
+        if !word.is_empty() {
+            let text = std::mem::take(word);
+            tokens.push(if *escaped {
+                Token::Escaped(text)
+            } else {
+                Token::Word(text)
+            });
+        }
+        *escaped = false;
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next)
+                    if next == '(' || next == ')' || next == '\\' || next.is_whitespace() =>
+                {
+                    word.push(next);
+                    escaped = true;
+                }
+                Some(next) => {
+                    return Err(format!(
+                        "Tag expression \"{}\" has an illegal escape before \"{}\"",
+                        expression, next
+                    ))
+                }
+                None => {
+                    return Err(format!(
+                        "Tag expression \"{}\" ends with an escape",
+                        expression
+                    ))
+                }
+            },
+            '(' | ')' => {
+                flush(&mut word, &mut escaped, &mut tokens);
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut escaped, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut escaped, &mut tokens);
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek_operator(&self, operator: &str) -> bool {
+        matches!(self.tokens.get(self.position), Some(Token::Word(word)) if word == operator)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Tag expression \"{}\" could not be parsed: {}",
+            self.expression, message
+        )
+    }
+
+    fn parse_or(&mut self) -> Result<TagExpression, String> {
+        let mut left = self.parse_and()?;
+        while self.peek_operator("or") {
+            self.position += 1;
+            left = TagExpression::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<TagExpression, String> {
+        let mut left = self.parse_not()?;
+        while self.peek_operator("and") {
+            self.position += 1;
+            left = TagExpression::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<TagExpression, String> {
+        if self.peek_operator("not") {
+            self.position += 1;
+            return Ok(TagExpression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_operand()
+    }
+
+    fn parse_operand(&mut self) -> Result<TagExpression, String> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        match token {
+            Some(Token::Open) => {
+                let inner = self.parse_or()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(inner)
+                    }
+                    _ => Err(self.error("missing )")),
+                }
+            }
+            Some(Token::Word(word)) if ["and", "or", "not"].contains(&word.as_str()) => {
+                Err(self.error(&format!("expected a tag, found \"{}\"", word)))
+            }
+            Some(Token::Word(tag)) | Some(Token::Escaped(tag)) => {
+                Ok(TagExpression::Tag(tag.clone()))
+            }
+            Some(Token::Close) => Err(self.error("unexpected )")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+}
+
+pub fn parse(expression: &str) -> Result<TagExpression, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(TagExpression::Any);
+    }
+
+    let mut parser = Parser {
+        expression,
+        tokens,
+        position: 0,
+    };
+    let parsed = parser.parse_or()?;
+    if parser.position < parser.tokens.len() {
+        return Err(parser.error("unexpected trailing tokens"));
+    }
+    Ok(parsed)
+}
+
+impl TagExpression {
+    pub fn evaluate(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpression::Any => true,
+            TagExpression::Tag(tag) => tags.iter().any(|t| t == tag),
+            TagExpression::Not(inner) => !inner.evaluate(tags),
+            TagExpression::And(left, right) => left.evaluate(tags) && right.evaluate(tags),
+            TagExpression::Or(left, right) => left.evaluate(tags) || right.evaluate(tags),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(expression: &str, tags: &[&str]) -> bool {
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        parse(expression).unwrap().evaluate(&tags)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert!(matches("", &[]));
+        assert!(matches("@a", &["@a", "@b"]));
+        assert!(!matches("not @a", &["@a"]));
+        // and binds tighter than or
+        assert!(matches("@a or @b and @c", &["@a"]));
+        assert!(!matches("(@a or @b) and @c", &["@a"]));
+        assert!(matches("@smoke and not (@broken or @slow)", &["@smoke"]));
+        assert!(!matches(
+            "@smoke and not (@broken or @slow)",
+            &["@smoke", "@slow"]
+        ));
+        assert!(matches("not not @a", &["@a"]));
+        assert!(matches(r"@a\(1\) or @b\ c", &["@b c"]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in ["@a and", "(@a", "@a)", "or @a", "@a @b", r"@a\x", "@a \\"] {
+            assert!(parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+const SETTINGS_KEY: &str = "toolchain";
+// Slow shell startup files shouldn't hold up the first run for long
+const SHELL_TIMEOUT: Duration = Duration::from_secs(5);
+const PATH_MARKER: &str = "__PROTESTIFY_PATH__";
+
+/// Binaries the user picked explicitly, they win over anything found on PATH
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ToolchainSettings {
+    pub node: Option<String>,
+    pub npx: Option<String>,
+    pub pnpm: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionManager {
+    Nvm,
+    Volta,
+    Fnm,
+    Asdf,
+}
+
+/// Where the version managers keep their data, `None` when not installed
+#[derive(Debug, Clone, Default)]
+struct ManagerRoots {
+    nvm: Option<PathBuf>,
+    volta: Option<PathBuf>,
+    fnm: Option<PathBuf>,
+    asdf: Option<PathBuf>,
+}
+
+impl ManagerRoots {
+    fn from_env() -> Self {
+        let root = |var: &str, default: Option<PathBuf>| {
+            env::var_os(var)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+                .or(default)
+                .filter(|dir| dir.is_dir())
+        };
+        let home = dirs::home_dir();
+        ManagerRoots {
+            nvm: root("NVM_DIR", home.as_ref().map(|h| h.join(".nvm"))),
+            volta: root("VOLTA_HOME", home.as_ref().map(|h| h.join(".volta"))),
+            fnm: root("FNM_DIR", dirs::data_dir().map(|d| d.join("fnm")))
+                .or_else(|| root("FNM_DIR", home.as_ref().map(|h| h.join(".fnm")))),
+            asdf: root("ASDF_DATA_DIR", home.as_ref().map(|h| h.join(".asdf"))),
+        }
+    }
+
+    fn managers(&self) -> Vec<VersionManager> {
+        [
+            (VersionManager::Nvm, &self.nvm),
+            (VersionManager::Volta, &self.volta),
+            (VersionManager::Fnm, &self.fnm),
+            (VersionManager::Asdf, &self.asdf),
+        ]
+        .into_iter()
+        .filter(|(_, root)| root.is_some())
+        .map(|(manager, _)| manager)
+        .collect()
+    }
+
+    /// `(version, bin dir)` of every Node install the managers know about
+    fn installs(&self) -> Vec<(String, PathBuf)> {
+        let mut installs = Vec::new();
+        let mut add = |dir: Option<PathBuf>, bin: &str| {
+            let Some(entries) = dir.and_then(|dir| fs::read_dir(dir).ok()) else {
+                return;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let bin_dir = entry.path().join(bin);
+                if bin_dir.is_dir() {
+                    let version = entry.file_name().to_string_lossy().to_string();
+                    installs.push((version, bin_dir));
+                }
+            }
+        };
+        add(self.nvm.as_ref().map(|r| r.join("versions/node")), "bin");
+        add(
+            self.fnm.as_ref().map(|r| r.join("node-versions")),
+            "installation/bin",
+        );
+        add(self.asdf.as_ref().map(|r| r.join("installs/nodejs")), "bin");
+        installs
+    }
+
+    /// Shim folders that pick the Node version by themselves
+    fn shims(&self) -> Vec<PathBuf> {
+        [
+            self.volta.as_ref().map(|r| r.join("bin")),
+            self.asdf.as_ref().map(|r| r.join("shims")),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|dir| dir.is_dir())
+        .collect()
+    }
+}
+
+/// Node version the repo pins and the file that pins it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PinnedVersion {
+    pub version: String,
+    pub source: String,
+}
+
+/// How processes for an e2e repo are started
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Toolchain {
+    /// PATH given to spawned processes, most specific entries first
+    pub path: Vec<PathBuf>,
+    pub managers: Vec<VersionManager>,
+    pub pinned: Option<PinnedVersion>,
+    /// Bin dir of the installed Node matching the pinned version
+    pub node_dir: Option<PathBuf>,
+    pub node: Option<PathBuf>,
+    pub npx: Option<PathBuf>,
+    pub pnpm: Option<PathBuf>,
+    /// Cucumber runs through `pnpm exec` instead of `npx`
+    pub use_pnpm: bool,
+    pub settings: ToolchainSettings,
+    /// Why the login shell's PATH is not part of `path`
+    pub shell_path_error: Option<String>,
+}
+
+lazy_static! {
+    static ref SETTINGS: RwLock<ToolchainSettings> = RwLock::new(ToolchainSettings::default());
+}
+
+static SHELL_PATH: OnceLock<Result<Vec<PathBuf>, String>> = OnceLock::new();
+
+/// Use `settings` for every process started from now on.
+pub fn configure(settings: ToolchainSettings) {
+    *SETTINGS.write().unwrap() = settings;
+}
+
+pub fn load_settings(connection: &Connection) -> Result<ToolchainSettings, String> {
+    let value: Option<String> = connection
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![SETTINGS_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+pub fn save_settings(connection: &Connection, settings: &ToolchainSettings) -> Result<(), String> {
+    for binary in [&settings.node, &settings.npx, &settings.pnpm]
+        .into_iter()
+        .flatten()
+    {
+        if !Path::new(binary).is_file() {
+            return Err(format!("Binary not found: {}", binary));
+        }
+    }
+    let value = serde_json::to_string(settings).map_err(|e| e.to_string())?;
+    connection
+        .execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![SETTINGS_KEY, value],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// PATH between the markers in the login shell's output, rc files may print around it
+fn marked_path(output: &str) -> Option<Vec<PathBuf>> {
+    let start = output.find(PATH_MARKER)? + PATH_MARKER.len();
+    let end = start + output[start..].find(PATH_MARKER)?;
+    Some(env::split_paths(&output[start..end]).collect())
+}
+
+/// PATH as an interactive login shell of the user sets it up, including the lines
+/// version managers add to the rc files. Apps started from the desktop don't get it.
+#[cfg(unix)]
+fn read_login_shell_path() -> Result<Vec<PathBuf>, String> {
+    let shell = env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .ok_or("SHELL is not set")?;
+    read_shell_path(&shell)
+}
+
+#[cfg(unix)]
+fn read_shell_path(shell: &str) -> Result<Vec<PathBuf>, String> {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+
+    let script = if shell.ends_with("fish") {
+        format!("printf '{0}%s{0}' (string join : $PATH)", PATH_MARKER)
+    } else {
+        format!("printf '{0}%s{0}' \"$PATH\"", PATH_MARKER)
+    };
+    let mut child = Command::new(shell)
+        .args(["-ilc", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", shell, e))?;
+
+    // The output ends when the shell exits
+    let mut stdout = child.stdout.take().ok_or("No shell output")?;
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stdout.read_to_string(&mut output);
+        let _ = sender.send(output);
+    });
+    let Ok(output) = receiver.recv_timeout(SHELL_TIMEOUT) else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(format!("Reading PATH from {} timed out", shell));
+    };
+    let _ = child.wait();
+    marked_path(&output).ok_or_else(|| format!("{} did not print its PATH", shell))
+}
+
+#[cfg(windows)]
+fn read_login_shell_path() -> Result<Vec<PathBuf>, String> {
+    // GUI apps on Windows get the same PATH as a new terminal
+    Ok(Vec::new())
+}
+
+/// Read the login shell's PATH for every process started from now on. Slow rc files
+/// make this take seconds, so call it once at startup off the async runtime.
+pub fn load_shell_path() {
+    SHELL_PATH.get_or_init(read_login_shell_path);
+}
+
+/// Why the login shell's PATH is not in use, `None` once it is.
+pub fn shell_path_error() -> Option<String> {
+    match SHELL_PATH.get() {
+        None => Some("The login shell's PATH is still being read".to_string()),
+        Some(Err(e)) => Some(e.clone()),
+        Some(Ok(_)) => None,
+    }
+}
+
+fn push_unique(path: &mut Vec<PathBuf>, entries: impl IntoIterator<Item = PathBuf>) {
+    for entry in entries {
+        if !entry.as_os_str().is_empty() && !path.contains(&entry) {
+            path.push(entry);
+        }
+    }
+}
+
+/// The login shell's PATH, once `load_shell_path` read it, followed by the inherited one.
+pub fn search_path() -> Vec<PathBuf> {
+    let mut path = Vec::new();
+    if let Some(Ok(shell)) = SHELL_PATH.get() {
+        push_unique(&mut path, shell.iter().cloned());
+    }
+    if let Some(inherited) = env::var_os("PATH") {
+        push_unique(&mut path, env::split_paths(&inherited));
+    }
+    path
+}
+
+/// First executable named `name` in `path`, with the Windows script extensions.
+pub fn find_in(path: &[PathBuf], name: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &[".cmd", ".exe", ""]
+    } else {
+        &[""]
+    };
+    path.iter().find_map(|dir| {
+        extensions
+            .iter()
+            .map(|extension| dir.join(format!("{}{}", name, extension)))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Whether `actual` (`v20.11.1`) satisfies a pinned version like `20`, `v20.11` or
+/// `20.11.1`. Aliases such as `lts/iron` or `node` can't be checked and give `None`.
+pub fn version_matches(required: &str, actual: &str) -> Option<bool> {
+    let required = parse_version(required)?;
+    let actual = parse_version(actual)?;
+    Some(required.len() <= actual.len() && required.iter().zip(&actual).all(|(r, a)| r == a))
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Node version pinned by `.nvmrc`, `.node-version`, `.tool-versions` or the volta
+/// section of `package.json`, in that order.
+pub fn pinned_version(repo: &Path) -> Option<PinnedVersion> {
+    let read = |file: &str| fs::read_to_string(repo.join(file)).ok();
+    let pinned = |version: &str, source: &str| {
+        let version = version.trim();
+        (!version.is_empty()).then(|| PinnedVersion {
+            version: version.to_string(),
+            source: source.to_string(),
+        })
+    };
+
+    for file in [".nvmrc", ".node-version"] {
+        if let Some(version) =
+            read(file).and_then(|c| c.lines().next().and_then(|l| pinned(l, file)))
+        {
+            return Some(version);
+        }
+    }
+    if let Some(content) = read(".tool-versions") {
+        let version = content.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next() == Some("nodejs"))
+                .then(|| parts.next())
+                .flatten()
+        });
+        if let Some(version) = version.and_then(|v| pinned(v, ".tool-versions")) {
+            return Some(version);
+        }
+    }
+    let package: Value = serde_json::from_str(&read("package.json")?).ok()?;
+    pinned(package.pointer("/volta/node")?.as_str()?, "package.json")
+}
+
+fn resolve_with(
+    repo: &Path,
+    settings: ToolchainSettings,
+    roots: &ManagerRoots,
+    search_path: Vec<PathBuf>,
+) -> Toolchain {
+    let pinned = pinned_version(repo);
+    // Newest install that satisfies the pin
+    let node_dir = pinned.as_ref().and_then(|pinned| {
+        roots
+            .installs()
+            .into_iter()
+            .filter(|(version, _)| version_matches(&pinned.version, version) == Some(true))
+            .max_by_key(|(version, _)| parse_version(version))
+            .map(|(_, bin)| bin)
+    });
+
+    let mut path = Vec::new();
+    let configured_node_dir = settings
+        .node
+        .as_ref()
+        .and_then(|node| Path::new(node).parent().map(Path::to_path_buf));
+    push_unique(&mut path, configured_node_dir);
+    push_unique(&mut path, node_dir.clone());
+    push_unique(&mut path, roots.shims());
+    push_unique(&mut path, search_path);
+
+    let binary = |configured: &Option<String>, name: &str| {
+        configured
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| find_in(&path, name))
+    };
+    let node = binary(&settings.node, "node");
+    let npx = binary(&settings.npx, "npx");
+    let pnpm = binary(&settings.pnpm, "pnpm");
+    let use_pnpm =
+        settings.pnpm.is_some() || (repo.join("pnpm-lock.yaml").is_file() && pnpm.is_some());
+
+    Toolchain {
+        path,
+        managers: roots.managers(),
+        pinned,
+        node_dir,
+        node,
+        npx,
+        pnpm,
+        use_pnpm,
+        settings,
+        shell_path_error: None,
+    }
+}
+
+/// Work out how to start Node tools in the e2e repo at `repo`.
+pub fn resolve(repo: &Path) -> Toolchain {
+    let settings = SETTINGS.read().unwrap().clone();
+    Toolchain {
+        shell_path_error: shell_path_error(),
+        ..resolve_with(repo, settings, &ManagerRoots::from_env(), search_path())
+    }
+}
+
+impl Toolchain {
+    fn program(&self, name: &str) -> OsString {
+        let resolved = match name {
+            "node" => self.node.clone(),
+            "npx" => self.npx.clone(),
+            "pnpm" => self.pnpm.clone(),
+            _ => find_in(&self.path, name),
+        };
+        resolved.map(OsString::from).unwrap_or_else(|| name.into())
+    }
+
+    fn joined_path(&self) -> Option<OsString> {
+        if self.path.is_empty() {
+            return None;
+        }
+        env::join_paths(&self.path).ok()
+    }
+
+    /// A command for `name` that finds its binaries and Node on the resolved PATH.
+    pub fn command(&self, name: &str) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(self.program(name));
+        if let Some(path) = self.joined_path() {
+            command.env("PATH", path);
+        }
+        command
+    }
+
+    /// Like `command`, for callers off the async runtime.
+    pub fn blocking_command(&self, name: &str) -> std::process::Command {
+        let mut command = std::process::Command::new(self.program(name));
+        if let Some(path) = self.joined_path() {
+            command.env("PATH", path);
+        }
+        command
+    }
+
+    /// Program and leading arguments that run a binary from the repo's node_modules
+    pub fn package_runner(&self) -> (&'static str, Vec<String>) {
+        if self.use_pnpm {
+            ("pnpm", vec!["exec".to_string()])
+        } else {
+            ("npx", Vec::new())
+        }
+    }
+}
+
+/// A blocking command for tools outside the repo's Node toolchain, like git.
+pub fn std_command(name: &str) -> std::process::Command {
+    let path = search_path();
+    let mut command = std::process::Command::new(
+        find_in(&path, name)
+            .map(OsString::from)
+            .unwrap_or_else(|| name.into()),
+    );
+    if let Ok(path) = env::join_paths(&path) {
+        command.env("PATH", path);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_version_matches() {
+        assert_eq!(version_matches("20", "v20.11.1"), Some(true));
+        assert_eq!(version_matches("v20.11", "v20.11.1"), Some(true));
+        assert_eq!(version_matches("20.12", "v20.11.1"), Some(false));
+        assert_eq!(version_matches("18", "v20.11.1"), Some(false));
+        assert_eq!(version_matches("lts/iron", "v20.11.1"), None);
+    }
+
+    #[test]
+    fn test_marked_path() {
+        let output = format!(
+            "Welcome back!\n{0}/home/u/.nvm/versions/node/v20.11.1/bin:/usr/bin{0}",
+            PATH_MARKER
+        );
+        assert_eq!(
+            marked_path(&output),
+            Some(vec![
+                PathBuf::from("/home/u/.nvm/versions/node/v20.11.1/bin"),
+                PathBuf::from("/usr/bin")
+            ])
+        );
+        assert_eq!(marked_path("no markers"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_shell_path() {
+        assert!(!read_shell_path("/bin/sh").unwrap().is_empty());
+        assert!(read_shell_path("/nonexistent/sh")
+            .unwrap_err()
+            .starts_with("Failed to start /nonexistent/sh"));
+    }
+
+    #[test]
+    fn test_pinned_version() {
+        let dir = tempdir().unwrap();
+        assert_eq!(pinned_version(dir.path()), None);
+
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"volta": {"node": "18.19.0"}}"#,
+        )
+        .unwrap();
+        assert_eq!(pinned_version(dir.path()).unwrap().version, "18.19.0");
+
+        fs::write(
+            dir.path().join(".tool-versions"),
+            "python 3.12\nnodejs 20.10.0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            pinned_version(dir.path()),
+            Some(PinnedVersion {
+                version: "20.10.0".into(),
+                source: ".tool-versions".into()
+            })
+        );
+
+        fs::write(dir.path().join(".nvmrc"), "v20\n").unwrap();
+        assert_eq!(pinned_version(dir.path()).unwrap().source, ".nvmrc");
+    }
+
+    #[test]
+    fn test_resolve_prefers_pinned_install() {
+        let repo = tempdir().unwrap();
+        let nvm = tempdir().unwrap();
+        fs::write(repo.path().join(".nvmrc"), "20\n").unwrap();
+        for version in ["v18.19.0", "v20.9.0", "v20.11.1"] {
+            let bin = nvm.path().join("versions/node").join(version).join("bin");
+            fs::create_dir_all(&bin).unwrap();
+            fs::write(bin.join("node"), "").unwrap();
+            fs::write(bin.join("npx"), "").unwrap();
+        }
+        let roots = ManagerRoots {
+            nvm: Some(nvm.path().to_path_buf()),
+            ..ManagerRoots::default()
+        };
+        let settings = ToolchainSettings {
+            pnpm: Some("/opt/pnpm/pnpm".into()),
+            ..ToolchainSettings::default()
+        };
+
+        let toolchain = resolve_with(
+            repo.path(),
+            settings,
+            &roots,
+            vec![PathBuf::from("/usr/bin")],
+        );
+
+        let expected = nvm.path().join("versions/node/v20.11.1/bin");
+        assert_eq!(toolchain.managers, vec![VersionManager::Nvm]);
+        assert_eq!(toolchain.node_dir.as_ref(), Some(&expected));
+        assert_eq!(
+            toolchain.path,
+            vec![expected.clone(), PathBuf::from("/usr/bin")]
+        );
+        assert_eq!(toolchain.npx, Some(expected.join("npx")));
+        assert_eq!(toolchain.pnpm, Some(PathBuf::from("/opt/pnpm/pnpm")));
+        assert_eq!(
+            toolchain.package_runner(),
+            ("pnpm", vec!["exec".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let connection = crate::database::open_in_memory();
+        assert_eq!(
+            load_settings(&connection).unwrap(),
+            ToolchainSettings::default()
+        );
+
+        let dir = tempdir().unwrap();
+        let node = dir.path().join("node");
+        fs::write(&node, "").unwrap();
+        let settings = ToolchainSettings {
+            node: Some(node.to_string_lossy().to_string()),
+            ..ToolchainSettings::default()
+        };
+        save_settings(&connection, &settings).unwrap();
+        assert_eq!(load_settings(&connection).unwrap(), settings);
+
+        let missing = ToolchainSettings {
+            npx: Some("/does/not/exist/npx".into()),
+            ..ToolchainSettings::default()
+        };
+        assert!(save_settings(&connection, &missing).is_err());
+    }
+}
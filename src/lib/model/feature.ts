@@ -74,6 +74,6 @@ export default class Feature extends Model {
   }
 
   get isBroken() {
-    return this.tags.some((s) => s.includes('broken'));
+    return this.tags.some((s) => s === '@broken');
   }
 }
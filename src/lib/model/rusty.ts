@@ -16,6 +16,22 @@ interface RustyOrganization {
   users: Record<string, string>;
 }
 
+interface RustyAuthorship {
+  created_by: string;
+  created_at: number;
+  last_modified_by: string;
+  last_modified_at: number;
+  commit_count: number;
+  /** The file has uncommitted edits, its scenarios have no authorship */
+  uncommitted: boolean;
+}
+
+interface RustyReferences {
+  tickets: string[];
+  links: string[];
+  quarantine_tickets: string[];
+}
+
 interface RustyScenario {
   id: string;
   name: string;
@@ -26,6 +42,11 @@ interface RustyScenario {
   tags: string[]
   feature_id: string;
   organization_name: string;
+  line: number;
+  end_line: number;
+  comments: string[];
+  references: RustyReferences;
+  authorship: RustyAuthorship | null;
 }
 
 interface RustyFeature {
@@ -34,6 +55,9 @@ interface RustyFeature {
   description: string,
   file_path: string,
   tags: string[],
+  comments: string[],
+  references: RustyReferences,
+  authorship: RustyAuthorship | null,
 }
 
 interface RustyFindE2eRepo {
@@ -43,7 +67,225 @@ interface RustyFindE2eRepo {
   password: string;
 }
 
+interface RustyRunOutput {
+  run_id: string;
+  stream: 'stdout' | 'stderr';
+  line: string;
+}
+
+interface RustyRunStart {
+  run_id: string;
+  folder_path: string;
+  target: string;
+  command_line: string;
+  options: RustyRunOptions;
+  started_at: number;
+}
+
+interface RustyRunExit {
+  run_id: string;
+  success: boolean;
+  code: number | null;
+  status: string;
+  duration_ms: number;
+  cancelled: boolean;
+  report_path: string | null;
+  results: RustyScenarioResult[] | null;
+  report_error: string | null;
+}
+
+type RustyResultStatus = 'passed' | 'skipped' | 'pending' | 'undefined' | 'ambiguous' | 'failed';
+
+interface RustyStepResult {
+  keyword: string;
+  name: string;
+  line: number | null;
+  status: RustyResultStatus;
+  seconds: number;
+  error_message: string | null;
+  stack_trace: string | null;
+  attachments: { media_type: string; data: string }[];
+}
+
+interface RustyScenarioResult {
+  key: string;
+  uri: string;
+  line: number;
+  feature_name: string;
+  name: string;
+  status: RustyResultStatus;
+  seconds: number;
+  steps: RustyStepResult[];
+  scenario_line: number | null;
+  scenario_key: string | null;
+}
+
+/** Payload of `e2e-run-history-error` */
+interface RustyHistoryError {
+  run_id: string;
+  message: string;
+}
+
+interface RustyRunRecord {
+  id: string;
+  repo_path: string;
+  target: string;
+  command_line: string;
+  /** Env values are stored as `<redacted>` */
+  options: RustyRunOptions;
+  git: { commit: string | null; branch: string | null; dirty: boolean };
+  started_at: number;
+  ended_at: number | null;
+  exit_code: number | null;
+  status: 'running' | 'passed' | 'failed' | 'cancelled' | 'interrupted';
+  log_path: string | null;
+  report_path: string | null;
+  parent_run_id: string | null;
+}
+
+interface RustyLastResult {
+  uri: string;
+  scenario_line: number;
+  name: string;
+  status: RustyResultStatus;
+  seconds: number;
+  run_id: string;
+  ended_at: number;
+}
+
+interface RustyFlakyScenario {
+  key: string;
+  uri: string;
+  scenario_line: number;
+  name: string;
+  score: number;
+  runs: number;
+  failures: number;
+  flips: number;
+  retry_passes: number;
+  last_flip_at: number | null;
+  failure_messages: { message: string; count: number }[];
+}
+
+interface RustyActiveRun {
+  run_id: string;
+  pid: number | null;
+  started_at: number;
+  target: string;
+  cancelling: boolean;
+}
+
+interface RustyBrowserFlags {
+  headed: boolean;
+  debug: boolean;
+  slow_mo_ms: number | null;
+  keep_open: boolean;
+  trace: boolean;
+}
+
+interface RustyEnvVar {
+  name: string;
+  value: string;
+}
+
+interface RustyBrowserEnv {
+  headed: RustyEnvVar;
+  debug: RustyEnvVar;
+  slow_mo: RustyEnvVar;
+  keep_open: RustyEnvVar;
+  trace: RustyEnvVar;
+}
+
+interface RustyRunOptions {
+  profile: string | null;
+  extra_args: string[];
+  env: Record<string, string>;
+  browser: RustyBrowserFlags;
+}
+
+interface RustyRunPreset {
+  name: string;
+  options: RustyRunOptions;
+}
+
+interface RustyQueueItem {
+  id: string;
+  folder_path: string;
+  feature_file: string;
+  scenario_name: string | null;
+  options: RustyRunOptions;
+  enqueued_at: number;
+}
+
+interface RustyQueueStatus {
+  paused: boolean;
+  max_concurrency: number;
+  running: { item: RustyQueueItem; run_id: string }[];
+  pending: RustyQueueItem[];
+  failed: { item: RustyQueueItem; error: string }[];
+  error: string | null;
+}
+
+type RustySelection =
+  | { kind: 'feature'; file: string }
+  | { kind: 'scenario'; file: string; line: number }
+  | { kind: 'example_row'; file: string; line: number }
+  | { kind: 'tags'; expression: string };
+
+interface RustyPreflightCheck {
+  id: string;
+  label: string;
+  status: 'ok' | 'warning' | 'error' | 'skipped';
+  detail: string;
+  hint: string | null;
+}
+
+interface RustyPreflight {
+  ok: boolean;
+  checks: RustyPreflightCheck[];
+}
+
+interface RustyToolchainSettings {
+  node: string | null;
+  npx: string | null;
+  pnpm: string | null;
+}
+
+interface RustyToolchain {
+  path: string[];
+  managers: ('nvm' | 'volta' | 'fnm' | 'asdf')[];
+  pinned: { version: string; source: string } | null;
+  node_dir: string | null;
+  node: string | null;
+  npx: string | null;
+  pnpm: string | null;
+  use_pnpm: boolean;
+  settings: RustyToolchainSettings;
+  shell_path_error: string | null;
+}
+
+/** Load `path` with `convertFileSrc` from `@tauri-apps/api/core` */
+interface RustyArtifact {
+  run_id: string;
+  path: string;
+  kind: 'screenshot' | 'video' | 'trace' | 'log' | 'other';
+  size: number;
+  modified_at: number;
+  scenario_key: string | null;
+  /** Written while another run of the repo was active */
+  shared: boolean;
+}
+
+type RustyReportFormat = 'junit' | 'html';
+
 type InvokeErr = { err: string };
 type InvokeFindE2eRepo = { ok: RustyFindE2eRepo };
 type InvokeGetOrganizations = { people: RustyPerson[]; teams: RustyTeam[]; organizations: RustyOrganization[]; };
 type InvokeGetFeatures = { features: RustyFeature[]; scenarios: RustyScenario[]; }
+type InvokeRunE2e = { ok: string };
+type InvokeRunSelected = { ok: { run_id: string; warnings: string[] } };
+type InvokeQueueStatus = { ok: RustyQueueStatus };
+type InvokePreflight = { ok: RustyPreflight };
+type InvokeToolchain = { ok: RustyToolchain };
+type InvokeRunArtifacts = { ok: RustyArtifact[] };
+type InvokeBrowserEnv = { ok: RustyBrowserEnv };
@@ -114,6 +114,6 @@ export default class Scenario extends Model {
   }
 
   get isBroken() {
-    return this.allTags.some((s) => s.includes('broken'));
+    return this.allTags.some((s) => s === '@broken');
   }
 }
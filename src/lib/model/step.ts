@@ -2,6 +2,7 @@ export interface StepDefinition {
   id: string;
   keyword: 'Given' | 'When' | 'Then' | 'And' | 'But';
   pattern: string;
+  is_regex: boolean;
   file_path: string;
   line_number: number;
   category: string;
@@ -0,0 +1,28 @@
+export interface ScenarioKey {
+  file: string;
+  name: string;
+}
+
+export interface Suite {
+  name: string;
+  description: string;
+  scenarios: ScenarioKey[];
+  tag_expression: string | null;
+  teams: string[];
+  owners: string[];
+  organizations: string[];
+  screens: string[];
+}
+
+export interface ResolvedScenario {
+  file_path: string;
+  line: number;
+  name: string;
+  feature_name: string;
+}
+
+export interface ResolvedSuite {
+  name: string;
+  scenarios: ResolvedScenario[];
+  missing: ScenarioKey[];
+}
@@ -66,6 +66,9 @@ export class Store {
     this.parseFeatures(data.features);
     this.parseScenarios(data.scenarios);
 
+    // Trend snapshot for /graphs, a failure here should not block the catalog
+    invoke('record_metrics_snapshot', { basePath }).catch(() => {});
+
     this.isLoaded = true;
   }
 